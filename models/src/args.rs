@@ -0,0 +1,229 @@
+//! Argument objects for Tauri commands.
+//!
+//! Every backend command that takes arguments receives one of these as its `args` parameter,
+//! and the frontend sends the same struct under that key, so both sides share one definition.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Availability, ContactDetails, ContactFormat, ImageSource, LogFormat, SearchQuery, Settings,
+    SoundEvent,
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateUsernameArgs {
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateFriendArgs {
+    pub email: String,
+    pub name: Option<String>,
+    pub status: Option<String>,
+    pub availability: Option<Availability>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddFriendArgs {
+    pub name: String,
+    pub email: String,
+    pub status: Option<String>,
+    pub availability: Option<Availability>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateContactArgs {
+    pub email: String,
+    pub details: ContactDetails,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportContactsArgs {
+    pub path: String,
+    pub format: ContactFormat,
+    pub dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportContactsArgs {
+    pub path: String,
+    pub format: ContactFormat,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetConversationArgs {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendMessageArgs {
+    pub email: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusChatArgs {
+    pub email: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateSettingsArgs {
+    pub settings: Settings,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaySoundArgs {
+    pub event: SoundEvent,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendNudgeArgs {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendTypingArgs {
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddCustomEmoticonArgs {
+    pub path: String,
+    pub shortcut: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveCustomEmoticonArgs {
+    pub shortcut: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendFileArgs {
+    pub email: String,
+    pub path: String,
+}
+
+/// Arguments of the commands that act on a single file transfer.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileTransferArgs {
+    pub id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListFileTransfersArgs {
+    pub email: String,
+}
+
+/// A `source` of `None` removes the display picture.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDisplayPictureArgs {
+    pub source: Option<ImageSource>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SetAvailabilityArgs {
+    pub availability: Availability,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendImageArgs {
+    pub email: String,
+    pub source: ImageSource,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendVoiceClipArgs {
+    pub email: String,
+    /// Mono 16-bit little-endian PCM, base64-encoded.
+    pub samples: String,
+    pub sample_rate: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InviteToConversationArgs {
+    pub conversation: String,
+    pub email: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LeaveConversationArgs {
+    pub conversation: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChatWindowArgs {
+    pub conversation: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchMessagesArgs {
+    pub query: SearchQuery,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShowMessageArgs {
+    pub conversation: String,
+    pub message_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditMessageArgs {
+    pub conversation: String,
+    pub message_id: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteMessageArgs {
+    pub conversation: String,
+    pub message_id: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportConversationArgs {
+    /// The conversation to save, or `None` to save every conversation into the folder at `path`.
+    pub conversation: Option<String>,
+    pub path: String,
+    pub format: LogFormat,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportMsnLogArgs {
+    pub path: String,
+    /// Email of the friend the log is a conversation with.
+    pub conversation: String,
+    /// Names in the log the user sent messages under.
+    pub my_names: Vec<String>,
+    pub dry_run: bool,
+}
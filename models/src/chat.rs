@@ -0,0 +1,386 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{emoticons::CustomEmoticon, AppError, FileOffer};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ChatMessage {
+    pub id: String,
+    /// Email of the sender, which is the user's own email for outgoing messages.
+    pub from: String,
+    pub body: String,
+    /// Milliseconds since the Unix epoch, as measured by the sender.
+    pub sent_at: u64,
+    #[serde(default)]
+    pub kind: MessageKind,
+    /// The sender's custom emoticons that appear in `body`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emoticons: Vec<CustomEmoticon>,
+    /// How the sender wanted the text to look, rendered through `TextStyle::to_css`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<TextStyle>,
+    /// The file offered by a `MessageKind::File` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<FileOffer>,
+    /// The picture shared by a `MessageKind::Image` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<SharedImage>,
+    /// The recording sent by a `MessageKind::Voice` message.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voice: Option<VoiceClip>,
+    /// When the sender last changed the text, in milliseconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<u64>,
+    /// The sender took the message back. Only its place in the conversation is kept.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+    /// How far a message the user sent to a friend got. Not tracked for received messages or in
+    /// group conversations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<MessageStatus>,
+}
+
+impl ChatMessage {
+    /// Whether the sender can still edit or delete the message at `now`, in milliseconds since
+    /// the Unix epoch, given their `Settings::edit_window_minutes`.
+    pub fn editable_at(&self, now: u64, edit_window_minutes: Option<u32>) -> bool {
+        !self.deleted
+            && edit_window_minutes.is_none_or(|minutes| {
+                now <= self.sent_at.saturating_add(u64::from(minutes) * 60 * 1000)
+            })
+    }
+
+    /// Checks that the user, whose email is `me`, may edit or delete the message at `now`.
+    pub fn check_editable(
+        &self,
+        me: &str,
+        now: u64,
+        edit_window_minutes: Option<u32>,
+    ) -> Result<(), AppError> {
+        if self.from != me {
+            return Err(AppError::Auth(
+                "Only your own messages can be changed".to_string(),
+            ));
+        }
+        if !self.editable_at(now, edit_window_minutes) {
+            return Err(AppError::Validation(
+                "This message can't be changed anymore".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Empties a deleted message, keeping only who sent it and when.
+    pub fn delete(&mut self) {
+        self.body.clear();
+        self.emoticons.clear();
+        self.style = None;
+        self.image = None;
+        self.voice = None;
+        self.deleted = true;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum MessageKind {
+    #[default]
+    Text,
+    /// Shakes the receiver's chat window and shows as a system line instead of a message.
+    Nudge,
+    /// Offers the friend the file in `ChatMessage::file`, shown as a card with the transfer's
+    /// progress.
+    File,
+    /// Shares the picture in `ChatMessage::image`, shown as a thumbnail that opens full size.
+    Image,
+    /// Plays the recording in `ChatMessage::voice`.
+    Voice,
+    /// `from` joined a group conversation, under the name in `body`. Shown as a system line.
+    Joined,
+    /// `from` left a group conversation, under the name in `body`. Shown as a system line.
+    Left,
+}
+
+/// How far a message got, acknowledged by the friend it was sent to.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessageStatus {
+    /// Left for the friend's app.
+    Sent,
+    /// The friend's app has it in their conversation.
+    Delivered,
+    /// The friend has seen it, and lets the user know.
+    Read,
+}
+
+/// A picture sent in a conversation, stored by the hex SHA-256 of its content.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SharedImage {
+    pub hash: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A voice clip recorded in the chat window, stored as Ogg Opus by the hex SHA-256 of the file.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct VoiceClip {
+    pub hash: String,
+    pub duration_ms: u32,
+    /// Loudness of consecutive slices of the clip, from 0 to 100, drawn as the clip's waveform.
+    pub waveform: Vec<u8>,
+}
+
+/// Where a picture to send comes from.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ImageSource {
+    /// A file picked or dropped onto the chat.
+    Path(String),
+    /// A pasted image, base64-encoded.
+    Data(String),
+}
+
+/// The font, color and emphasis the user writes their messages in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct TextStyle {
+    pub font: String,
+    /// A `#rrggbb` color.
+    pub color: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            font: TextStyle::FONTS[0].to_string(),
+            color: "#000000".to_string(),
+            bold: false,
+            italic: false,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+}
+
+impl TextStyle {
+    /// The fonts a style may use, which are also the only ones a friend's style can show in.
+    pub const FONTS: &'static [&'static str] = &[
+        "Segoe UI",
+        "Arial",
+        "Comic Sans MS",
+        "Courier New",
+        "Georgia",
+        "Lucida Console",
+        "Tahoma",
+        "Times New Roman",
+        "Trebuchet MS",
+        "Verdana",
+    ];
+
+    pub fn validate(&self) -> Result<(), AppError> {
+        if !TextStyle::FONTS.contains(&self.font.as_str()) {
+            return Err(AppError::Validation(format!(
+                "{} is not one of the available fonts",
+                self.font
+            )));
+        }
+        if !is_hex_color(&self.color) {
+            return Err(AppError::Validation(format!(
+                "{} is not a color like #1a2b3c",
+                self.color
+            )));
+        }
+        Ok(())
+    }
+
+    /// Inline CSS for the style.
+    ///
+    /// Styles arrive from friends, so anything that isn't a known font or a plain `#rrggbb` color
+    /// is left out rather than copied into the page, and the text size never changes.
+    pub fn to_css(&self) -> String {
+        let mut css = String::new();
+        if TextStyle::FONTS.contains(&self.font.as_str()) {
+            css.push_str(&format!("font-family: '{}', sans-serif;", self.font));
+        }
+        if is_hex_color(&self.color) {
+            css.push_str(&format!("color: {};", self.color));
+        }
+        if self.bold {
+            css.push_str("font-weight: bold;");
+        }
+        if self.italic {
+            css.push_str("font-style: italic;");
+        }
+        match (self.underline, self.strikethrough) {
+            (true, true) => css.push_str("text-decoration: underline line-through;"),
+            (true, false) => css.push_str("text-decoration: underline;"),
+            (false, true) => css.push_str("text-decoration: line-through;"),
+            (false, false) => {}
+        }
+        css
+    }
+}
+
+fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Payload of the `events::CHAT_MESSAGE` event.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ChatMessageEvent {
+    /// Email of the friend the conversation is with, or the id of the group.
+    pub conversation: String,
+    pub message: ChatMessage,
+}
+
+/// Payload of the `events::TYPING` event.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TypingEvent {
+    /// Email of the friend the conversation is with.
+    pub conversation: String,
+    /// False once the friend sent their message or stopped typing for a while.
+    pub typing: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(font: &str, color: &str) -> TextStyle {
+        TextStyle {
+            font: font.to_string(),
+            color: color.to_string(),
+            ..Default::default()
+        }
+    }
+
+    const MINUTE: u64 = 60 * 1000;
+
+    fn sent_by(from: &str) -> ChatMessage {
+        ChatMessage {
+            id: "1".to_string(),
+            from: from.to_string(),
+            body: "hi".to_string(),
+            sent_at: 1_000_000,
+            kind: MessageKind::Text,
+            style: None,
+            emoticons: Vec::new(),
+            file: None,
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn editable_up_to_the_end_of_the_window() {
+        let message = sent_by("me@example.com");
+        let end = message.sent_at + 15 * MINUTE;
+        assert!(message.editable_at(message.sent_at, Some(15)));
+        assert!(message.editable_at(end, Some(15)));
+        assert!(!message.editable_at(end + 1, Some(15)));
+        assert!(!message.editable_at(message.sent_at + 1, Some(0)));
+    }
+
+    #[test]
+    fn editable_forever_without_a_window() {
+        let message = sent_by("me@example.com");
+        assert!(message.editable_at(u64::MAX, None));
+        let far_future = ChatMessage {
+            sent_at: u64::MAX - 1,
+            ..sent_by("me@example.com")
+        };
+        assert!(far_future.editable_at(u64::MAX, Some(60)));
+    }
+
+    #[test]
+    fn deleted_message_is_never_editable() {
+        let mut message = sent_by("me@example.com");
+        message.delete();
+        assert!(message.body.is_empty());
+        assert!(!message.editable_at(message.sent_at, None));
+        assert!(matches!(
+            message.check_editable("me@example.com", message.sent_at, None),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn only_the_author_can_change_a_message() {
+        let message = sent_by("friend@example.com");
+        assert!(matches!(
+            message.check_editable("me@example.com", message.sent_at, None),
+            Err(AppError::Auth(_))
+        ));
+        let mine = sent_by("me@example.com");
+        let end = mine.sent_at + 5 * MINUTE;
+        assert_eq!(mine.check_editable("me@example.com", end, Some(5)), Ok(()));
+        assert!(matches!(
+            mine.check_editable("me@example.com", end + 1, Some(5)),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn css_of_a_valid_style() {
+        let css = TextStyle {
+            bold: true,
+            underline: true,
+            strikethrough: true,
+            ..style("Comic Sans MS", "#1a2B3c")
+        }
+        .to_css();
+        assert_eq!(
+            css,
+            "font-family: 'Comic Sans MS', sans-serif;color: #1a2B3c;font-weight: bold;\
+             text-decoration: underline line-through;"
+        );
+    }
+
+    #[test]
+    fn unknown_font_is_dropped() {
+        assert_eq!(style("Wingdings", "#000000").to_css(), "color: #000000;");
+        assert_eq!(style("arial", "#000000").to_css(), "color: #000000;");
+    }
+
+    #[test]
+    fn color_that_is_not_rrggbb_is_dropped() {
+        for color in [
+            "red",
+            "#fff",
+            "#12345g",
+            "#1234567",
+            "rgb(0,0,0)",
+            "#ééé",
+            "",
+        ] {
+            assert_eq!(
+                style("Arial", color).to_css(),
+                "font-family: 'Arial', sans-serif;",
+                "{}",
+                color
+            );
+        }
+    }
+
+    #[test]
+    fn declarations_cannot_be_injected() {
+        let hostile = [
+            style("Arial'; position: fixed; }", "#000000; background: url(x)"),
+            style("Arial;}", "#00000;"),
+            style("Arial\"", "#000;}x"),
+        ];
+        for style in hostile {
+            let css = style.to_css();
+            assert!(!css.contains('}'), "{}", css);
+            assert!(
+                !css.contains("position") && !css.contains("background"),
+                "{}",
+                css
+            );
+            assert_eq!(css.matches(';').count(), 0, "{}", css);
+        }
+    }
+}
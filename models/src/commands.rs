@@ -0,0 +1,60 @@
+//! Names of the commands the backend registers with Tauri.
+//!
+//! The frontend invokes commands only through these constants, and the backend refuses to
+//! compile a handler that is missing from the list, so a renamed command can't go unnoticed.
+//! The backend's tests check the other way round, that everything in `ALL` is registered.
+#![allow(non_upper_case_globals)]
+
+macro_rules! commands {
+    ($($name:ident),* $(,)?) => {
+        $(pub const $name: &str = stringify!($name);)*
+
+        pub const ALL: &[&str] = &[$($name),*];
+    };
+}
+
+commands![
+    get_user,
+    get_friends,
+    update_friend,
+    add_friend,
+    update_contact,
+    import_contacts,
+    export_contacts,
+    update_username,
+    set_display_picture,
+    set_availability,
+    sign_out,
+    get_conversation,
+    send_message,
+    focus_chat,
+    get_unread_counts,
+    get_settings,
+    update_settings,
+    list_sound_packs,
+    play_sound,
+    send_nudge,
+    send_typing,
+    list_custom_emoticons,
+    add_custom_emoticon,
+    remove_custom_emoticon,
+    send_file,
+    accept_file,
+    decline_file,
+    resume_file,
+    cancel_file,
+    list_file_transfers,
+    send_image,
+    send_voice_clip,
+    list_groups,
+    invite_to_conversation,
+    leave_conversation,
+    open_chat_window,
+    close_chat_window,
+    search_messages,
+    show_message,
+    export_conversation,
+    import_msn_log,
+    edit_message,
+    delete_message,
+];
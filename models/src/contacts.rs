@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, Friend};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum ContactFormat {
+    Json,
+    VCard,
+    Csv,
+}
+
+impl ContactFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ContactFormat::Json => "json",
+            ContactFormat::VCard => "vcf",
+            ContactFormat::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ContactUpdate {
+    pub before: Friend,
+    pub after: Friend,
+}
+
+/// What importing a file would do to the friends list, computed before anything is committed.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ImportPreview {
+    pub new: Vec<Friend>,
+    pub updated: Vec<ContactUpdate>,
+    pub duplicates: Vec<Friend>,
+}
+
+/// Checks that `email` looks like an address before it is added to the friends list.
+pub fn validate_email(email: &str) -> Result<(), AppError> {
+    if !email.contains('@') {
+        return Err(AppError::Validation(format!(
+            "{} is not an email address",
+            email
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,639 @@
+//! The classic MSN emoticon shortcuts and a tokenizer that finds them in message bodies.
+//!
+//! Shortcuts are matched case-insensitively, longest first, so `(h)` and `(H)` are the same
+//! emoticon and `<:o)` wins over `:o`. Custom emoticons are matched exactly as they were defined.
+//! Text between backticks is left alone, and a backslash in front of a shortcut or a backtick
+//! keeps it as typed.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq)]
+pub struct Emoticon {
+    /// Name of the emoticon's image in the bundled set.
+    pub id: &'static str,
+    pub title: &'static str,
+    /// Every way of typing the emoticon, the first being the one the picker inserts.
+    pub shortcuts: &'static [&'static str],
+}
+
+/// An emoticon the user made from their own image, shared with friends by the image's hash.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct CustomEmoticon {
+    pub shortcut: String,
+    /// Hex-encoded SHA-256 of the image.
+    pub hash: String,
+}
+
+pub const EMOTICONS: &[Emoticon] = &[
+    Emoticon {
+        id: "smile",
+        title: "Smile",
+        shortcuts: &[":)", ":-)"],
+    },
+    Emoticon {
+        id: "open-mouthed",
+        title: "Open-mouthed",
+        shortcuts: &[":D", ":-D"],
+    },
+    Emoticon {
+        id: "wink",
+        title: "Wink",
+        shortcuts: &[";)", ";-)"],
+    },
+    Emoticon {
+        id: "surprised",
+        title: "Surprised",
+        shortcuts: &[":O", ":-O"],
+    },
+    Emoticon {
+        id: "tongue-out",
+        title: "Tongue out",
+        shortcuts: &[":P", ":-P"],
+    },
+    Emoticon {
+        id: "hot",
+        title: "Hot",
+        shortcuts: &["(H)"],
+    },
+    Emoticon {
+        id: "angry",
+        title: "Angry",
+        shortcuts: &[":@", ":-@"],
+    },
+    Emoticon {
+        id: "embarrassed",
+        title: "Embarrassed",
+        shortcuts: &[":$", ":-$"],
+    },
+    Emoticon {
+        id: "confused",
+        title: "Confused",
+        shortcuts: &[":S", ":-S"],
+    },
+    Emoticon {
+        id: "sad",
+        title: "Sad",
+        shortcuts: &[":(", ":-("],
+    },
+    Emoticon {
+        id: "crying",
+        title: "Crying",
+        shortcuts: &[":'("],
+    },
+    Emoticon {
+        id: "disappointed",
+        title: "Disappointed",
+        shortcuts: &[":|", ":-|"],
+    },
+    Emoticon {
+        id: "devil",
+        title: "Devil",
+        shortcuts: &["(6)"],
+    },
+    Emoticon {
+        id: "angel",
+        title: "Angel",
+        shortcuts: &["(A)"],
+    },
+    Emoticon {
+        id: "red-heart",
+        title: "Red heart",
+        shortcuts: &["(L)"],
+    },
+    Emoticon {
+        id: "broken-heart",
+        title: "Broken heart",
+        shortcuts: &["(U)"],
+    },
+    Emoticon {
+        id: "messenger",
+        title: "Messenger",
+        shortcuts: &["(M)"],
+    },
+    Emoticon {
+        id: "cat",
+        title: "Cat face",
+        shortcuts: &["(@)"],
+    },
+    Emoticon {
+        id: "dog",
+        title: "Dog face",
+        shortcuts: &["(&)"],
+    },
+    Emoticon {
+        id: "sleeping-moon",
+        title: "Sleeping half-moon",
+        shortcuts: &["(S)"],
+    },
+    Emoticon {
+        id: "star",
+        title: "Star",
+        shortcuts: &["(*)"],
+    },
+    Emoticon {
+        id: "film",
+        title: "Filmstrip",
+        shortcuts: &["(~)"],
+    },
+    Emoticon {
+        id: "note",
+        title: "Note",
+        shortcuts: &["(8)"],
+    },
+    Emoticon {
+        id: "email",
+        title: "E-mail",
+        shortcuts: &["(E)"],
+    },
+    Emoticon {
+        id: "rose",
+        title: "Red rose",
+        shortcuts: &["(F)"],
+    },
+    Emoticon {
+        id: "wilted-rose",
+        title: "Wilted rose",
+        shortcuts: &["(W)"],
+    },
+    Emoticon {
+        id: "clock",
+        title: "Clock",
+        shortcuts: &["(O)"],
+    },
+    Emoticon {
+        id: "kiss",
+        title: "Red lips",
+        shortcuts: &["(K)"],
+    },
+    Emoticon {
+        id: "gift",
+        title: "Gift with a bow",
+        shortcuts: &["(G)"],
+    },
+    Emoticon {
+        id: "cake",
+        title: "Birthday cake",
+        shortcuts: &["(^)"],
+    },
+    Emoticon {
+        id: "camera",
+        title: "Camera",
+        shortcuts: &["(P)"],
+    },
+    Emoticon {
+        id: "light-bulb",
+        title: "Light bulb",
+        shortcuts: &["(I)"],
+    },
+    Emoticon {
+        id: "coffee",
+        title: "Coffee cup",
+        shortcuts: &["(C)"],
+    },
+    Emoticon {
+        id: "phone",
+        title: "Telephone receiver",
+        shortcuts: &["(T)"],
+    },
+    Emoticon {
+        id: "hug-left",
+        title: "Left hug",
+        shortcuts: &["({)"],
+    },
+    Emoticon {
+        id: "hug-right",
+        title: "Right hug",
+        shortcuts: &["(})"],
+    },
+    Emoticon {
+        id: "beer",
+        title: "Beer mug",
+        shortcuts: &["(B)"],
+    },
+    Emoticon {
+        id: "martini",
+        title: "Martini glass",
+        shortcuts: &["(D)"],
+    },
+    Emoticon {
+        id: "boy",
+        title: "Boy",
+        shortcuts: &["(Z)"],
+    },
+    Emoticon {
+        id: "girl",
+        title: "Girl",
+        shortcuts: &["(X)"],
+    },
+    Emoticon {
+        id: "thumbs-up",
+        title: "Thumbs up",
+        shortcuts: &["(Y)"],
+    },
+    Emoticon {
+        id: "thumbs-down",
+        title: "Thumbs down",
+        shortcuts: &["(N)"],
+    },
+    Emoticon {
+        id: "vampire-bat",
+        title: "Vampire bat",
+        shortcuts: &[":[", ":-["],
+    },
+    Emoticon {
+        id: "nerd",
+        title: "Nerd",
+        shortcuts: &["8-|"],
+    },
+    Emoticon {
+        id: "party",
+        title: "Party",
+        shortcuts: &["<:o)"],
+    },
+    Emoticon {
+        id: "sleepy",
+        title: "Sleepy",
+        shortcuts: &["|-)"],
+    },
+    Emoticon {
+        id: "dont-tell",
+        title: "Don't tell anyone",
+        shortcuts: &[":-#"],
+    },
+    Emoticon {
+        id: "secret",
+        title: "Secret telling",
+        shortcuts: &[":-*"],
+    },
+    Emoticon {
+        id: "sarcastic",
+        title: "Sarcastic",
+        shortcuts: &["^o)"],
+    },
+    Emoticon {
+        id: "sick",
+        title: "Sick",
+        shortcuts: &["+o("],
+    },
+    Emoticon {
+        id: "dont-know",
+        title: "I don't know",
+        shortcuts: &[":^)"],
+    },
+    Emoticon {
+        id: "thinking",
+        title: "Thinking",
+        shortcuts: &["*-)"],
+    },
+    Emoticon {
+        id: "eye-rolling",
+        title: "Eye-rolling",
+        shortcuts: &["8-)"],
+    },
+    Emoticon {
+        id: "baring-teeth",
+        title: "Baring teeth",
+        shortcuts: &["8o|"],
+    },
+    Emoticon {
+        id: "sun",
+        title: "Sun",
+        shortcuts: &["(#)"],
+    },
+    Emoticon {
+        id: "rainbow",
+        title: "Rainbow",
+        shortcuts: &["(R)"],
+    },
+    Emoticon {
+        id: "umbrella",
+        title: "Umbrella",
+        shortcuts: &["(um)"],
+    },
+    Emoticon {
+        id: "island",
+        title: "Island with a palm tree",
+        shortcuts: &["(ip)"],
+    },
+    Emoticon {
+        id: "pizza",
+        title: "Pizza",
+        shortcuts: &["(pi)"],
+    },
+    Emoticon {
+        id: "soccer",
+        title: "Soccer ball",
+        shortcuts: &["(so)"],
+    },
+    Emoticon {
+        id: "car",
+        title: "Auto",
+        shortcuts: &["(au)"],
+    },
+    Emoticon {
+        id: "airplane",
+        title: "Airplane",
+        shortcuts: &["(ap)"],
+    },
+    Emoticon {
+        id: "computer",
+        title: "Computer",
+        shortcuts: &["(co)"],
+    },
+    Emoticon {
+        id: "money",
+        title: "Money",
+        shortcuts: &["(mo)"],
+    },
+    Emoticon {
+        id: "turtle",
+        title: "Turtle",
+        shortcuts: &["(tu)"],
+    },
+    Emoticon {
+        id: "snail",
+        title: "Snail",
+        shortcuts: &["(sn)"],
+    },
+    Emoticon {
+        id: "sheep",
+        title: "Black sheep",
+        shortcuts: &["(bah)"],
+    },
+    Emoticon {
+        id: "plate",
+        title: "Plate",
+        shortcuts: &["(pl)"],
+    },
+    Emoticon {
+        id: "bowl",
+        title: "Bowl",
+        shortcuts: &["(||)"],
+    },
+    Emoticon {
+        id: "storm",
+        title: "Stormy cloud",
+        shortcuts: &["(st)"],
+    },
+    Emoticon {
+        id: "mobile",
+        title: "Mobile phone",
+        shortcuts: &["(mp)"],
+    },
+    Emoticon {
+        id: "xbox",
+        title: "Games",
+        shortcuts: &["(xx)"],
+    },
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Segment<'a> {
+    Text(String),
+    /// Text the sender put between backticks, shown without the backticks.
+    Code(&'a str),
+    Emoticon {
+        emoticon: &'static Emoticon,
+        /// The shortcut as it appears in the message.
+        shortcut: &'a str,
+    },
+    Custom {
+        emoticon: &'a CustomEmoticon,
+        shortcut: &'a str,
+    },
+}
+
+/// Splits a message body into runs of text, code and emoticons, including the sender's `custom`
+/// ones.
+pub fn tokenize<'a>(body: &'a str, custom: &'a [CustomEmoticon]) -> Vec<Segment<'a>> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut i = 0;
+    while let Some(c) = body[i..].chars().next() {
+        let rest = &body[i..];
+        if c == '\\' {
+            if rest[1..].starts_with('`') {
+                text.push('`');
+                i += 2;
+                continue;
+            }
+            if let Some((_, len)) = match_emoticon(&rest[1..], custom) {
+                text.push_str(&rest[1..len + 1]);
+                i += 1 + len;
+                continue;
+            }
+        } else if c == '`' {
+            if let Some(end) = rest[1..].find('`') {
+                flush_text(&mut text, &mut segments);
+                segments.push(Segment::Code(&rest[1..end + 1]));
+                i += end + 2;
+                continue;
+            }
+        } else if let Some((emoticon, len)) = match_emoticon(rest, custom) {
+            flush_text(&mut text, &mut segments);
+            segments.push(emoticon);
+            i += len;
+            continue;
+        }
+        text.push(c);
+        i += c.len_utf8();
+    }
+    flush_text(&mut text, &mut segments);
+    segments
+}
+
+/// Whether `shortcut` would show one of the built-in emoticons.
+pub fn is_built_in(shortcut: &str) -> bool {
+    EMOTICONS
+        .iter()
+        .flat_map(|emoticon| emoticon.shortcuts.iter())
+        .any(|s| s.eq_ignore_ascii_case(shortcut))
+}
+
+fn flush_text(text: &mut String, segments: &mut Vec<Segment<'_>>) {
+    if !text.is_empty() {
+        segments.push(Segment::Text(std::mem::take(text)));
+    }
+}
+
+/// Finds the longest shortcut `text` starts with, returning its segment and length in bytes.
+///
+/// A custom emoticon wins over a built-in one with a shortcut of the same length.
+fn match_emoticon<'a>(text: &'a str, custom: &'a [CustomEmoticon]) -> Option<(Segment<'a>, usize)> {
+    let built_in = EMOTICONS
+        .iter()
+        .flat_map(|emoticon| emoticon.shortcuts.iter().map(move |s| (emoticon, *s)))
+        .filter(|(_, shortcut)| {
+            // Shortcuts are ASCII, so a byte-wise match always ends on a char boundary.
+            text.as_bytes()
+                .get(..shortcut.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(shortcut.as_bytes()))
+        })
+        .map(|(emoticon, shortcut)| (emoticon, shortcut.len()))
+        .max_by_key(|(_, len)| *len);
+    let custom = custom
+        .iter()
+        .filter(|emoticon| !emoticon.shortcut.is_empty() && text.starts_with(&emoticon.shortcut))
+        .max_by_key(|emoticon| emoticon.shortcut.len());
+
+    match (built_in, custom) {
+        (Some((_, built_in_len)), Some(emoticon)) if emoticon.shortcut.len() >= built_in_len => {
+            custom_match(text, emoticon)
+        }
+        (Some((emoticon, len)), _) => Some((
+            Segment::Emoticon {
+                emoticon,
+                shortcut: &text[..len],
+            },
+            len,
+        )),
+        (None, Some(emoticon)) => custom_match(text, emoticon),
+        (None, None) => None,
+    }
+}
+
+fn custom_match<'a>(text: &'a str, emoticon: &'a CustomEmoticon) -> Option<(Segment<'a>, usize)> {
+    let len = emoticon.shortcut.len();
+    Some((
+        Segment::Custom {
+            emoticon,
+            shortcut: &text[..len],
+        },
+        len,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn built_in(id: &str) -> &'static Emoticon {
+        EMOTICONS.iter().find(|e| e.id == id).unwrap()
+    }
+
+    fn custom(shortcut: &str) -> CustomEmoticon {
+        CustomEmoticon {
+            shortcut: shortcut.to_string(),
+            hash: "ab".repeat(32),
+        }
+    }
+
+    fn text(text: &str) -> Segment<'static> {
+        Segment::Text(text.to_string())
+    }
+
+    #[test]
+    fn matches_longest_shortcut_in_any_case() {
+        assert_eq!(
+            tokenize("hi <:O) (h)", &[]),
+            [
+                text("hi "),
+                Segment::Emoticon {
+                    emoticon: built_in("party"),
+                    shortcut: "<:O)",
+                },
+                text(" "),
+                Segment::Emoticon {
+                    emoticon: built_in("hot"),
+                    shortcut: "(h)",
+                },
+            ]
+        );
+        assert_eq!(
+            tokenize(":o", &[]),
+            [Segment::Emoticon {
+                emoticon: built_in("surprised"),
+                shortcut: ":o",
+            }]
+        );
+    }
+
+    #[test]
+    fn match_reports_length_in_bytes() {
+        let (_, len) = match_emoticon("<:o)é", &[]).unwrap();
+        assert_eq!(len, 4);
+        assert!(match_emoticon("é:)", &[]).is_none());
+        assert!(match_emoticon("(", &[]).is_none());
+    }
+
+    #[test]
+    fn custom_emoticon_wins_a_tie_and_matches_exactly() {
+        let mine = [custom("(H)"), custom("(cat)")];
+        assert_eq!(
+            tokenize("(H)(cat)(CAT)", &mine),
+            [
+                Segment::Custom {
+                    emoticon: &mine[0],
+                    shortcut: "(H)",
+                },
+                Segment::Custom {
+                    emoticon: &mine[1],
+                    shortcut: "(cat)",
+                },
+                text("(CAT)"),
+            ]
+        );
+        // A longer built-in shortcut still wins over a shorter custom one.
+        let short = [custom("<:")];
+        assert_eq!(
+            tokenize("<:o)", &short),
+            [Segment::Emoticon {
+                emoticon: built_in("party"),
+                shortcut: "<:o)",
+            }]
+        );
+    }
+
+    #[test]
+    fn backticks_keep_text_as_typed() {
+        assert_eq!(
+            tokenize("run `a :) b` now :)", &[]),
+            [
+                text("run "),
+                Segment::Code("a :) b"),
+                text(" now "),
+                Segment::Emoticon {
+                    emoticon: built_in("smile"),
+                    shortcut: ":)",
+                },
+            ]
+        );
+        assert_eq!(tokenize("``", &[]), [Segment::Code("")]);
+    }
+
+    #[test]
+    fn unterminated_backtick_is_plain_text() {
+        assert_eq!(
+            tokenize("a `b :)", &[]),
+            [
+                text("a `b "),
+                Segment::Emoticon {
+                    emoticon: built_in("smile"),
+                    shortcut: ":)",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn backslash_escapes_shortcuts_and_backticks() {
+        let mine = [custom("(cat)")];
+        assert_eq!(
+            tokenize(r"\:) \(cat) \`x` \a", &mine),
+            [text(r":) (cat) `x` \a")]
+        );
+        // Only shortcuts and backticks can be escaped, so a second backslash escapes the smile.
+        assert_eq!(tokenize(r"\\:)", &[]), [text(r"\:)")]);
+    }
+
+    #[test]
+    fn trailing_backslash_is_kept() {
+        assert_eq!(tokenize(r"bye\", &[]), [text(r"bye\")]);
+        assert_eq!(tokenize(r"\", &[]), [text(r"\")]);
+    }
+
+    #[test]
+    fn empty_custom_shortcut_never_matches() {
+        assert_eq!(tokenize("hi", &[custom("")]), [text("hi")]);
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+/// Error returned by every backend command.
+///
+/// Serialized as `{"code": "NOT_FOUND", "message": "..."}` so the frontend can pick a message
+/// for the user by code and still log the details.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "code", content = "message", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum AppError {
+    /// A contact, conversation or file the command refers to doesn't exist.
+    NotFound(String),
+    /// The arguments or an imported file were rejected.
+    Validation(String),
+    /// Reading or writing the app's data on disk failed.
+    Storage(String),
+    /// A contact couldn't be reached.
+    Transport(String),
+    /// The user isn't allowed to do this, e.g. a peer that isn't in the friends list.
+    Auth(String),
+    /// A window couldn't be opened, closed or changed.
+    Window(String),
+}
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Validation(_) => "VALIDATION",
+            AppError::Storage(_) => "STORAGE",
+            AppError::Transport(_) => "TRANSPORT",
+            AppError::Auth(_) => "AUTH",
+            AppError::Window(_) => "WINDOW",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Validation(message)
+            | AppError::Storage(message)
+            | AppError::Transport(message)
+            | AppError::Auth(message)
+            | AppError::Window(message) => message,
+        }
+    }
+}
+
+impl Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        AppError::Storage(e.to_string())
+    }
+}
@@ -0,0 +1,34 @@
+//! Names of the events the backend emits to the frontend.
+
+/// A message was sent or received. Payload: `ChatMessageEvent`.
+pub const CHAT_MESSAGE: &str = "chat-message";
+/// The friends list changed and should be fetched again. No payload.
+pub const FRIENDS_CHANGED: &str = "friends-changed";
+/// The user asked to open a conversation from outside the window, or was added to a group
+/// conversation. Payload: friend's email or the group's id.
+pub const OPEN_CHAT: &str = "open-chat";
+/// A nudge was sent or received and the conversation's window should shake. Payload: friend's
+/// email or the group's id.
+pub const NUDGE: &str = "nudge";
+/// A message was edited or deleted, by the user or whoever sent it. Payload: `ChatMessageEvent`
+/// with the message as it is now.
+pub const MESSAGE_CHANGED: &str = "message-changed";
+/// A friend started or stopped writing a message. Payload: `TypingEvent`.
+pub const TYPING: &str = "typing";
+/// A file transfer was offered or changed status, or made progress. Payload: `FileTransfer`.
+pub const FILE_TRANSFER: &str = "file-transfer";
+/// A group conversation was started, changed members or was left, and the list of groups should
+/// be fetched again. No payload.
+pub const GROUPS_CHANGED: &str = "groups-changed";
+/// A search result was picked and its conversation should scroll to the message. Payload:
+/// `ShowMessageEvent`.
+pub const SHOW_MESSAGE: &str = "show-message";
+/// Messages arrived in a conversation that wasn't on screen, or one with unread messages was
+/// looked at. Payload: the number of unread messages keyed by friend's email or group id,
+/// leaving out conversations with none.
+pub const UNREAD_CHANGED: &str = "unread-changed";
+/// The user's profile changed outside the window showing it, such as their availability from the
+/// tray menu. Payload: `User`.
+pub const USER_CHANGED: &str = "user-changed";
+/// The user signed out from the tray menu and the sign-in page should be shown. No payload.
+pub const SIGNED_OUT: &str = "signed-out";
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+/// The file a `MessageKind::File` message offers, whose transfer shares the message's id.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct FileOffer {
+    pub name: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Hex SHA-256 of the content, checked by the receiver once every byte has arrived.
+    pub hash: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TransferStatus {
+    /// Waiting for the receiver to accept or decline.
+    Offered,
+    Declined,
+    Transferring,
+    /// A chunk couldn't be delivered or the app was closed, and the transfer can be resumed.
+    Interrupted,
+    Completed,
+    /// The file couldn't be read or what arrived didn't match its hash.
+    Failed,
+    Cancelled,
+}
+
+impl TransferStatus {
+    /// Whether nothing more can happen to the transfer.
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            TransferStatus::Declined
+                | TransferStatus::Completed
+                | TransferStatus::Failed
+                | TransferStatus::Cancelled
+        )
+    }
+}
+
+/// A file being sent to or received from a friend. Payload of the `events::FILE_TRANSFER` event.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct FileTransfer {
+    /// Id of the message that offered the file.
+    pub id: String,
+    /// Email of the friend on the other end.
+    pub conversation: String,
+    pub incoming: bool,
+    pub file: FileOffer,
+    pub status: TransferStatus,
+    /// Bytes sent or received so far.
+    pub transferred: u64,
+    /// The file being sent, or where the received file is being written and then saved.
+    pub path: Option<String>,
+    /// Why the transfer failed or was interrupted.
+    pub error: Option<String>,
+}
+
+impl FileTransfer {
+    /// Progress from 0 to 100.
+    pub fn percent(&self) -> u64 {
+        (self.transferred * 100)
+            .checked_div(self.file.size)
+            .unwrap_or(100)
+            .min(100)
+    }
+}
+
+/// Formats a byte count the way file sizes are shown to the user, e.g. `1.5 MB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} bytes", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+/// Group conversations are identified by this followed by a UUID, which can't be mistaken for
+/// the email that identifies a one-to-one conversation.
+pub const GROUP_PREFIX: &str = "group:";
+
+/// Whether a conversation id belongs to a group conversation rather than a single friend.
+pub fn is_group(conversation: &str) -> bool {
+    conversation.starts_with(GROUP_PREFIX)
+}
+
+/// Someone taking part in a group conversation, who may not be in the user's friends list.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Participant {
+    pub email: String,
+    pub name: String,
+    /// Where to reach them, for members the user hasn't added as a friend.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+/// A conversation with more than one friend, started by inviting someone into a chat.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Group {
+    pub id: String,
+    /// Everyone in the conversation, the user included, in the order they joined.
+    pub participants: Vec<Participant>,
+}
+
+impl Group {
+    pub fn participant(&self, email: &str) -> Option<&Participant> {
+        self.participants.iter().find(|p| p.email == email)
+    }
+
+    /// Everyone in the conversation except `email`, usually the user.
+    pub fn others<'a>(&'a self, email: &'a str) -> impl Iterator<Item = &'a Participant> {
+        self.participants.iter().filter(move |p| p.email != email)
+    }
+}
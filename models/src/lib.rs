@@ -0,0 +1,162 @@
+//! Types exchanged between the frontend and the backend over Tauri IPC.
+//!
+//! Both sides depend on this crate, so changing a field here is a compile error wherever the
+//! old shape was used instead of a `from_value` failure at runtime.
+
+pub mod commands;
+pub mod emoticons;
+pub mod events;
+
+mod args;
+mod chat;
+mod contacts;
+mod error;
+mod files;
+mod groups;
+mod logs;
+mod search;
+mod settings;
+
+pub use args::*;
+pub use chat::*;
+pub use contacts::*;
+pub use error::AppError;
+pub use files::*;
+pub use groups::*;
+pub use logs::*;
+pub use search::*;
+pub use settings::*;
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+#[derive(Clone, Deserialize, Serialize)]
+pub struct User {
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    pub availability: Availability,
+    /// Hash of the user's display picture, served by the backend's `avatar` protocol.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_picture: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Friend {
+    pub name: String,
+    pub email: String,
+    pub status: String,
+    pub availability: Availability,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// Where this friend's app listens for peer connections, as `host:port`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub muted: bool,
+    /// Hash of the display picture the friend last sent, once it has been downloaded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_picture: Option<String>,
+}
+
+/// The parts of a `Friend` only the user sees and edits, never sent by the friend themselves.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ContactDetails {
+    pub alias: Option<String>,
+    pub notes: String,
+    pub address: Option<String>,
+    pub muted: bool,
+}
+
+impl Friend {
+    pub fn new(
+        name: String,
+        email: String,
+        status: Option<String>,
+        availability: Option<Availability>,
+    ) -> Self {
+        Self {
+            name,
+            email,
+            status: status.unwrap_or("".to_string()),
+            availability: availability.unwrap_or(Availability::Online),
+            alias: None,
+            notes: "".to_string(),
+            address: None,
+            muted: false,
+            display_picture: None,
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        name: Option<String>,
+        status: Option<String>,
+        availability: Option<Availability>,
+    ) {
+        if let Some(name) = name {
+            self.name = name;
+        }
+        if let Some(status) = status {
+            self.status = status;
+        }
+        if let Some(availability) = availability {
+            self.availability = availability;
+        }
+    }
+
+    pub fn update_contact_details(&mut self, details: ContactDetails) {
+        let non_empty =
+            |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+        self.alias = non_empty(details.alias);
+        self.notes = details.notes.trim().to_string();
+        self.address = non_empty(details.address);
+        self.muted = details.muted;
+    }
+
+    pub fn contact_details(&self) -> ContactDetails {
+        ContactDetails {
+            alias: self.alias.clone(),
+            notes: self.notes.clone(),
+            address: self.address.clone(),
+            muted: self.muted,
+        }
+    }
+
+    /// The private alias if the user set one, otherwise the name the contact chose.
+    pub fn display_name(&self) -> String {
+        self.alias.clone().unwrap_or_else(|| self.name.clone())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Availability {
+    Online,
+    Away,
+    Busy,
+    Offline,
+}
+
+impl Display for Availability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Availability::Online => write!(f, "Online"),
+            Availability::Away => write!(f, "Away"),
+            Availability::Busy => write!(f, "Busy"),
+            Availability::Offline => write!(f, "Offline"),
+        }
+    }
+}
+
+impl Availability {
+    pub fn to_icon(&self) -> String {
+        match self {
+            Availability::Online => "👤".to_string(),
+            Availability::Away => "⏳".to_string(),
+            Availability::Busy => "⛔".to_string(),
+            Availability::Offline => "📴".to_string(),
+        }
+    }
+}
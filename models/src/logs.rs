@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// File formats a conversation can be saved in.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum LogFormat {
+    /// A web page keeping each message's font and color.
+    Html,
+    Text,
+    /// The XML MSN Messenger saved its history in, shown in a browser through `MessageLog.xsl`.
+    MsnXml,
+}
+
+impl LogFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LogFormat::Html => "html",
+            LogFormat::Text => "txt",
+            LogFormat::MsnXml => "xml",
+        }
+    }
+}
+
+/// What importing an MSN Messenger log into a conversation would do, computed before anything is
+/// committed.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct LogImportPreview {
+    /// Names the messages were sent under, in the order they first appear, for the user to say
+    /// which were theirs. MSN logs don't record email addresses.
+    pub senders: Vec<String>,
+    /// Messages that would be added to the conversation.
+    pub new: usize,
+    /// Messages already in the conversation from an earlier import, which are left out.
+    pub duplicates: usize,
+}
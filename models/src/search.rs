@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+/// What to look for with the `search_messages` command.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    /// Words that must all appear in a message, each also matching longer words it starts.
+    pub text: String,
+    /// Only search the conversation with this friend's email or group id.
+    #[serde(default)]
+    pub conversation: Option<String>,
+    /// Only messages sent at or after this time, in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub since: Option<u64>,
+    /// Only messages sent at or before this time, in milliseconds since the Unix epoch.
+    #[serde(default)]
+    pub until: Option<u64>,
+}
+
+/// A message matching a `SearchQuery`, best matches first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SearchResult {
+    /// Email of the friend the conversation is with, or the id of the group.
+    pub conversation: String,
+    pub message_id: String,
+    /// Email of the sender, which is the user's own email for outgoing messages.
+    pub from: String,
+    pub sent_at: u64,
+    /// The part of the message around the matches, cut to a few words either side.
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// A piece of a search result's snippet, either plain text or words that matched the query.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SnippetPart {
+    pub text: String,
+    pub matched: bool,
+}
+
+/// Payload of the `events::SHOW_MESSAGE` event.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ShowMessageEvent {
+    /// Email of the friend the conversation is with, or the id of the group.
+    pub conversation: String,
+    pub message_id: String,
+}
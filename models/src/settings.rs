@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use crate::TextStyle;
+
+/// App-wide preferences, stored in the backend's `settings.json`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Settings {
+    #[serde(default)]
+    pub sounds: SoundSettings,
+    /// Applied to every message the user sends.
+    #[serde(default)]
+    pub text_style: TextStyle,
+    /// Where received files are saved, or the system's downloads folder if unset.
+    #[serde(default)]
+    pub download_dir: Option<String>,
+    #[serde(default)]
+    pub chat_layout: ChatLayout,
+    /// Closing the contact list quits instead of leaving the messenger running in the tray.
+    #[serde(default)]
+    pub quit_on_close: bool,
+    /// How long after sending a message it can still be edited or deleted, or `None` for as long
+    /// as the user likes.
+    #[serde(default = "Settings::default_edit_window")]
+    pub edit_window_minutes: Option<u32>,
+    /// Friends are told when the user has seen their messages.
+    #[serde(default = "Settings::default_send_read_receipts")]
+    pub send_read_receipts: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            sounds: SoundSettings::default(),
+            text_style: TextStyle::default(),
+            download_dir: None,
+            chat_layout: ChatLayout::default(),
+            quit_on_close: false,
+            edit_window_minutes: Settings::default_edit_window(),
+            send_read_receipts: Settings::default_send_read_receipts(),
+        }
+    }
+}
+
+impl Settings {
+    /// Choices offered for `edit_window_minutes`.
+    pub const EDIT_WINDOWS: [Option<u32>; 4] = [Some(5), Some(15), Some(60), None];
+
+    fn default_edit_window() -> Option<u32> {
+        Some(15)
+    }
+
+    fn default_send_read_receipts() -> bool {
+        true
+    }
+}
+
+/// Where conversations open.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum ChatLayout {
+    /// Each conversation in a window of its own, next to the contact list.
+    #[default]
+    Windows,
+    /// Conversations as tabs that take the contact list's place in the main window.
+    Tabs,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct SoundSettings {
+    pub enabled: bool,
+    /// Name of the sound pack directory to play sounds from.
+    pub pack: String,
+    /// Playback volume from 0 to 100.
+    pub volume: u8,
+    pub disabled_events: Vec<SoundEvent>,
+}
+
+impl Default for SoundSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pack: SoundSettings::DEFAULT_PACK.to_string(),
+            volume: 80,
+            disabled_events: Vec::new(),
+        }
+    }
+}
+
+impl SoundSettings {
+    pub const DEFAULT_PACK: &'static str = "default";
+
+    pub fn plays(&self, event: SoundEvent) -> bool {
+        self.enabled && !self.disabled_events.contains(&event)
+    }
+}
+
+/// Something that happened in the messenger and has a sound attached to it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SoundEvent {
+    NewMessage,
+    ContactOnline,
+    Nudge,
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 3] = [
+        SoundEvent::NewMessage,
+        SoundEvent::ContactOnline,
+        SoundEvent::Nudge,
+    ];
+
+    /// File name of the sound inside a pack, without its extension.
+    pub fn file_stem(self) -> &'static str {
+        match self {
+            SoundEvent::NewMessage => "new_message",
+            SoundEvent::ContactOnline => "contact_online",
+            SoundEvent::Nudge => "nudge",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SoundEvent::NewMessage => "A message arrives",
+            SoundEvent::ContactOnline => "A contact signs in",
+            SoundEvent::Nudge => "You receive a nudge",
+        }
+    }
+}
@@ -0,0 +1,55 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<!-- Shows a conversation saved in MSN Messenger's log format as a table when the XML file is
+     opened in a browser. Written next to every log exported in that format. -->
+<xsl:stylesheet version="1.0" xmlns:xsl="http://www.w3.org/1999/XSL/Transform">
+  <xsl:output method="html" encoding="UTF-8"/>
+
+  <xsl:template match="/Log">
+    <html>
+      <head>
+        <meta charset="UTF-8"/>
+        <title>Message History</title>
+        <style>
+          body { font-family: Tahoma, "Segoe UI", sans-serif; font-size: 10pt; margin: 16px;
+                 background: linear-gradient(#daeced, #ecf5f6); }
+          table { border-collapse: collapse; width: 100%; background-color: #ffffff; }
+          th { text-align: left; background: linear-gradient(#a1c4d2, #f6fffb); padding: 4px; }
+          td { padding: 2px 4px; vertical-align: top; border-bottom: 1px solid #e5eef5; }
+          td.when { color: #6b7a89; white-space: nowrap; }
+          td.from { font-weight: bold; white-space: nowrap; }
+          tr.session td { border-top: 2px solid #c8ded9; }
+          tr.system td { color: #6b7a89; font-style: italic; }
+        </style>
+      </head>
+      <body>
+        <table>
+          <tr><th>Date</th><th>Time</th><th>From</th><th>To</th><th>Message</th></tr>
+          <xsl:apply-templates/>
+        </table>
+      </body>
+    </html>
+  </xsl:template>
+
+  <xsl:template match="Message">
+    <tr>
+      <xsl:if test="not(preceding-sibling::*[1]/@SessionID = @SessionID)">
+        <xsl:attribute name="class">session</xsl:attribute>
+      </xsl:if>
+      <td class="when"><xsl:value-of select="@Date"/></td>
+      <td class="when"><xsl:value-of select="@Time"/></td>
+      <td class="from"><xsl:value-of select="From/User/@FriendlyName"/></td>
+      <td><xsl:value-of select="To/User/@FriendlyName"/></td>
+      <td>
+        <span style="{Text/@Style}"><xsl:value-of select="Text"/></span>
+      </td>
+    </tr>
+  </xsl:template>
+
+  <xsl:template match="Invitation | InvitationResponse | Join | Leave">
+    <tr class="system">
+      <td class="when"><xsl:value-of select="@Date"/></td>
+      <td class="when"><xsl:value-of select="@Time"/></td>
+      <td colspan="3"><xsl:value-of select="Text"/></td>
+    </tr>
+  </xsl:template>
+</xsl:stylesheet>
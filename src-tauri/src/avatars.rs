@@ -0,0 +1,189 @@
+//! Display pictures of the user and their friends.
+//!
+//! Pictures are cropped to a square and scaled down to `AVATAR_SIZE` before being stored as PNGs
+//! under the hex SHA-256 of the result, so presence updates only need to carry the hash and a
+//! friend's picture is downloaded once, the first time a hash they send isn't already here. The
+//! webview loads them through the `avatar` protocol registered in `main`.
+
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use emiessiene_models::{AppError, ImageSource};
+use image::{imageops::FilterType, ImageFormat};
+use sha2::{Digest, Sha256};
+
+use super::{
+    emoticons::{image_mime_type, is_hash},
+    images::{self, MAX_DIMENSION, MAX_IMAGE_BYTES},
+};
+
+/// Width and height of a stored display picture, in pixels.
+const AVATAR_SIZE: u32 = 256;
+/// Largest display picture accepted from a friend, in bytes, far above a 256 pixel PNG.
+const MAX_AVATAR_BYTES: usize = 512 * 1024;
+
+pub struct AvatarStore {
+    dir: PathBuf,
+}
+
+impl AvatarStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Crops and scales a picture chosen by the user, then stores it.
+    pub fn store_source(&self, source: &ImageSource) -> Result<String, AppError> {
+        let bytes = match source {
+            ImageSource::Path(path) => {
+                let path = Path::new(path);
+                if fs::metadata(path)?.len() > MAX_IMAGE_BYTES as u64 {
+                    return Err(too_large());
+                }
+                fs::read(path)?
+            }
+            ImageSource::Data(data) => STANDARD
+                .decode(data)
+                .map_err(|e| AppError::Validation(format!("Invalid image data: {}", e)))?,
+        };
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(too_large());
+        }
+        if image_mime_type(&bytes).is_none() {
+            return Err(AppError::Validation(
+                "Display pictures must be PNG, GIF or JPEG images".to_string(),
+            ));
+        }
+        let unreadable = |e| AppError::Validation(format!("The picture couldn't be read: {}", e));
+        let (width, height) = images::dimensions(&bytes).map_err(unreadable)?;
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(AppError::Validation(format!(
+                "Display pictures can be made from pictures of at most {} by {} pixels",
+                MAX_DIMENSION, MAX_DIMENSION
+            )));
+        }
+        let picture = images::decode(&bytes, MAX_DIMENSION).map_err(unreadable)?;
+        // Keep the middle of the picture, which is usually where the face is.
+        let side = width.min(height);
+        let square = picture
+            .crop_imm((width - side) / 2, (height - side) / 2, side, side)
+            .resize_exact(AVATAR_SIZE, AVATAR_SIZE, FilterType::Lanczos3);
+        let mut png = Vec::new();
+        square
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| AppError::Storage(format!("Couldn't save display picture: {}", e)))?;
+        self.store(&png)
+    }
+
+    /// Stores a friend's display picture, checking it matches the hash they announced.
+    pub fn store_received(&self, hash: &str, data: &str) -> Result<(), AppError> {
+        let bytes = STANDARD
+            .decode(data)
+            .map_err(|e| AppError::Validation(format!("Invalid display picture data: {}", e)))?;
+        if bytes.len() > MAX_AVATAR_BYTES || image_mime_type(&bytes) != Some("image/png") {
+            return Err(AppError::Validation(
+                "Display picture is not a PNG of a reasonable size".to_string(),
+            ));
+        }
+        let unreadable = |e| AppError::Validation(format!("Unreadable display picture: {}", e));
+        let (width, height) = images::dimensions(&bytes).map_err(unreadable)?;
+        if width > AVATAR_SIZE || height > AVATAR_SIZE {
+            return Err(AppError::Validation(format!(
+                "Display picture is larger than {} pixels",
+                AVATAR_SIZE
+            )));
+        }
+        images::decode(&bytes, AVATAR_SIZE).map_err(unreadable)?;
+        if format!("{:x}", Sha256::digest(&bytes)) != hash {
+            return Err(AppError::Validation(format!(
+                "Display picture doesn't match its hash {}",
+                hash
+            )));
+        }
+        self.store(&bytes)?;
+        Ok(())
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        is_hash(hash) && self.dir.join(hash).is_file()
+    }
+
+    pub fn read(&self, hash: &str) -> Result<Vec<u8>, AppError> {
+        if !is_hash(hash) {
+            return Err(AppError::Validation(format!(
+                "{} is not a display picture",
+                hash
+            )));
+        }
+        let path = self.dir.join(hash);
+        if !path.is_file() {
+            return Err(AppError::NotFound(format!("No display picture {}", hash)));
+        }
+        Ok(fs::read(path)?)
+    }
+
+    pub fn avatar_for_transfer(&self, hash: &str) -> Result<String, AppError> {
+        Ok(STANDARD.encode(self.read(hash)?))
+    }
+
+    fn store(&self, bytes: &[u8]) -> Result<String, AppError> {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(&hash);
+        if !path.exists() {
+            fs::write(path, bytes)?;
+        }
+        Ok(hash)
+    }
+}
+
+fn too_large() -> AppError {
+    AppError::Validation(format!(
+        "Display pictures can be made from pictures of at most {} MB",
+        MAX_IMAGE_BYTES / 1024 / 1024
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GrayImage;
+
+    fn png(width: u32, height: u32) -> String {
+        let mut bytes = Vec::new();
+        GrayImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        STANDARD.encode(bytes)
+    }
+
+    #[test]
+    fn oversized_source_is_refused_before_decoding() {
+        let store = AvatarStore::new(PathBuf::from("/nonexistent"));
+        let source = ImageSource::Data(png(MAX_DIMENSION + 1, 1));
+        match store.store_source(&source) {
+            Err(AppError::Validation(message)) => {
+                assert!(message.contains("pixels"), "{}", message)
+            }
+            _ => panic!("oversized picture was accepted"),
+        }
+    }
+
+    #[test]
+    fn source_is_cropped_and_scaled_to_a_square() {
+        let dir = std::env::temp_dir().join(format!("emiessiene-test-{}", uuid::Uuid::new_v4()));
+        let store = AvatarStore::new(dir.clone());
+        let hash = store
+            .store_source(&ImageSource::Data(png(300, 200)))
+            .unwrap();
+        let stored = store.read(&hash).unwrap();
+        assert_eq!(
+            images::dimensions(&stored).unwrap(),
+            (AVATAR_SIZE, AVATAR_SIZE)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,572 @@
+use std::{collections::HashSet, fs, path::Path};
+
+use emiessiene_models::{
+    validate_email, AppError, Availability, ContactDetails, ContactFormat, ContactUpdate, Friend,
+    ImportPreview,
+};
+use log::info;
+
+use super::{storage, FRIENDS_SCHEMA};
+
+/// Longest line allowed by RFC 6350 before it must be folded, in octets.
+const VCARD_LINE_LIMIT: usize = 75;
+
+const CSV_HEADERS: [&str; 4] = ["name", "email", "alias", "notes"];
+
+/// A contact read from a file, before it is compared with the friends list.
+///
+/// `alias` and `notes` are `None` when the file has no such field, in which case importing it
+/// keeps whatever the user wrote for an existing friend.
+pub struct ImportedContact {
+    name: String,
+    email: String,
+    alias: Option<String>,
+    notes: Option<String>,
+}
+
+impl ImportedContact {
+    fn into_friend(self) -> Friend {
+        let mut friend = Friend::new(self.name, self.email, None, Some(Availability::Offline));
+        friend.update_contact_details(ContactDetails {
+            alias: self.alias,
+            notes: self.notes.unwrap_or_default(),
+            ..Default::default()
+        });
+        friend
+    }
+}
+
+/// Compares imported entries with the current friends list by email.
+///
+/// Entries matching an existing friend with identical details, or repeating an email seen
+/// earlier in the same file, are reported as duplicates and left out of the import.
+pub fn preview_import(existing: &[Friend], imported: Vec<ImportedContact>) -> ImportPreview {
+    let mut preview = ImportPreview::default();
+    let mut seen = HashSet::new();
+    for entry in imported {
+        if !seen.insert(entry.email.to_lowercase()) {
+            preview.duplicates.push(entry.into_friend());
+            continue;
+        }
+        let Some(current) = existing
+            .iter()
+            .find(|f| f.email.eq_ignore_ascii_case(&entry.email))
+        else {
+            preview.new.push(entry.into_friend());
+            continue;
+        };
+        let mut after = current.clone();
+        after.name = entry.name;
+        let mut details = after.contact_details();
+        if let Some(alias) = entry.alias {
+            details.alias = Some(alias);
+        }
+        if let Some(notes) = entry.notes {
+            details.notes = notes;
+        }
+        after.update_contact_details(details);
+        if same_contact_details(current, &after) {
+            preview.duplicates.push(after);
+        } else {
+            preview.updated.push(ContactUpdate {
+                before: current.clone(),
+                after,
+            });
+        }
+    }
+    preview
+}
+
+pub fn apply_import(preview: ImportPreview, friends: &mut Vec<Friend>) {
+    info!(
+        "Importing {} new and {} updated contacts",
+        preview.new.len(),
+        preview.updated.len()
+    );
+    for update in preview.updated {
+        if let Some(f) = friends.iter_mut().find(|f| f.email == update.before.email) {
+            *f = update.after;
+        }
+    }
+    friends.extend(preview.new);
+}
+
+fn same_contact_details(a: &Friend, b: &Friend) -> bool {
+    a.name == b.name && a.alias == b.alias && a.notes == b.notes
+}
+
+pub fn read_contacts(path: &Path, format: ContactFormat) -> Result<Vec<ImportedContact>, AppError> {
+    info!("Reading {:?} contacts from {}", format, path.display());
+    parse_contacts(&fs::read_to_string(path)?, format)
+}
+
+fn parse_contacts(content: &str, format: ContactFormat) -> Result<Vec<ImportedContact>, AppError> {
+    let contacts = match format {
+        ContactFormat::Json => parse_json(content)?,
+        ContactFormat::VCard => parse_vcard(content)?,
+        ContactFormat::Csv => parse_csv(content)?,
+    };
+    for contact in &contacts {
+        validate_email(&contact.email)?;
+    }
+    Ok(contacts)
+}
+
+pub fn write_contacts(
+    path: &Path,
+    format: ContactFormat,
+    friends: &[Friend],
+) -> Result<(), AppError> {
+    info!(
+        "Writing {} contacts as {:?} to {}",
+        friends.len(),
+        format,
+        path.display()
+    );
+    let content = match format {
+        ContactFormat::Json => storage::to_string(&FRIENDS_SCHEMA, &friends)?,
+        ContactFormat::VCard => friends.iter().map(vcard_from_friend).collect(),
+        ContactFormat::Csv => csv_from_friends(friends)?,
+    };
+    fs::write(path, content)?;
+    Ok(())
+}
+
+fn parse_json(content: &str) -> Result<Vec<ImportedContact>, AppError> {
+    let json: Vec<Friend> = storage::from_str(content, &FRIENDS_SCHEMA)
+        .map_err(|e| AppError::Validation(e.message().to_string()))?;
+    // Empty notes are left out of the file, so they can't be told apart from no notes at all.
+    Ok(json
+        .into_iter()
+        .map(|f| ImportedContact {
+            name: f.name,
+            email: f.email,
+            alias: f.alias,
+            notes: Some(f.notes).filter(|n| !n.is_empty()),
+        })
+        .collect())
+}
+
+fn parse_csv(content: &str) -> Result<Vec<ImportedContact>, AppError> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(invalid_csv)?
+        .iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let email_column = column("email")
+        .ok_or_else(|| AppError::Validation("CSV file has no \"email\" column".to_string()))?;
+    let (name_column, alias_column, notes_column) =
+        (column("name"), column("alias"), column("notes"));
+
+    let mut friends = Vec::new();
+    for (i, record) in reader.records().enumerate() {
+        let record = record.map_err(invalid_csv)?;
+        let field = |c: Option<usize>| c.map(|c| record.get(c).unwrap_or_default().to_string());
+        let email = field(Some(email_column)).unwrap_or_default();
+        if email.is_empty() {
+            return Err(AppError::Validation(format!(
+                "CSV row {} has no email",
+                i + 2
+            )));
+        }
+        let name = field(name_column)
+            .filter(|n| !n.is_empty())
+            .unwrap_or_else(|| email.clone());
+        friends.push(ImportedContact {
+            name,
+            email,
+            alias: field(alias_column),
+            notes: field(notes_column),
+        });
+    }
+    Ok(friends)
+}
+
+fn invalid_csv(e: csv::Error) -> AppError {
+    AppError::Validation(format!("Invalid CSV file: {}", e))
+}
+
+fn csv_from_friends(friends: &[Friend]) -> Result<String, AppError> {
+    let write_error = |e: csv::Error| AppError::Storage(format!("Failed to write CSV: {}", e));
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(CSV_HEADERS).map_err(write_error)?;
+    for f in friends {
+        writer
+            .write_record([
+                f.name.as_str(),
+                f.email.as_str(),
+                f.alias.as_deref().unwrap_or_default(),
+                f.notes.as_str(),
+            ])
+            .map_err(write_error)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| AppError::Storage(format!("Failed to write CSV: {}", e)))?;
+    // Every field came from a `String`, so the output is always valid UTF-8.
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn parse_vcard(content: &str) -> Result<Vec<ImportedContact>, AppError> {
+    // Folded lines continue on the next line after a single leading space or tab.
+    let unfolded = content
+        .replace("\r\n", "\n")
+        .replace("\n ", "")
+        .replace("\n\t", "");
+
+    let mut friends = Vec::new();
+    let mut card: Option<VCardFields> = None;
+    for line in unfolded.lines().filter(|l| !l.trim().is_empty()) {
+        let (property, value) = split_vcard_line(line)
+            .ok_or_else(|| invalid_vcard(format!("malformed line {}", line)))?;
+        match (property.as_str(), card.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VCARD") => {
+                card = Some(VCardFields::default())
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VCARD") => {
+                let fields = card.take().unwrap_or_default();
+                let email = fields.email.ok_or_else(|| {
+                    invalid_vcard(format!("card {} has no EMAIL", friends.len() + 1))
+                })?;
+                let name = fields
+                    .name
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or_else(|| email.clone());
+                friends.push(ImportedContact {
+                    name,
+                    email,
+                    alias: fields.nickname,
+                    notes: fields.note,
+                });
+            }
+            (_, Some(fields)) => fields.set(&property, unescape_vcard_value(value)),
+            _ => return Err(invalid_vcard(format!("unexpected line {}", line))),
+        }
+    }
+    if card.is_some() {
+        return Err(invalid_vcard("file ends inside a card".to_string()));
+    }
+    Ok(friends)
+}
+
+fn invalid_vcard(reason: String) -> AppError {
+    AppError::Validation(format!("Invalid vCard file: {}", reason))
+}
+
+#[derive(Default)]
+struct VCardFields {
+    name: Option<String>,
+    email: Option<String>,
+    nickname: Option<String>,
+    note: Option<String>,
+}
+
+impl VCardFields {
+    fn set(&mut self, property: &str, value: String) {
+        match property {
+            "FN" => self.name = Some(value),
+            // The first EMAIL is the preferred one when a card lists several.
+            "EMAIL" if self.email.is_none() => self.email = Some(value),
+            "NICKNAME" => self.nickname = Some(value),
+            "NOTE" => self.note = Some(value),
+            _ => {}
+        }
+    }
+}
+
+/// Splits `group.NAME;PARAM="a:b":value` into the upper-cased property name and the raw value.
+fn split_vcard_line(line: &str) -> Option<(String, &str)> {
+    let mut in_quotes = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            in_quotes = !in_quotes;
+            None
+        }
+        ':' if !in_quotes => Some(i),
+        _ => None,
+    })?;
+    let name = line[..colon].split(';').next()?;
+    let name = name.rsplit('.').next()?;
+    Some((name.to_uppercase(), &line[colon + 1..]))
+}
+
+fn unescape_vcard_value(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn escape_vcard_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace('\n', "\\n")
+}
+
+fn vcard_from_friend(friend: &Friend) -> String {
+    let mut lines = vec![
+        "BEGIN:VCARD".to_string(),
+        "VERSION:4.0".to_string(),
+        format!("FN:{}", escape_vcard_value(&friend.name)),
+        format!("EMAIL:{}", escape_vcard_value(&friend.email)),
+    ];
+    if let Some(alias) = &friend.alias {
+        lines.push(format!("NICKNAME:{}", escape_vcard_value(alias)));
+    }
+    if !friend.notes.is_empty() {
+        lines.push(format!("NOTE:{}", escape_vcard_value(&friend.notes)));
+    }
+    lines.push("END:VCARD".to_string());
+    lines.iter().map(|l| fold_vcard_line(l)).collect()
+}
+
+fn fold_vcard_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 2);
+    let mut line_len = 0;
+    for c in line.chars() {
+        if line_len + c.len_utf8() > VCARD_LINE_LIMIT {
+            folded.push_str("\r\n ");
+            line_len = 1;
+        }
+        folded.push(c);
+        line_len += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn friend(name: &str, email: &str, alias: Option<&str>, notes: &str) -> Friend {
+        ImportedContact {
+            name: name.to_string(),
+            email: email.to_string(),
+            alias: alias.map(str::to_string),
+            notes: Some(notes.to_string()),
+        }
+        .into_friend()
+    }
+
+    fn into_friends(imported: Vec<ImportedContact>) -> Vec<Friend> {
+        imported
+            .into_iter()
+            .map(ImportedContact::into_friend)
+            .collect()
+    }
+
+    fn details(friends: &[Friend]) -> Vec<(&str, &str, Option<&str>, &str)> {
+        friends
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.email.as_str(),
+                    f.alias.as_deref(),
+                    f.notes.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    fn awkward_friends() -> Vec<Friend> {
+        vec![
+            friend(
+                "Ann, \"the\" Boss; CEO\\",
+                "ann@example.com",
+                Some("a;b,c"),
+                "",
+            ),
+            friend(
+                "Bob",
+                "bob@example.com",
+                None,
+                &format!("line one\nline two, {}", "é".repeat(60)),
+            ),
+        ]
+    }
+
+    #[test]
+    fn vcard_round_trip() {
+        let friends = awkward_friends();
+        let written: String = friends.iter().map(vcard_from_friend).collect();
+        assert_eq!(
+            details(&into_friends(parse_vcard(&written).unwrap())),
+            details(&friends)
+        );
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let friends = awkward_friends();
+        let written = csv_from_friends(&friends).unwrap();
+        assert_eq!(
+            details(&into_friends(parse_csv(&written).unwrap())),
+            details(&friends)
+        );
+    }
+
+    #[test]
+    fn fold_keeps_lines_within_limit() {
+        let line = format!("NOTE:{}", "é".repeat(100));
+        let folded = fold_vcard_line(&line);
+        assert!(folded.ends_with("\r\n"));
+        for part in folded.trim_end_matches("\r\n").split("\r\n") {
+            assert!(part.len() <= VCARD_LINE_LIMIT, "{} octets", part.len());
+        }
+        assert_eq!(folded.replace("\r\n ", "").trim_end(), line);
+    }
+
+    #[test]
+    fn parses_folded_crlf_lines() {
+        let content = "BEGIN:VCARD\r\nFN:Ann\r\n  Lee\r\nEMAIL;TYPE=work:ann@\r\n\texample.com\r\n\
+                       EMAIL:other@example.com\r\nEND:VCARD\r\n";
+        let friends = into_friends(parse_vcard(content).unwrap());
+        assert_eq!(
+            details(&friends),
+            [("Ann Lee", "ann@example.com", None, "")]
+        );
+    }
+
+    #[test]
+    fn escapes_round_trip() {
+        let value = "a\\b,c;d\ne";
+        assert_eq!(escape_vcard_value(value), r"a\\b\,c\;d\ne");
+        assert_eq!(unescape_vcard_value(&escape_vcard_value(value)), value);
+        assert_eq!(unescape_vcard_value("trailing\\"), "trailing\\");
+        assert_eq!(unescape_vcard_value("\\N"), "\n");
+    }
+
+    #[test]
+    fn vcard_without_email_is_rejected() {
+        let content = "BEGIN:VCARD\nFN:Ann\nEND:VCARD\n";
+        assert!(matches!(parse_vcard(content), Err(AppError::Validation(_))));
+        let unterminated = "BEGIN:VCARD\nEMAIL:ann@example.com\n";
+        assert!(matches!(
+            parse_vcard(unterminated),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn csv_without_email_is_rejected() {
+        assert!(matches!(
+            parse_csv("name,notes\nAnn,hi\n"),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            parse_csv("name,email\nAnn,\n"),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn csv_headers_are_case_insensitive_and_name_defaults_to_email() {
+        let friends = into_friends(parse_csv("EMAIL,Name\r\n ann@example.com , \r\n").unwrap());
+        assert_eq!(
+            details(&friends),
+            [("ann@example.com", "ann@example.com", None, "")]
+        );
+    }
+
+    #[test]
+    fn preview_sorts_new_updated_and_duplicate_rows() {
+        let existing = vec![
+            friend("Ann", "ann@example.com", None, ""),
+            friend("Bob", "bob@example.com", None, ""),
+        ];
+        let imported = parse_csv(
+            "name,email,alias,notes\n\
+             Ann,ANN@example.com,,\n\
+             Bobby,bob@example.com,,\n\
+             Cat,cat@example.com,,\n\
+             Cat again,CAT@example.com,,\n",
+        )
+        .unwrap();
+        let preview = preview_import(&existing, imported);
+        assert_eq!(
+            details(&preview.new),
+            [("Cat", "cat@example.com", None, "")]
+        );
+        assert_eq!(preview.updated.len(), 1);
+        assert_eq!(preview.updated[0].after.name, "Bobby");
+        assert_eq!(preview.updated[0].after.email, "bob@example.com");
+        let duplicates: Vec<&str> = preview.duplicates.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(duplicates, ["Ann", "Cat again"]);
+    }
+
+    #[test]
+    fn preview_keeps_alias_and_notes_missing_from_the_file() {
+        let existing = vec![
+            friend("Ann", "ann@example.com", Some("Annie"), "met at work"),
+            friend("Bob", "bob@example.com", Some("Bobby"), "likes jazz"),
+        ];
+        let imported = parse_csv(
+            "name,email\n\
+             Ann,ann@example.com\n\
+             Robert,bob@example.com\n",
+        )
+        .unwrap();
+        let preview = preview_import(&existing, imported);
+        assert!(preview.new.is_empty());
+        assert_eq!(
+            details(
+                &preview
+                    .updated
+                    .iter()
+                    .map(|u| u.after.clone())
+                    .collect::<Vec<_>>()
+            ),
+            [("Robert", "bob@example.com", Some("Bobby"), "likes jazz")]
+        );
+        assert_eq!(
+            details(&preview.duplicates),
+            [("Ann", "ann@example.com", Some("Annie"), "met at work")]
+        );
+
+        let vcard = "BEGIN:VCARD\nFN:Ann\nEMAIL:ann@example.com\nNOTE:new notes\nEND:VCARD\n";
+        let preview = preview_import(&existing, parse_vcard(vcard).unwrap());
+        assert_eq!(
+            details(
+                &preview
+                    .updated
+                    .iter()
+                    .map(|u| u.after.clone())
+                    .collect::<Vec<_>>()
+            ),
+            [("Ann", "ann@example.com", Some("Annie"), "new notes")]
+        );
+    }
+
+    #[test]
+    fn invalid_emails_are_rejected() {
+        assert!(matches!(
+            parse_contacts("name,email\nAnn,not-an-address\n", ContactFormat::Csv),
+            Err(AppError::Validation(_))
+        ));
+        let vcard = "BEGIN:VCARD\nFN:Ann\nEMAIL:ann\nEND:VCARD\n";
+        assert!(matches!(
+            parse_contacts(vcard, ContactFormat::VCard),
+            Err(AppError::Validation(_))
+        ));
+        assert!(parse_contacts("email\nann@example.com\n", ContactFormat::Csv).is_ok());
+    }
+}
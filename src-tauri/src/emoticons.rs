@@ -0,0 +1,210 @@
+//! The user's custom emoticons and the images of those friends sent along with their messages.
+//!
+//! Images are stored by the hex SHA-256 of their content, so an emoticon a friend already has is
+//! never sent twice and two friends using the same picture share one file. The webview loads
+//! them through the `emoticon://` protocol registered in `main`.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use emiessiene_models::{
+    emoticons::{self, CustomEmoticon},
+    AppError,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::storage::{self, Schema};
+
+/// Largest image accepted for a custom emoticon, in bytes.
+pub const MAX_IMAGE_BYTES: usize = 64 * 1024;
+/// Longest custom shortcut, in characters, the same limit MSN had.
+pub const MAX_SHORTCUT_CHARS: usize = 7;
+
+const EMOTICONS_SCHEMA: Schema = Schema {
+    name: "custom emoticons",
+    migrations: &[],
+};
+
+/// An emoticon image as sent to a friend the first time it appears in a message.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EmoticonImage {
+    pub hash: String,
+    /// The image file, base64-encoded.
+    pub data: String,
+}
+
+pub struct EmoticonLibrary {
+    images_dir: PathBuf,
+    registry_path: PathBuf,
+    emoticons: Vec<CustomEmoticon>,
+}
+
+impl EmoticonLibrary {
+    pub fn load(images_dir: PathBuf, registry_path: PathBuf) -> Result<Self, AppError> {
+        let emoticons = if registry_path.exists() {
+            info!("Loading custom emoticons from {}", registry_path.display());
+            storage::load(&registry_path, &EMOTICONS_SCHEMA)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            images_dir,
+            registry_path,
+            emoticons,
+        })
+    }
+
+    pub fn list(&self) -> &[CustomEmoticon] {
+        &self.emoticons
+    }
+
+    pub fn add(&mut self, source: &Path, shortcut: &str) -> Result<CustomEmoticon, AppError> {
+        let shortcut = shortcut.trim();
+        self.validate_shortcut(shortcut)?;
+        let image = fs::read(source)?;
+        let hash = self.store_image(&image)?;
+        let emoticon = CustomEmoticon {
+            shortcut: shortcut.to_string(),
+            hash,
+        };
+        self.emoticons.push(emoticon.clone());
+        self.save()?;
+        Ok(emoticon)
+    }
+
+    /// Removes an emoticon from the library, keeping its image for messages that already use it.
+    pub fn remove(&mut self, shortcut: &str) -> Result<(), AppError> {
+        let count = self.emoticons.len();
+        self.emoticons.retain(|e| e.shortcut != shortcut);
+        if self.emoticons.len() == count {
+            return Err(AppError::NotFound(format!(
+                "No custom emoticon with shortcut {}",
+                shortcut
+            )));
+        }
+        self.save()
+    }
+
+    /// The custom emoticons a message body shows, each listed once.
+    pub fn used_in(&self, body: &str) -> Vec<CustomEmoticon> {
+        let mut used: Vec<CustomEmoticon> = Vec::new();
+        for segment in emoticons::tokenize(body, &self.emoticons) {
+            if let emoticons::Segment::Custom { emoticon, .. } = segment {
+                if !used.contains(emoticon) {
+                    used.push(emoticon.clone());
+                }
+            }
+        }
+        used
+    }
+
+    pub fn image(&self, hash: &str) -> Result<Vec<u8>, AppError> {
+        if !is_hash(hash) {
+            return Err(AppError::Validation(format!(
+                "{} is not an emoticon hash",
+                hash
+            )));
+        }
+        let path = self.images_dir.join(hash);
+        if !path.is_file() {
+            return Err(AppError::NotFound(format!("No emoticon image {}", hash)));
+        }
+        Ok(fs::read(path)?)
+    }
+
+    pub fn image_for_transfer(&self, hash: &str) -> Result<EmoticonImage, AppError> {
+        Ok(EmoticonImage {
+            hash: hash.to_string(),
+            data: STANDARD.encode(self.image(hash)?),
+        })
+    }
+
+    /// Stores an image received from a friend, checking it matches the hash it was sent with.
+    pub fn store_received(&self, image: &EmoticonImage) -> Result<(), AppError> {
+        let bytes = STANDARD
+            .decode(&image.data)
+            .map_err(|e| AppError::Validation(format!("Invalid emoticon image data: {}", e)))?;
+        if format!("{:x}", Sha256::digest(&bytes)) != image.hash {
+            return Err(AppError::Validation(format!(
+                "Emoticon image doesn't match its hash {}",
+                image.hash
+            )));
+        }
+        self.store_image(&bytes)?;
+        Ok(())
+    }
+
+    fn validate_shortcut(&self, shortcut: &str) -> Result<(), AppError> {
+        let invalid = |reason: &str| Err(AppError::Validation(reason.to_string()));
+        if shortcut.is_empty() {
+            return invalid("Type a shortcut for the emoticon");
+        }
+        if shortcut.chars().count() > MAX_SHORTCUT_CHARS {
+            return Err(AppError::Validation(format!(
+                "Shortcuts can be at most {} characters long",
+                MAX_SHORTCUT_CHARS
+            )));
+        }
+        if shortcut
+            .chars()
+            .any(|c| c.is_whitespace() || c == '`' || c == '\\')
+        {
+            return invalid("Shortcuts can't contain spaces, backticks or backslashes");
+        }
+        if emoticons::is_built_in(shortcut) {
+            return invalid("That shortcut is already used by a built-in emoticon");
+        }
+        if self.emoticons.iter().any(|e| e.shortcut == shortcut) {
+            return invalid("You already have an emoticon with that shortcut");
+        }
+        Ok(())
+    }
+
+    fn store_image(&self, bytes: &[u8]) -> Result<String, AppError> {
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(AppError::Validation(format!(
+                "Emoticon images can be at most {} KB",
+                MAX_IMAGE_BYTES / 1024
+            )));
+        }
+        if image_mime_type(bytes).is_none() {
+            return Err(AppError::Validation(
+                "Emoticons must be PNG, GIF or JPEG images".to_string(),
+            ));
+        }
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        fs::create_dir_all(&self.images_dir)?;
+        let path = self.images_dir.join(&hash);
+        if !path.exists() {
+            fs::write(path, bytes)?;
+        }
+        Ok(hash)
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        storage::save(&self.registry_path, &EMOTICONS_SCHEMA, &self.emoticons)
+    }
+}
+
+/// Recognizes the image formats emoticons can use from their first bytes.
+pub fn image_mime_type(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else {
+        None
+    }
+}
+
+/// Whether a string is a hex SHA-256, as used to name stored images.
+pub fn is_hash(hash: &str) -> bool {
+    hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit())
+}
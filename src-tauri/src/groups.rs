@@ -0,0 +1,128 @@
+//! Group conversations the user takes part in.
+//!
+//! There is no server keeping track of who is in a group, so every member keeps their own copy
+//! and messages are sent to each of the others in turn. Whoever invites someone sends the new
+//! member list to everyone, and whoever leaves tells everyone they've gone.
+
+use std::path::PathBuf;
+
+use emiessiene_models::{AppError, ChatMessage, Friend, Group, MessageKind, Participant};
+use log::info;
+
+use super::{
+    storage::{self, Schema},
+    unix_millis,
+};
+
+const GROUPS_SCHEMA: Schema = Schema {
+    name: "group conversations",
+    migrations: &[],
+};
+
+pub struct Groups {
+    path: PathBuf,
+    groups: Vec<Group>,
+}
+
+impl Groups {
+    pub fn load(path: PathBuf) -> Result<Self, AppError> {
+        let groups = if path.exists() {
+            info!("Loading group conversations from {}", path.display());
+            storage::load(&path, &GROUPS_SCHEMA)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { path, groups })
+    }
+
+    pub fn list(&self) -> Vec<Group> {
+        self.groups.clone()
+    }
+
+    pub fn get(&self, id: &str) -> Result<&Group, AppError> {
+        self.groups
+            .iter()
+            .find(|g| g.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("No group conversation {}", id)))
+    }
+
+    /// Adds a group, or replaces the one with the same id.
+    pub fn save_group(&mut self, group: Group) -> Result<(), AppError> {
+        match self.groups.iter_mut().find(|g| g.id == group.id) {
+            Some(existing) => *existing = group,
+            None => self.groups.push(group),
+        }
+        self.save()
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<Group, AppError> {
+        let index = self
+            .groups
+            .iter()
+            .position(|g| g.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("No group conversation {}", id)))?;
+        let group = self.groups.remove(index);
+        self.save()?;
+        Ok(group)
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        storage::save(&self.path, &GROUPS_SCHEMA, &self.groups)
+    }
+}
+
+/// How a friend appears in the member list sent to the rest of a group.
+pub fn participant(friend: &Friend) -> Participant {
+    Participant {
+        email: friend.email.clone(),
+        name: friend.name.clone(),
+        address: friend.address.clone(),
+    }
+}
+
+/// A member of a group the user hasn't added as a friend, as far as the rest of the app is
+/// concerned while handling something they sent.
+pub fn stranger(participant: &Participant) -> Friend {
+    let mut friend = Friend::new(
+        participant.name.clone(),
+        participant.email.clone(),
+        None,
+        None,
+    );
+    friend.address = participant.address.clone();
+    friend
+}
+
+/// Names everyone in a group but the user, as the user knows them.
+pub fn title(group: &Group, me: &str, friends: &[Friend]) -> String {
+    group
+        .others(me)
+        .map(|p| {
+            friends
+                .iter()
+                .find(|f| f.email == p.email)
+                .map(|f| f.display_name())
+                .unwrap_or_else(|| p.name.clone())
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The system line recording that someone joined or left a group.
+pub fn membership_message(kind: MessageKind, participant: &Participant) -> ChatMessage {
+    ChatMessage {
+        id: uuid::Uuid::new_v4().to_string(),
+        from: participant.email.clone(),
+        body: participant.name.clone(),
+        sent_at: unix_millis(),
+        kind,
+        emoticons: Vec::new(),
+        style: None,
+        file: None,
+        image: None,
+        voice: None,
+        edited_at: None,
+        deleted: false,
+        status: None,
+    }
+}
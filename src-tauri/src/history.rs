@@ -0,0 +1,269 @@
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use emiessiene_models::{AppError, ChatMessage, MessageStatus, SearchQuery, SearchResult};
+use log::{info, warn};
+
+use super::{
+    search::SearchIndex,
+    storage::{self, Schema},
+};
+
+const HISTORY_SCHEMA: Schema = Schema {
+    name: "conversation history",
+    migrations: &[],
+};
+
+/// Conversations with each friend, keyed by the friend's email.
+///
+/// Each conversation lives in its own file under `dir` and is only read the first time it is
+/// needed. Every message is also added to `index` so conversations can be searched.
+pub struct History {
+    dir: PathBuf,
+    conversations: HashMap<String, Vec<ChatMessage>>,
+    index: SearchIndex,
+}
+
+impl History {
+    pub fn new(dir: PathBuf, index: SearchIndex) -> Self {
+        Self {
+            dir,
+            conversations: HashMap::new(),
+            index,
+        }
+    }
+
+    pub fn conversation(&mut self, email: &str) -> Result<&Vec<ChatMessage>, AppError> {
+        self.load(email).map(|messages| &*messages)
+    }
+
+    /// Whether a conversation already has a message with this id.
+    pub fn contains(&mut self, email: &str, id: &str) -> Result<bool, AppError> {
+        Ok(self.load(email)?.iter().any(|m| m.id == id))
+    }
+
+    /// Adds a message to the end of a conversation. Ids are how messages are edited and
+    /// acknowledged later, so a message can't reuse one the conversation already has.
+    pub fn append(&mut self, email: &str, message: ChatMessage) -> Result<(), AppError> {
+        let path = self.file_path(email);
+        let messages = self.load(email)?;
+        if messages.iter().any(|m| m.id == message.id) {
+            return Err(AppError::Validation(format!(
+                "There is already a message {} with {}",
+                message.id, email
+            )));
+        }
+        messages.push(message.clone());
+        fs::create_dir_all(path.parent().unwrap_or(&path))?;
+        storage::save(&path, &HISTORY_SCHEMA, messages)?;
+        // The message is safely in the history either way, and the index can be rebuilt from it.
+        if let Err(e) = self.index.add(email, &message) {
+            warn!("Couldn't index a message from {}: {}", email, e);
+        }
+        Ok(())
+    }
+
+    /// Changes a message in a conversation, and returns it as it is now.
+    pub fn update(
+        &mut self,
+        email: &str,
+        id: &str,
+        change: impl FnOnce(&mut ChatMessage),
+    ) -> Result<ChatMessage, AppError> {
+        let path = self.file_path(email);
+        let messages = self.load(email)?;
+        let message = messages
+            .iter_mut()
+            .find(|m| m.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("No message {} with {}", id, email)))?;
+        change(message);
+        let message = message.clone();
+        storage::save(&path, &HISTORY_SCHEMA, messages)?;
+        if let Err(e) = self.index.replace(email, &message) {
+            warn!("Couldn't index a changed message from {}: {}", email, e);
+        }
+        Ok(message)
+    }
+
+    /// Moves messages the user sent in a conversation on to `status`, unless they already got
+    /// further, and returns those that changed.
+    pub fn acknowledge(
+        &mut self,
+        email: &str,
+        ids: &[String],
+        status: MessageStatus,
+    ) -> Result<Vec<ChatMessage>, AppError> {
+        let path = self.file_path(email);
+        let messages = self.load(email)?;
+        let mut changed = Vec::new();
+        for message in messages.iter_mut().filter(|m| ids.contains(&m.id)) {
+            if message.status.is_some_and(|current| current < status) {
+                message.status = Some(status);
+                changed.push(message.clone());
+            }
+        }
+        if !changed.is_empty() {
+            storage::save(&path, &HISTORY_SCHEMA, messages)?;
+        }
+        Ok(changed)
+    }
+
+    /// Adds messages from elsewhere to a conversation in the order they were sent, leaving out
+    /// any it already has or that repeat an id earlier in `messages`, and returns how many were
+    /// added.
+    pub fn import(&mut self, email: &str, messages: Vec<ChatMessage>) -> Result<usize, AppError> {
+        let path = self.file_path(email);
+        let existing = self.load(email)?;
+        let mut ids: HashSet<String> = existing.iter().map(|m| m.id.clone()).collect();
+        let new: Vec<ChatMessage> = messages
+            .into_iter()
+            .filter(|m| ids.insert(m.id.clone()))
+            .collect();
+        if new.is_empty() {
+            return Ok(0);
+        }
+        existing.extend(new.iter().cloned());
+        existing.sort_by_key(|m| m.sent_at);
+        fs::create_dir_all(path.parent().unwrap_or(&path))?;
+        storage::save(&path, &HISTORY_SCHEMA, existing)?;
+        if let Err(e) = self.index.add_all(email, &new) {
+            warn!("Couldn't index messages imported into {}: {}", email, e);
+        }
+        Ok(new.len())
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>, AppError> {
+        self.index.search(query)
+    }
+
+    /// Fills a new search index with everything already said in these conversations.
+    pub fn reindex(&mut self, conversations: &[String]) -> Result<(), AppError> {
+        for conversation in conversations {
+            info!("Indexing conversation {}", conversation);
+            let messages = self.load(conversation)?.clone();
+            self.index.add_all(conversation, &messages)?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, email: &str) -> Result<&mut Vec<ChatMessage>, AppError> {
+        let path = self.file_path(email);
+        match self.conversations.entry(email.to_string()) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let messages = if path.exists() {
+                    info!("Loading conversation history from {}", path.display());
+                    storage::load(&path, &HISTORY_SCHEMA)?
+                } else {
+                    Vec::new()
+                };
+                Ok(entry.insert(messages))
+            }
+        }
+    }
+
+    fn file_path(&self, email: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", file_stem(email)))
+    }
+}
+
+/// A conversation's id with anything that can't go in a file name replaced.
+pub fn file_stem(conversation: &str) -> String {
+    conversation
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '@' | '.' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use emiessiene_models::MessageKind;
+
+    use super::*;
+
+    const ANN: &str = "ann@example.com";
+
+    fn test_history() -> (History, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("emiessiene-test-{}", uuid::Uuid::new_v4()));
+        let (index, _) = SearchIndex::open(Path::new(":memory:")).unwrap();
+        (History::new(dir.clone(), index), dir)
+    }
+
+    fn message(id: &str, sent_at: u64) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            from: ANN.to_string(),
+            body: format!("message {}", id),
+            sent_at,
+            kind: MessageKind::Text,
+            style: None,
+            emoticons: Vec::new(),
+            file: None,
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: None,
+        }
+    }
+
+    fn ids(history: &mut History) -> Vec<String> {
+        history
+            .conversation(ANN)
+            .unwrap()
+            .iter()
+            .map(|m| m.id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn append_refuses_an_id_already_in_the_conversation() {
+        let (mut history, dir) = test_history();
+        history.append(ANN, message("a", 10)).unwrap();
+        assert!(history.contains(ANN, "a").unwrap());
+        assert!(!history.contains(ANN, "b").unwrap());
+        let mut copy = message("a", 20);
+        copy.body = "changed".to_string();
+        assert!(matches!(
+            history.append(ANN, copy),
+            Err(AppError::Validation(_))
+        ));
+        let messages = history.conversation(ANN).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].body, "message a");
+        // Other conversations have ids of their own.
+        history.append("bob@example.com", message("a", 10)).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn import_leaves_out_repeated_ids_and_keeps_order() {
+        let (mut history, dir) = test_history();
+        history.append(ANN, message("b", 20)).unwrap();
+        let imported = vec![
+            message("c", 30),
+            message("a", 10),
+            message("b", 20),
+            message("a", 10),
+        ];
+        assert_eq!(history.import(ANN, imported).unwrap(), 2);
+        assert_eq!(ids(&mut history), ["a", "b", "c"]);
+        assert_eq!(history.import(ANN, vec![message("c", 30)]).unwrap(), 0);
+        let results = history
+            .search(&SearchQuery {
+                text: "message".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+//! Pictures shared in conversations, kept next to the history that refers to them.
+//!
+//! Each picture is stored under the hex SHA-256 of its content along with a PNG thumbnail made
+//! when it was sent or received, so a conversation full of photos only ever loads the small
+//! versions. The webview loads both through the `image` protocol registered in `main`.
+
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use emiessiene_models::{AppError, SharedImage};
+use image::{DynamicImage, ImageFormat, ImageReader, ImageResult, Limits};
+use sha2::{Digest, Sha256};
+
+use super::emoticons::{image_mime_type, is_hash};
+
+/// Largest picture that can be sent, in bytes.
+pub const MAX_IMAGE_BYTES: usize = 8 * 1024 * 1024;
+/// Largest width or height of a picture that can be sent, in pixels.
+pub const MAX_DIMENSION: u32 = 4096;
+/// Thumbnails fit in a square this many pixels wide.
+const THUMBNAIL_SIZE: u32 = 240;
+const THUMBNAIL_SUFFIX: &str = "-thumbnail";
+
+pub struct ImageStore {
+    dir: PathBuf,
+}
+
+impl ImageStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn store_file(&self, path: &Path) -> Result<SharedImage, AppError> {
+        let size = fs::metadata(path)?.len();
+        if size > MAX_IMAGE_BYTES as u64 {
+            return Err(too_large());
+        }
+        self.store(&fs::read(path)?)
+    }
+
+    pub fn store_base64(&self, data: &str) -> Result<SharedImage, AppError> {
+        let bytes = STANDARD
+            .decode(data)
+            .map_err(|e| AppError::Validation(format!("Invalid image data: {}", e)))?;
+        self.store(&bytes)
+    }
+
+    /// Checks a picture against the limits, then saves it and its thumbnail.
+    pub fn store(&self, bytes: &[u8]) -> Result<SharedImage, AppError> {
+        if bytes.len() > MAX_IMAGE_BYTES {
+            return Err(too_large());
+        }
+        if image_mime_type(bytes).is_none() {
+            return Err(AppError::Validation(
+                "Pictures must be PNG, GIF or JPEG images".to_string(),
+            ));
+        }
+        let unreadable = |e| AppError::Validation(format!("The picture couldn't be read: {}", e));
+        let (width, height) = dimensions(bytes).map_err(unreadable)?;
+        if width > MAX_DIMENSION || height > MAX_DIMENSION {
+            return Err(AppError::Validation(format!(
+                "Pictures can be at most {} by {} pixels",
+                MAX_DIMENSION, MAX_DIMENSION
+            )));
+        }
+        let picture = decode(bytes, MAX_DIMENSION).map_err(unreadable)?;
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(&hash);
+        if !path.exists() {
+            let mut thumbnail = Vec::new();
+            picture
+                .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+                .write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Png)
+                .map_err(|e| AppError::Storage(format!("Couldn't make a thumbnail: {}", e)))?;
+            fs::write(self.thumbnail_path(&hash), thumbnail)?;
+            fs::write(path, bytes)?;
+        }
+        Ok(SharedImage {
+            hash,
+            width,
+            height,
+        })
+    }
+
+    /// Stores a picture received from a friend, checking it matches the hash it was sent with.
+    pub fn store_received(&self, image: &SharedImage, data: &str) -> Result<SharedImage, AppError> {
+        let bytes = STANDARD
+            .decode(data)
+            .map_err(|e| AppError::Validation(format!("Invalid image data: {}", e)))?;
+        if format!("{:x}", Sha256::digest(&bytes)) != image.hash {
+            return Err(AppError::Validation(format!(
+                "Picture doesn't match its hash {}",
+                image.hash
+            )));
+        }
+        self.store(&bytes)
+    }
+
+    /// Reads a picture, or its thumbnail for a name ending in `-thumbnail`.
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, AppError> {
+        let (hash, path) = match name.strip_suffix(THUMBNAIL_SUFFIX) {
+            Some(hash) => (hash, self.thumbnail_path(hash)),
+            None => (name, self.dir.join(name)),
+        };
+        if !is_hash(hash) {
+            return Err(AppError::Validation(format!("{} is not a picture", name)));
+        }
+        if !path.is_file() {
+            return Err(AppError::NotFound(format!("No picture {}", name)));
+        }
+        Ok(fs::read(path)?)
+    }
+
+    pub fn image_for_transfer(&self, hash: &str) -> Result<String, AppError> {
+        Ok(STANDARD.encode(self.read(hash)?))
+    }
+
+    fn thumbnail_path(&self, hash: &str) -> PathBuf {
+        self.dir.join(format!("{}{}", hash, THUMBNAIL_SUFFIX))
+    }
+}
+
+/// Reads the size of a picture from its header, without decoding it.
+///
+/// A small file can describe a huge picture, so this comes before `decode` for anything a
+/// friend sent.
+pub fn dimensions(bytes: &[u8]) -> ImageResult<(u32, u32)> {
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_dimensions()
+}
+
+/// Decodes a picture, failing instead of allocating for one wider or taller than
+/// `max_dimension`.
+pub fn decode(bytes: &[u8], max_dimension: u32) -> ImageResult<DynamicImage> {
+    let mut reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(max_dimension);
+    limits.max_image_height = Some(max_dimension);
+    reader.limits(limits);
+    reader.decode()
+}
+
+fn too_large() -> AppError {
+    AppError::Validation(format!(
+        "Pictures can be at most {} MB",
+        MAX_IMAGE_BYTES / 1024 / 1024
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GrayImage;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        GrayImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn dimensions_come_from_the_header() {
+        assert_eq!(dimensions(&png(3, 2)).unwrap(), (3, 2));
+    }
+
+    #[test]
+    fn oversized_picture_is_refused() {
+        let store = ImageStore::new(PathBuf::from("/nonexistent"));
+        match store.store(&png(MAX_DIMENSION + 1, 1)) {
+            Err(AppError::Validation(message)) => {
+                assert!(message.contains("pixels"), "{}", message)
+            }
+            _ => panic!("oversized picture was accepted"),
+        }
+        assert!(decode(&png(MAX_DIMENSION + 1, 1), MAX_DIMENSION).is_err());
+    }
+
+    #[test]
+    fn received_picture_must_match_its_hash() {
+        let dir = std::env::temp_dir().join(format!("emiessiene-test-{}", uuid::Uuid::new_v4()));
+        let store = ImageStore::new(dir.clone());
+        let bytes = png(3, 2);
+        let shared = SharedImage {
+            hash: format!("{:x}", Sha256::digest(png(2, 3))),
+            width: 3,
+            height: 2,
+        };
+        assert!(matches!(
+            store.store_received(&shared, &STANDARD.encode(&bytes)),
+            Err(AppError::Validation(_))
+        ));
+        assert!(!dir.exists(), "a mismatched picture was written");
+
+        let shared = SharedImage {
+            hash: format!("{:x}", Sha256::digest(&bytes)),
+            ..shared
+        };
+        let stored = store
+            .store_received(&shared, &STANDARD.encode(&bytes))
+            .unwrap();
+        assert_eq!(stored.hash, shared.hash);
+        assert_eq!(store.read(&stored.hash).unwrap(), bytes);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
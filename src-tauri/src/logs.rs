@@ -0,0 +1,623 @@
+//! Conversations saved as files to keep or read elsewhere, and MSN Messenger logs read back in.
+//!
+//! MSN Messenger kept each contact's history as XML next to a stylesheet, so opening the file in
+//! a browser showed a table of messages. Logs exported in that format come with a stylesheet of
+//! their own, and logs from an old MSN archive can be imported, although they only record the
+//! names people went by, not their email addresses.
+
+use std::{collections::HashMap, fs, path::Path};
+
+use chrono::{DateTime, Local, Utc};
+use emiessiene_models::{AppError, ChatMessage, LogFormat, MessageKind, TextStyle};
+use log::info;
+use quick_xml::{
+    escape::escape,
+    events::{BytesStart, Event},
+    Reader,
+};
+use sha2::{Digest, Sha256};
+
+const MSN_STYLESHEET: &str = include_str!("../logs/MessageLog.xsl");
+const MSN_STYLESHEET_FILE: &str = "MessageLog.xsl";
+/// Messages further apart than this start a new session in MSN logs, as a new chat window did.
+const SESSION_GAP_MILLIS: u64 = 30 * 60 * 1000;
+/// How MSN Messenger showed lines that weren't messages.
+const MSN_SYSTEM_STYLE: &str = "color:#545454; ";
+
+const HTML_STYLE: &str = "
+body { font-family: 'Segoe UI', Tahoma, sans-serif; margin: 16px; background-color: #ecf5f6; }
+h1 { font-size: 1.2rem; }
+h2 { font-size: 0.95rem; color: #6b7a89; border-bottom: 1px solid #c8ded9; }
+p { margin: 2px 0; }
+.time { color: #6b7a89; font-size: 0.85rem; }
+.author { font-weight: bold; }
+.text { white-space: pre-wrap; overflow-wrap: anywhere; }
+.system { color: #6b7a89; font-style: italic; }
+";
+
+/// A conversation as it is written to a log.
+pub struct Log<'a> {
+    /// Who the conversation is with, as the user knows them.
+    pub title: String,
+    /// The name of everyone who wrote in the conversation, keyed by email.
+    pub names: &'a HashMap<String, String>,
+    /// The user's email.
+    pub me: &'a str,
+    pub messages: &'a [ChatMessage],
+}
+
+impl Log<'_> {
+    fn name(&self, email: &str) -> String {
+        self.names
+            .get(email)
+            .cloned()
+            .unwrap_or_else(|| email.to_string())
+    }
+}
+
+/// A message read from an MSN Messenger log.
+pub struct MsnMessage {
+    /// The name the sender went by.
+    pub from: String,
+    pub sent_at: u64,
+    pub text: String,
+    pub style: TextStyle,
+}
+
+/// Writes a conversation to `path`, with the stylesheet beside it for MSN logs.
+pub fn write_log(path: &Path, format: LogFormat, log: &Log) -> Result<(), AppError> {
+    info!(
+        "Writing {} messages as {:?} to {}",
+        log.messages.len(),
+        format,
+        path.display()
+    );
+    let content = match format {
+        LogFormat::Html => html(log),
+        LogFormat::Text => text(log),
+        LogFormat::MsnXml => {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            fs::write(dir.join(MSN_STYLESHEET_FILE), MSN_STYLESHEET)?;
+            msn_xml(log)
+        }
+    };
+    fs::write(path, content)?;
+    Ok(())
+}
+
+fn utc_time(millis: u64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(millis as i64).unwrap_or_default()
+}
+
+fn local_time(millis: u64) -> DateTime<Local> {
+    utc_time(millis).with_timezone(&Local)
+}
+
+/// What a message other than text said, as a sentence.
+fn describe(message: &ChatMessage, author: &str) -> Option<String> {
+    if message.deleted {
+        return Some(format!("{} deleted a message.", author));
+    }
+    match message.kind {
+        MessageKind::Text => None,
+        MessageKind::Nudge => Some(format!("{} sent a nudge.", author)),
+        MessageKind::File => Some(format!(
+            "{} sent the file {}.",
+            author,
+            message.file.as_ref().map_or("", |file| file.name.as_str())
+        )),
+        MessageKind::Image => Some(format!("{} sent a picture.", author)),
+        MessageKind::Voice => Some(format!("{} sent a voice clip.", author)),
+        MessageKind::Joined => Some(format!("{} joined the conversation.", message.body)),
+        MessageKind::Left => Some(format!("{} left the conversation.", message.body)),
+    }
+}
+
+fn text(log: &Log) -> String {
+    let mut out = format!("Conversation with {}\n\n", log.title);
+    for message in log.messages {
+        let time = local_time(message.sent_at).format("%Y-%m-%d %H:%M:%S");
+        let author = log.name(&message.from);
+        match describe(message, &author) {
+            Some(description) => out.push_str(&format!("[{}] * {}\n", time, description)),
+            None => out.push_str(&format!("[{}] {}: {}\n", time, author, message.body)),
+        }
+    }
+    out
+}
+
+fn html(log: &Log) -> String {
+    let title = escape(format!("Conversation with {}", log.title).as_str()).into_owned();
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>{}</style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, HTML_STYLE, title
+    );
+    let mut day = None;
+    for message in log.messages {
+        let time = local_time(message.sent_at);
+        if day != Some(time.date_naive()) {
+            day = Some(time.date_naive());
+            out.push_str(&format!("<h2>{}</h2>\n", time.format("%A %-d %B %Y")));
+        }
+        let author = log.name(&message.from);
+        let time = time.format("%H:%M");
+        match describe(message, &author) {
+            Some(description) => out.push_str(&format!(
+                "<p class=\"system\"><span class=\"time\">{}</span> {}</p>\n",
+                time,
+                escape(description.as_str())
+            )),
+            None => out.push_str(&format!(
+                "<p><span class=\"time\">{}</span> <span class=\"author\">{}:</span> \
+                 <span class=\"text\" style=\"{}\">{}</span></p>\n",
+                time,
+                escape(author.as_str()),
+                escape(message.style.clone().unwrap_or_default().to_css().as_str()),
+                escape(message.body.as_str())
+            )),
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn msn_xml(log: &Log) -> String {
+    let mut sessions = Vec::with_capacity(log.messages.len());
+    let mut session = 0;
+    let mut last_sent_at = None;
+    for message in log.messages {
+        if last_sent_at.is_none_or(|last| message.sent_at > last + SESSION_GAP_MILLIS) {
+            session += 1;
+        }
+        last_sent_at = Some(message.sent_at);
+        sessions.push(session);
+    }
+    let mut out = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <?xml-stylesheet type='text/xsl' href='{}'?>\n\
+         <Log FirstSessionID=\"1\" LastSessionID=\"{}\">\n",
+        MSN_STYLESHEET_FILE,
+        session.max(1)
+    );
+    let my_name = log.name(log.me);
+    for (message, session) in log.messages.iter().zip(sessions) {
+        let local = local_time(message.sent_at);
+        let attributes = format!(
+            "Date=\"{}\" Time=\"{}\" DateTime=\"{}\" SessionID=\"{}\"",
+            local.format("%m/%d/%Y"),
+            local.format("%H:%M:%S"),
+            utc_time(message.sent_at).format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+            session
+        );
+        let author = log.name(&message.from);
+        let user = |name: &str| format!("<User FriendlyName=\"{}\"/>", escape(name));
+        let system_text = |description: String| {
+            format!(
+                "<Text Style=\"{}\">{}</Text>",
+                MSN_SYSTEM_STYLE,
+                escape(description.as_str())
+            )
+        };
+        let element = match (message.kind, describe(message, &author)) {
+            (MessageKind::Joined, Some(description)) => format!(
+                "<Join {}>{}{}</Join>",
+                attributes,
+                user(&message.body),
+                system_text(description)
+            ),
+            (MessageKind::Left, Some(description)) => format!(
+                "<Leave {}>{}{}</Leave>",
+                attributes,
+                user(&message.body),
+                system_text(description)
+            ),
+            (MessageKind::File, Some(description)) => format!(
+                "<Invitation {}><From>{}</From><File>{}</File>{}</Invitation>",
+                attributes,
+                user(&author),
+                escape(message.file.as_ref().map_or("", |file| file.name.as_str())),
+                system_text(description)
+            ),
+            (_, description) => {
+                let to = if message.from == log.me {
+                    log.title.clone()
+                } else {
+                    my_name.clone()
+                };
+                let text = match description {
+                    Some(description) => system_text(description),
+                    None => format!(
+                        "<Text Style=\"{}\">{}</Text>",
+                        escape(msn_style(&message.style.clone().unwrap_or_default()).as_str()),
+                        escape(message.body.as_str())
+                    ),
+                };
+                format!(
+                    "<Message {}><From>{}</From><To>{}</To>{}</Message>",
+                    attributes,
+                    user(&author),
+                    user(&to),
+                    text
+                )
+            }
+        };
+        out.push_str(&element);
+        out.push('\n');
+    }
+    out.push_str("</Log>\n");
+    out
+}
+
+/// A text style the way MSN Messenger wrote it in the `Style` attribute of a message's text.
+///
+/// The stylesheet copies the attribute straight into the page, so a friend's style that isn't a
+/// known font and a plain color is written as the default one.
+fn msn_style(style: &TextStyle) -> String {
+    let default = TextStyle::default();
+    let style = if style.validate().is_ok() {
+        style
+    } else {
+        &default
+    };
+    let mut css = format!("font-family:{}; color:{}; ", style.font, style.color);
+    if style.bold {
+        css.push_str("font-weight:bold; ");
+    }
+    if style.italic {
+        css.push_str("font-style:italic; ");
+    }
+    match (style.underline, style.strikethrough) {
+        (true, true) => css.push_str("text-decoration:underline line-through; "),
+        (true, false) => css.push_str("text-decoration:underline; "),
+        (false, true) => css.push_str("text-decoration:line-through; "),
+        (false, false) => {}
+    }
+    css
+}
+
+/// Reads a text style from MSN Messenger's `Style` attribute, keeping only the fonts and colors
+/// a style may have.
+fn style_from_msn(css: &str) -> TextStyle {
+    let mut style = TextStyle::default();
+    for declaration in css.split(';') {
+        let Some((property, value)) = declaration.split_once(':') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(['"', '\'']);
+        match property.trim() {
+            "font-family" => {
+                if let Some(font) = TextStyle::FONTS
+                    .iter()
+                    .find(|f| f.eq_ignore_ascii_case(value))
+                {
+                    style.font = font.to_string();
+                }
+            }
+            "color" => style.color = value.to_lowercase(),
+            "font-weight" => style.bold = value == "bold",
+            "font-style" => style.italic = value == "italic",
+            "text-decoration" => {
+                style.underline = value.contains("underline");
+                style.strikethrough = value.contains("line-through");
+            }
+            _ => {}
+        }
+    }
+    if style.validate().is_err() {
+        style.color = TextStyle::default().color;
+    }
+    style
+}
+
+/// Reads the messages of an MSN Messenger log. Invitations, people joining and the like are left
+/// out, since only the text of what was said was kept.
+pub fn read_msn_log(path: &Path) -> Result<Vec<MsnMessage>, AppError> {
+    info!("Reading MSN Messenger log from {}", path.display());
+    let content = fs::read_to_string(path)?;
+    let mut reader = Reader::from_str(&content);
+    let mut messages = Vec::new();
+    let mut current: Option<MsnMessage> = None;
+    let mut seen_log = false;
+    let (mut in_from, mut in_text) = (false, false);
+    loop {
+        match reader.read_event().map_err(invalid_log)? {
+            Event::Start(element) => match element.name().as_ref() {
+                b"Log" => seen_log = true,
+                _ if !seen_log => return Err(not_a_log()),
+                b"Message" => {
+                    current = Some(MsnMessage {
+                        from: String::new(),
+                        sent_at: message_time(&element)?,
+                        text: String::new(),
+                        style: TextStyle::default(),
+                    })
+                }
+                b"From" => in_from = true,
+                b"User" if in_from => set_sender(&mut current, &element)?,
+                b"Text" => {
+                    if let Some(message) = current.as_mut() {
+                        in_text = true;
+                        message.style =
+                            style_from_msn(&attribute(&element, "Style")?.unwrap_or_default());
+                    }
+                }
+                _ => {}
+            },
+            Event::Empty(element) => match element.name().as_ref() {
+                _ if !seen_log => return Err(not_a_log()),
+                b"User" if in_from => set_sender(&mut current, &element)?,
+                _ => {}
+            },
+            Event::Text(text) if in_text => {
+                if let Some(message) = current.as_mut() {
+                    message
+                        .text
+                        .push_str(&text.unescape().map_err(invalid_log)?);
+                }
+            }
+            Event::End(element) => match element.name().as_ref() {
+                b"Message" => messages.extend(current.take().filter(|m| !m.text.is_empty())),
+                b"From" => in_from = false,
+                b"Text" => in_text = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if !seen_log {
+        return Err(not_a_log());
+    }
+    Ok(messages)
+}
+
+/// The message an entry of an MSN log becomes in a conversation. Its id comes from the entry's
+/// content, so importing the same log twice doesn't add its messages twice.
+pub fn imported_message(entry: &MsnMessage, from: &str) -> ChatMessage {
+    let digest = Sha256::digest(format!("{}\n{}\n{}", entry.sent_at, entry.from, entry.text));
+    ChatMessage {
+        id: format!("msn-{:x}", digest)[..36].to_string(),
+        from: from.to_string(),
+        body: entry.text.clone(),
+        sent_at: entry.sent_at,
+        kind: MessageKind::Text,
+        emoticons: Vec::new(),
+        style: Some(entry.style.clone()),
+        file: None,
+        image: None,
+        voice: None,
+        edited_at: None,
+        deleted: false,
+        status: None,
+    }
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, AppError> {
+    element
+        .try_get_attribute(name)
+        .map_err(invalid_log)?
+        .map(|attribute| {
+            attribute
+                .unescape_value()
+                .map(|value| value.into_owned())
+                .map_err(invalid_log)
+        })
+        .transpose()
+}
+
+fn set_sender(current: &mut Option<MsnMessage>, user: &BytesStart) -> Result<(), AppError> {
+    if let Some(message) = current.as_mut() {
+        message.from = attribute(user, "FriendlyName")?.unwrap_or_default();
+    }
+    Ok(())
+}
+
+/// When a message was sent, from the UTC `DateTime` MSN Messenger recorded beside the local date
+/// and time, whose format depended on the user's language.
+fn message_time(message: &BytesStart) -> Result<u64, AppError> {
+    let date_time = attribute(message, "DateTime")?
+        .ok_or_else(|| AppError::Validation("A message in the log has no DateTime".to_string()))?;
+    let sent_at = DateTime::parse_from_rfc3339(&date_time)
+        .map_err(|e| AppError::Validation(format!("Invalid message time {}: {}", date_time, e)))?;
+    Ok(sent_at.timestamp_millis().max(0) as u64)
+}
+
+fn not_a_log() -> AppError {
+    AppError::Validation("The file isn't an MSN Messenger log".to_string())
+}
+
+fn invalid_log(e: impl std::fmt::Display) -> AppError {
+    AppError::Validation(format!("Invalid MSN Messenger log: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(font: &str, color: &str) -> TextStyle {
+        TextStyle {
+            font: font.to_string(),
+            color: color.to_string(),
+            bold: true,
+            ..Default::default()
+        }
+    }
+
+    fn message(id: &str, from: &str, body: &str, sent_at: u64) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            from: from.to_string(),
+            body: body.to_string(),
+            sent_at,
+            kind: MessageKind::Text,
+            emoticons: Vec::new(),
+            style: Some(style("Georgia", "#aa0000")),
+            file: None,
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: None,
+        }
+    }
+
+    fn test_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("emiessiene-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const SAMPLE_LOG: &str = r#"<?xml version="1.0"?>
+<?xml-stylesheet type='text/xsl' href='MessageLog.xsl'?>
+<Log FirstSessionID="1" LastSessionID="1">
+<Message Date="3/14/2004" Time="21:07:11" DateTime="2004-03-14T20:07:11.250Z" SessionID="1">
+<From><User FriendlyName="~*Ann*~ &amp; co"/></From><To><User FriendlyName="Bob"/></To>
+<Text Style="font-family:&quot;Comic Sans MS&quot;; color:#FF0000; font-weight:bold; text-decoration:underline line-through; ">hi &lt;3</Text>
+</Message>
+<Invitation Date="3/14/2004" Time="21:08:00" DateTime="2004-03-14T20:08:00.000Z" SessionID="1">
+<From><User FriendlyName="Bob"/></From><File>photo.jpg</File><Text>Bob sends photo.jpg</Text>
+</Invitation>
+<Message Date="3/14/2004" Time="21:09:00" DateTime="2004-03-14T20:09:00.000Z" SessionID="1">
+<From><User FriendlyName="Bob"/></From><To><User FriendlyName="~*Ann*~"/></To>
+<Text Style="font-family:Wingdings; color:rgb(1,2,3); font-style:italic">hey</Text>
+</Message>
+</Log>
+"#;
+
+    #[test]
+    fn reads_messages_from_an_msn_log() {
+        let dir = test_dir();
+        let path = dir.join("Ann.xml");
+        fs::write(&path, SAMPLE_LOG).unwrap();
+        let messages = read_msn_log(&path).unwrap();
+        assert_eq!(messages.len(), 2);
+
+        assert_eq!(messages[0].from, "~*Ann*~ & co");
+        assert_eq!(messages[0].text, "hi <3");
+        assert_eq!(messages[0].sent_at, 1_079_294_831_250);
+        let first = &messages[0].style;
+        assert_eq!(
+            (first.font.as_str(), first.color.as_str()),
+            ("Comic Sans MS", "#ff0000")
+        );
+        assert!(first.bold && first.underline && first.strikethrough && !first.italic);
+
+        // Unknown fonts and colors fall back to the defaults, the rest is kept.
+        let second = &messages[1].style;
+        assert_eq!(second.font, TextStyle::default().font);
+        assert_eq!(second.color, TextStyle::default().color);
+        assert!(second.italic && !second.bold);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn other_files_are_not_logs() {
+        let dir = test_dir();
+        let path = dir.join("page.xml");
+        fs::write(&path, "<html><body/></html>").unwrap();
+        assert!(matches!(read_msn_log(&path), Err(AppError::Validation(_))));
+        fs::write(&path, "<Log><Message><Text>hi</Text></Message></Log>").unwrap();
+        assert!(matches!(read_msn_log(&path), Err(AppError::Validation(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn identical_entries_get_the_same_id() {
+        let entry = MsnMessage {
+            from: "Ann".to_string(),
+            sent_at: 1_000,
+            text: "ok".to_string(),
+            style: TextStyle::default(),
+        };
+        let again = imported_message(&entry, "ann@example.com");
+        assert_eq!(imported_message(&entry, "ann@example.com").id, again.id);
+        assert_eq!(again.id.len(), 36);
+        let later = MsnMessage {
+            sent_at: 2_000,
+            ..entry
+        };
+        assert_ne!(imported_message(&later, "ann@example.com").id, again.id);
+    }
+
+    #[test]
+    fn exports_round_trip() {
+        let names = HashMap::from([
+            ("me@example.com".to_string(), "Me & <you>".to_string()),
+            ("ann@example.com".to_string(), "Ann".to_string()),
+        ]);
+        let messages = [
+            message("1", "me@example.com", "hi <b>Ann</b> & \"co\"", 1_000_000),
+            message("2", "ann@example.com", "two\nlines", 1_060_000),
+            // Far enough apart to start a second session.
+            message(
+                "3",
+                "ann@example.com",
+                "later",
+                1_000_000 + 2 * SESSION_GAP_MILLIS,
+            ),
+        ];
+        let log = Log {
+            title: "Ann".to_string(),
+            names: &names,
+            me: "me@example.com",
+            messages: &messages,
+        };
+        let dir = test_dir();
+
+        let path = dir.join("Ann.xml");
+        write_log(&path, LogFormat::MsnXml, &log).unwrap();
+        assert!(dir.join(MSN_STYLESHEET_FILE).is_file());
+        let xml = fs::read_to_string(&path).unwrap();
+        assert!(xml.contains("LastSessionID=\"2\""));
+        let read = read_msn_log(&path).unwrap();
+        let read: Vec<_> = read
+            .iter()
+            .map(|m| (m.from.as_str(), m.sent_at, m.text.as_str(), &m.style))
+            .collect();
+        let written: Vec<_> = messages
+            .iter()
+            .map(|m| {
+                let from = if m.from == "me@example.com" {
+                    "Me & <you>"
+                } else {
+                    "Ann"
+                };
+                (from, m.sent_at, m.body.as_str(), m.style.as_ref().unwrap())
+            })
+            .collect();
+        assert_eq!(read, written);
+
+        let path = dir.join("Ann.txt");
+        write_log(&path, LogFormat::Text, &log).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with("Conversation with Ann\n\n"));
+        assert!(text.contains("] Me & <you>: hi <b>Ann</b> & \"co\"\n"));
+        assert!(text.contains("] Ann: two\nlines\n"));
+        assert_eq!(text.lines().filter(|l| l.starts_with('[')).count(), 3);
+
+        let path = dir.join("Ann.html");
+        write_log(&path, LogFormat::Html, &log).unwrap();
+        let html = fs::read_to_string(&path).unwrap();
+        assert!(html.contains("<title>Conversation with Ann</title>"));
+        assert!(html.contains("Me &amp; &lt;you&gt;:"));
+        assert!(html.contains("hi &lt;b&gt;Ann&lt;/b&gt; &amp; &quot;co&quot;"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains(&escape(style("Georgia", "#aa0000").to_css().as_str()).into_owned()));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn msn_style_only_writes_valid_styles() {
+        assert_eq!(
+            msn_style(&style("Tahoma", "#1a2b3c")),
+            "font-family:Tahoma; color:#1a2b3c; font-weight:bold; "
+        );
+        let default = msn_style(&TextStyle::default());
+        assert_eq!(
+            msn_style(&style("Tahoma", "red; background:url(x)")),
+            default
+        );
+        assert_eq!(msn_style(&style("x\"><script>", "#000000")), default);
+    }
+}
@@ -1,95 +1,188 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{env, fmt::Display, fs::File, io::BufReader, sync::Mutex};
+mod avatars;
+mod contacts;
+mod emoticons;
+mod groups;
+mod history;
+mod images;
+mod logs;
+mod notifications;
+mod receipts;
+mod search;
+mod sounds;
+mod storage;
+mod transfers;
+mod transport;
+mod tray;
+mod voice;
+mod windows;
 
-use serde::{Deserialize, Serialize};
-use tauri::{command, State};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use log::{debug, info};
+use avatars::AvatarStore;
+use emiessiene_models::emoticons::CustomEmoticon;
+use emiessiene_models::{
+    events, is_group, validate_email, AddCustomEmoticonArgs, AddFriendArgs, AppError, Availability,
+    ChatLayout, ChatMessage, ChatMessageEvent, ChatWindowArgs, DeleteMessageArgs, EditMessageArgs,
+    ExportContactsArgs, ExportConversationArgs, FileOffer, FileTransfer, FileTransferArgs,
+    FocusChatArgs, Friend, GetConversationArgs, Group, ImageSource, ImportContactsArgs,
+    ImportMsnLogArgs, ImportPreview, InviteToConversationArgs, LeaveConversationArgs,
+    ListFileTransfersArgs, LogImportPreview, MessageKind, MessageStatus, Participant,
+    PlaySoundArgs, RemoveCustomEmoticonArgs, SearchMessagesArgs, SearchResult, SendFileArgs,
+    SendImageArgs, SendMessageArgs, SendNudgeArgs, SendTypingArgs, SendVoiceClipArgs,
+    SetAvailabilityArgs, SetDisplayPictureArgs, Settings, SharedImage, ShowMessageArgs,
+    TransferStatus, UpdateContactArgs, UpdateFriendArgs, UpdateSettingsArgs, UpdateUsernameArgs,
+    User, VoiceClip, GROUP_PREFIX,
+};
+use emoticons::{EmoticonImage, EmoticonLibrary};
+use groups::Groups;
+use history::History;
+use images::ImageStore;
+use receipts::ReceiptsAhead;
+use search::SearchIndex;
+use serde::Serialize;
+use serde_json::Value;
+use sounds::SoundPlayer;
+use storage::Schema;
+use tauri::{command, http::ResponseBuilder, AppHandle, Manager, RunEvent, State, WindowEvent};
+use transfers::Transfers;
+use transport::PeerEvent;
+use voice::ClipStore;
+use windows::ChatWindows;
+
+use log::{info, warn};
 
 type App<'a> = State<'a, Mutex<AppState>>;
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 
-#[derive(Serialize, Deserialize, Clone)]
-struct User {
-    name: String,
-    email: String,
-    status: String,
-    availability: Availability,
-}
+const FRIENDS_FILE: &str = "friends.json";
+const USER_FILE: &str = "user.json";
+const SETTINGS_FILE: &str = "settings.json";
+const HISTORY_DIR: &str = "history";
+/// Pictures shared in conversations, inside `HISTORY_DIR`.
+const IMAGES_DIR: &str = "images";
+/// Full-text index of every conversation, inside `HISTORY_DIR`.
+const SEARCH_INDEX_FILE: &str = "search.sqlite3";
+/// Voice clips sent and received, inside `HISTORY_DIR`.
+const CLIPS_DIR: &str = "clips";
+/// Display pictures of the user and their friends.
+const AVATARS_DIR: &str = "avatars";
+const EMOTICONS_FILE: &str = "emoticons.json";
+/// Images of the user's custom emoticons and of those received from friends.
+const EMOTICONS_DIR: &str = "emoticons";
+/// User-installed sound packs, which take precedence over the bundled ones.
+const SOUNDS_DIR: &str = "sounds";
+const TRANSFERS_FILE: &str = "transfers.json";
+const GROUPS_FILE: &str = "groups.json";
+const WINDOWS_FILE: &str = "windows.json";
 
-#[derive(Serialize, Deserialize, Clone)]
-struct UserJson {
-    name: String,
-    email: String,
-    status: String,
-    availability: String,
-}
+/// Shortest time between two nudges in the same conversation, in either direction.
+const NUDGE_INTERVAL: Duration = Duration::from_secs(10);
+/// Shortest time between two typing notifications sent to the same friend.
+const TYPING_INTERVAL: Duration = Duration::from_secs(3);
 
-#[derive(Serialize, Deserialize, Clone)]
-struct Friend {
-    name: String,
-    email: String,
-    status: String,
-    availability: Availability,
+const FRIENDS_SCHEMA: Schema = Schema {
+    name: "friends list",
+    migrations: &[envelope_only_migration],
+};
+
+const USER_SCHEMA: Schema = Schema {
+    name: "user profile",
+    migrations: &[envelope_only_migration],
+};
+
+const SETTINGS_SCHEMA: Schema = Schema {
+    name: "settings",
+    migrations: &[],
+};
+
+/// v1 wrapped the original layout in a versioned envelope without changing the data itself.
+fn envelope_only_migration(data: Value) -> Result<Value, AppError> {
+    Ok(data)
 }
 
-impl Friend {
-    fn new(
-        name: String,
-        email: String,
-        status: Option<String>,
-        availability: Option<Availability>,
-    ) -> Self {
-        info!("Creating new friend: {} <{}>", name, email);
-        Self {
-            name,
-            email,
-            status: status.unwrap_or("".to_string()),
-            availability: availability.unwrap_or(Availability::Online),
-        }
-    }
+/// Locks the app state, recovering it if a command panicked while holding the lock.
+///
+/// Every command leaves the state consistent before doing anything that can fail, so the data
+/// behind a poisoned lock is still safe to use.
+fn lock(state: &Mutex<AppState>) -> MutexGuard<'_, AppState> {
+    state.lock().unwrap_or_else(|poisoned| {
+        warn!("Recovering app state after a command panicked while holding the lock");
+        PoisonError::into_inner(poisoned)
+    })
+}
 
-    fn update(
-        &mut self,
-        name: Option<String>,
-        status: Option<String>,
-        availability: Option<Availability>,
-    ) {
-        info!("Updating friend: {}", self.email);
-        if let Some(name) = name {
-            self.name = name;
-        }
-        if let Some(status) = status {
-            self.status = status;
-        }
-        if let Some(availability) = availability {
-            self.availability = availability;
-        }
-    }
+/// Resolves a data file inside `EMIESSIENE_DATA_DIR`, or the working directory if it isn't set.
+fn data_path(name: &str) -> PathBuf {
+    env::var_os("EMIESSIENE_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(name)
 }
 
-#[derive(Serialize, Deserialize)]
-struct FriendJson {
-    name: String,
-    email: String,
-    status: String,
-    availability: String,
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_else(|e| {
+            warn!("System clock is before the Unix epoch: {}", e);
+            0
+        })
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub enum Availability {
-    Online,
-    Away,
-    Busy,
-    Offline,
+/// Sends an event to every window, logging instead of failing since nothing is waiting on it.
+fn emit_all<S: Serialize + Clone>(app: &AppHandle, event: &str, payload: S) {
+    if let Err(e) = app.emit_all(event, payload) {
+        warn!("Failed to emit {}: {}", event, e);
+    }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
 struct AppState {
     user: User,
     friends: Vec<Friend>,
+    settings: Settings,
+    sounds: SoundPlayer,
+    emoticons: EmoticonLibrary,
+    /// Hashes of the custom emoticon images already sent to each friend, keyed by email.
+    emoticons_sent: HashMap<String, HashSet<String>>,
+    history: History,
+    images: ImageStore,
+    clips: ClipStore,
+    avatars: AvatarStore,
+    transfers: Transfers,
+    groups: Groups,
+    chat_windows: ChatWindows,
+    /// The conversation open in the main window, if any.
+    active_chat: Option<String>,
+    window_focused: bool,
+    /// The conversation whose own window has the focus, if any.
+    focused_chat_window: Option<String>,
+    /// When the last nudge was sent to each friend, keyed by email.
+    nudges_sent: HashMap<String, Instant>,
+    /// When the last nudge from each friend was shown, keyed by email.
+    nudges_received: HashMap<String, Instant>,
+    /// When the last typing notification was sent to each friend, keyed by email.
+    typing_sent: HashMap<String, Instant>,
+    /// When each friend who is writing a message last said so, keyed by email.
+    typing_received: HashMap<String, Instant>,
+    /// Messages received in each conversation while it wasn't on screen, keyed by conversation.
+    unread: HashMap<String, u32>,
+    /// Messages received from each friend while their conversation wasn't on screen, to
+    /// acknowledge as read once it is. Keyed by email.
+    read_receipts_due: HashMap<String, Vec<String>>,
+    /// Acknowledgements of messages still being sent, which aren't in the history yet.
+    receipts_ahead: ReceiptsAhead,
 }
 
 impl AppState {
@@ -105,6 +198,150 @@ impl AppState {
         offline_friends.sort_by(|a, b| a.email.cmp(&b.email));
         (online_friends, offline_friends)
     }
+
+    fn friend_mut(&mut self, email: &str) -> Result<&mut Friend, AppError> {
+        self.friends
+            .iter_mut()
+            .find(|f| f.email == email)
+            .ok_or_else(|| AppError::NotFound(format!("No friend with email {}", email)))
+    }
+
+    /// Applies a presence update sent by a friend, returning them if they have just signed in
+    /// and want to be announced.
+    fn update_presence(
+        &mut self,
+        email: &str,
+        name: String,
+        status: String,
+        availability: Availability,
+    ) -> Result<Option<Friend>, AppError> {
+        let friend = self.friend_mut(email)?;
+        let signed_in =
+            friend.availability == Availability::Offline && availability != Availability::Offline;
+        friend.update(Some(name), Some(status), Some(availability));
+        Ok(Some(friend.clone()).filter(|f| signed_in && !f.muted))
+    }
+
+    /// Records the display picture a friend announced, returning its hash if it still has to be
+    /// downloaded from them. Until it arrives they keep showing their previous picture.
+    fn update_display_picture(
+        &mut self,
+        email: &str,
+        picture: Option<String>,
+    ) -> Result<Option<String>, AppError> {
+        if let Some(hash) = picture.as_ref().filter(|hash| !self.avatars.contains(hash)) {
+            return Ok(Some(hash.clone()));
+        }
+        let friend = self.friend_mut(email)?;
+        if friend.display_picture != picture {
+            friend.display_picture = picture;
+            save_friends_list(&data_path(FRIENDS_FILE), &self.friends)?;
+        }
+        Ok(None)
+    }
+
+    /// Whether the user is looking at this conversation right now.
+    fn is_chat_focused(&self, conversation: &str) -> bool {
+        (self.window_focused && self.active_chat.as_deref() == Some(conversation))
+            || self.focused_chat_window.as_deref() == Some(conversation)
+    }
+
+    /// Forgets the unread messages of a conversation the user is now looking at, returning
+    /// whether there were any.
+    fn mark_read(&mut self, conversation: &str) -> bool {
+        self.unread.remove(conversation).is_some()
+    }
+
+    /// Who a conversation is with, as the user knows them.
+    fn conversation_title(&self, conversation: &str) -> Result<String, AppError> {
+        if is_group(conversation) {
+            let group = self.groups.get(conversation)?;
+            return Ok(groups::title(group, &self.user.email, &self.friends));
+        }
+        self.friends
+            .iter()
+            .find(|f| f.email == conversation)
+            .map(|f| f.display_name())
+            .ok_or_else(|| AppError::NotFound(format!("No friend with email {}", conversation)))
+    }
+
+    /// The name of everyone the user has talked with, and their own, keyed by email.
+    fn known_names(&self) -> HashMap<String, String> {
+        let mut names: HashMap<String, String> = self
+            .groups
+            .list()
+            .into_iter()
+            .flat_map(|g| g.participants)
+            .map(|p| (p.email, p.name))
+            .collect();
+        names.extend(
+            self.friends
+                .iter()
+                .map(|f| (f.email.clone(), f.display_name())),
+        );
+        names.insert(self.user.email.clone(), self.user.name.clone());
+        names
+    }
+
+    /// The custom emoticons used in `body`, and the images of those not yet sent to the
+    /// conversation.
+    fn emoticons_to_send(
+        &self,
+        conversation: &str,
+        body: &str,
+    ) -> Result<(Vec<CustomEmoticon>, Vec<EmoticonImage>), AppError> {
+        let used = self.emoticons.used_in(body);
+        let already_sent = self.emoticons_sent.get(conversation);
+        let images = used
+            .iter()
+            .filter(|e| already_sent.is_none_or(|sent| !sent.contains(&e.hash)))
+            .map(|e| self.emoticons.image_for_transfer(&e.hash))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((used, images))
+    }
+
+    /// A message the user sent and can still edit or delete.
+    fn own_message(&mut self, conversation: &str, id: &str) -> Result<ChatMessage, AppError> {
+        let message = self
+            .history
+            .conversation(conversation)?
+            .iter()
+            .find(|m| m.id == id)
+            .cloned()
+            .ok_or_else(|| {
+                AppError::NotFound(format!("No message {} with {}", id, conversation))
+            })?;
+        message.check_editable(
+            &self.user.email,
+            unix_millis(),
+            self.settings.edit_window_minutes,
+        )?;
+        Ok(message)
+    }
+
+    /// Whether a conversation still exists, so a window left open for it can be restored.
+    fn has_conversation(&self, conversation: &str) -> bool {
+        if is_group(conversation) {
+            self.groups.get(conversation).is_ok()
+        } else {
+            self.friends.iter().any(|f| f.email == conversation)
+        }
+    }
+}
+
+/// Whether the last event for `email` in `log` was less than `interval` ago.
+fn too_soon(log: &HashMap<String, Instant>, email: &str, interval: Duration) -> bool {
+    log.get(email).is_some_and(|last| last.elapsed() < interval)
+}
+
+/// Records an event for `email` in `log` and returns true, unless the last one was less than
+/// `interval` ago.
+fn throttle(log: &mut HashMap<String, Instant>, email: &str, interval: Duration) -> bool {
+    if too_soon(log, email, interval) {
+        return false;
+    }
+    log.insert(email.to_string(), Instant::now());
+    true
 }
 
 impl Display for AppState {
@@ -118,57 +355,154 @@ impl Display for AppState {
     }
 }
 
-fn load_friends_list(file_path: &str) -> Result<Vec<Friend>, Box<dyn std::error::Error>> {
-    info!("Loading friends list from {}", file_path);
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let json: Vec<FriendJson> = serde_json::from_reader(reader)?;
-
-    let friends: Vec<Friend> = json
-        .into_iter()
-        .map(|f| Friend {
-            name: f.name,
-            email: f.email,
-            status: f.status,
-            availability: match f.availability.as_str() {
-                "Online" => Availability::Online,
-                "Away" => Availability::Away,
-                "Busy" => Availability::Busy,
-                _ => Availability::Offline,
-            },
-        })
-        .collect();
-
+fn load_friends_list(file_path: &Path) -> Result<Vec<Friend>, AppError> {
+    info!("Loading friends list from {}", file_path.display());
+    let friends: Vec<Friend> = storage::load(file_path, &FRIENDS_SCHEMA)?;
     info!("Loaded {} friends", friends.len());
     Ok(friends)
 }
 
-fn load_user(file_path: &str) -> Result<User, Box<dyn std::error::Error>> {
-    info!("Loading user from {}", file_path);
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let json: UserJson = serde_json::from_reader(reader)?;
-
-    let user = User {
-        name: json.name,
-        email: json.email,
-        status: json.status,
-        availability: match json.availability.as_str() {
-            "Online" => Availability::Online,
-            "Away" => Availability::Away,
-            "Busy" => Availability::Busy,
-            _ => Availability::Offline,
-        },
-    };
+fn save_friends_list(file_path: &Path, friends: &[Friend]) -> Result<(), AppError> {
+    info!(
+        "Saving {} friends to {}",
+        friends.len(),
+        file_path.display()
+    );
+    storage::save(file_path, &FRIENDS_SCHEMA, &friends)
+}
+
+fn load_user(file_path: &Path) -> Result<User, AppError> {
+    info!("Loading user from {}", file_path.display());
+    let user: User = storage::load(file_path, &USER_SCHEMA)?;
     info!("Loaded user: {}", user.name);
     Ok(user)
 }
 
+fn save_user(file_path: &Path, user: &User) -> Result<(), AppError> {
+    info!("Saving user to {}", file_path.display());
+    storage::save(file_path, &USER_SCHEMA, user)
+}
+
+/// Wraps `tauri::generate_handler!`, failing to compile if a command is registered without being
+/// listed in `emiessiene_models::commands`, which is where the frontend takes command names from.
+macro_rules! handlers {
+    ($($cmd:ident),* $(,)?) => {{
+        $(let _ = emiessiene_models::commands::$cmd;)*
+        tauri::generate_handler![$($cmd),*]
+    }};
+}
+
+/// Passes every command the app registers with Tauri to the macro `$then`.
+macro_rules! app_commands {
+    ($then:ident) => {
+        $then![
+            get_user,
+            get_friends,
+            update_friend,
+            add_friend,
+            update_contact,
+            import_contacts,
+            export_contacts,
+            update_username,
+            set_display_picture,
+            set_availability,
+            sign_out,
+            get_conversation,
+            send_message,
+            focus_chat,
+            get_unread_counts,
+            get_settings,
+            update_settings,
+            list_sound_packs,
+            play_sound,
+            send_nudge,
+            send_typing,
+            list_custom_emoticons,
+            add_custom_emoticon,
+            remove_custom_emoticon,
+            send_file,
+            accept_file,
+            decline_file,
+            resume_file,
+            cancel_file,
+            list_file_transfers,
+            send_image,
+            send_voice_clip,
+            list_groups,
+            invite_to_conversation,
+            leave_conversation,
+            open_chat_window,
+            close_chat_window,
+            search_messages,
+            show_message,
+            export_conversation,
+            import_msn_log,
+            edit_message,
+            delete_message,
+        ]
+    };
+}
+
+fn load_settings(file_path: &Path) -> Result<Settings, AppError> {
+    if !file_path.exists() {
+        info!("No settings at {}, using defaults", file_path.display());
+        return Ok(Settings::default());
+    }
+    info!("Loading settings from {}", file_path.display());
+    storage::load(file_path, &SETTINGS_SCHEMA)
+}
+
 fn init_state() -> AppState {
     info!("Initializing application state");
-    let friends = load_friends_list("friends.json").expect("Failed to load friends list");
-    let user = load_user("user.json").expect("Failed to load user");
-    AppState { user, friends }
+    let friends = load_friends_list(&data_path(FRIENDS_FILE)).expect("Failed to load friends list");
+    let user = load_user(&data_path(USER_FILE)).expect("Failed to load user");
+    let settings = load_settings(&data_path(SETTINGS_FILE)).expect("Failed to load settings");
+    let emoticons = EmoticonLibrary::load(data_path(EMOTICONS_DIR), data_path(EMOTICONS_FILE))
+        .expect("Failed to load custom emoticons");
+    let transfers =
+        Transfers::load(data_path(TRANSFERS_FILE)).expect("Failed to load file transfers");
+    let groups = Groups::load(data_path(GROUPS_FILE)).expect("Failed to load group conversations");
+    let chat_windows =
+        ChatWindows::load(data_path(WINDOWS_FILE)).expect("Failed to load chat windows");
+    fs::create_dir_all(data_path(HISTORY_DIR)).expect("Failed to create the history folder");
+    let (index, index_created) = SearchIndex::open(&data_path(HISTORY_DIR).join(SEARCH_INDEX_FILE))
+        .expect("Failed to open the search index");
+    let mut history = History::new(data_path(HISTORY_DIR), index);
+    if index_created {
+        let conversations: Vec<String> = friends
+            .iter()
+            .map(|f| f.email.clone())
+            .chain(groups.list().into_iter().map(|g| g.id))
+            .collect();
+        if let Err(e) = history.reindex(&conversations) {
+            warn!("Couldn't index your conversations for search: {}", e);
+        }
+    }
+    AppState {
+        user,
+        friends,
+        settings,
+        sounds: SoundPlayer::new(vec![data_path(SOUNDS_DIR)]),
+        emoticons,
+        emoticons_sent: HashMap::new(),
+        history,
+        images: ImageStore::new(data_path(HISTORY_DIR).join(IMAGES_DIR)),
+        clips: ClipStore::new(data_path(HISTORY_DIR).join(CLIPS_DIR)),
+        avatars: AvatarStore::new(data_path(AVATARS_DIR)),
+        transfers,
+        groups,
+        chat_windows,
+        active_chat: None,
+        window_focused: true,
+        focused_chat_window: None,
+        nudges_sent: HashMap::new(),
+        nudges_received: HashMap::new(),
+        typing_sent: HashMap::new(),
+        typing_received: HashMap::new(),
+        unread: HashMap::new(),
+        read_receipts_due: HashMap::new(),
+        receipts_ahead: ReceiptsAhead::default(),
+    }
 }
 
 fn main() {
@@ -182,70 +516,1088 @@ fn main() {
     info!("App state: {}", app);
     tauri::Builder::default()
         .manage(Mutex::new(app))
-        .invoke_handler(tauri::generate_handler![
-            get_user,
-            get_friends,
-            update_friend,
-            add_friend,
-            update_username,
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .system_tray(tray::system_tray())
+        .on_system_tray_event(tray::handle_event)
+        .setup(|app| {
+            if let Some(bundled_sounds) = app.path_resolver().resolve_resource("sounds") {
+                lock(&app.state::<Mutex<AppState>>())
+                    .sounds
+                    .add_pack_dir(bundled_sounds);
+            }
+            transport::spawn_listener(app.handle(), transport::listen_address());
+            let handle = app.handle();
+            thread::spawn(move || transport::broadcast_presence(&handle));
+            tray::update(&app.handle());
+            tray::spawn_blinker(app.handle());
+            let state = app.state::<Mutex<AppState>>();
+            let app_state = lock(&state);
+            if app_state.settings.chat_layout == ChatLayout::Windows {
+                let reopen: Vec<String> = app_state
+                    .chat_windows
+                    .left_open()
+                    .into_iter()
+                    .filter(|c| app_state.has_conversation(c))
+                    .collect();
+                drop(app_state);
+                for conversation in reopen {
+                    if let Err(e) = windows::open(&app.handle(), &conversation) {
+                        warn!("Couldn't reopen the window for {}: {}", conversation, e);
+                    }
+                }
+            }
+            Ok(())
+        })
+        .register_uri_scheme_protocol("emoticon", |app, request| {
+            // The URL is emoticon://localhost/<hash>, or https://emoticon.localhost/<hash> on Windows.
+            let hash = request.uri().rsplit('/').next().unwrap_or_default();
+            let state = app.state::<Mutex<AppState>>();
+            let response = ResponseBuilder::new().header("Cache-Control", "max-age=31536000");
+            let image = lock(&state).emoticons.image(hash);
+            match image {
+                Ok(image) => response
+                    .mimetype(emoticons::image_mime_type(&image).unwrap_or("image/png"))
+                    .status(200)
+                    .body(image),
+                Err(e) => response.status(404).body(e.to_string().into_bytes()),
+            }
+        })
+        .register_uri_scheme_protocol("image", |app, request| {
+            // The URL is image://localhost/<hash>, with `-thumbnail` added for the small version.
+            let name = request.uri().rsplit('/').next().unwrap_or_default();
+            let state = app.state::<Mutex<AppState>>();
+            let response = ResponseBuilder::new().header("Cache-Control", "max-age=31536000");
+            let image = lock(&state).images.read(name);
+            match image {
+                Ok(image) => response
+                    .mimetype(emoticons::image_mime_type(&image).unwrap_or("image/png"))
+                    .status(200)
+                    .body(image),
+                Err(e) => response.status(404).body(e.to_string().into_bytes()),
+            }
+        })
+        .register_uri_scheme_protocol("avatar", |app, request| {
+            // The URL is avatar://localhost/<hash>.
+            let hash = request.uri().rsplit('/').next().unwrap_or_default();
+            let state = app.state::<Mutex<AppState>>();
+            let response = ResponseBuilder::new().header("Cache-Control", "max-age=31536000");
+            let avatar = lock(&state).avatars.read(hash);
+            match avatar {
+                Ok(avatar) => response.mimetype("image/png").status(200).body(avatar),
+                Err(e) => response.status(404).body(e.to_string().into_bytes()),
+            }
+        })
+        .register_uri_scheme_protocol("voice", |app, request| {
+            // The URL is voice://localhost/<hash>.
+            let hash = request.uri().rsplit('/').next().unwrap_or_default();
+            let state = app.state::<Mutex<AppState>>();
+            let response = ResponseBuilder::new().header("Cache-Control", "max-age=31536000");
+            let clip = lock(&state).clips.read(hash);
+            match clip {
+                Ok(clip) => response.mimetype("audio/ogg").status(200).body(clip),
+                Err(e) => response.status(404).body(e.to_string().into_bytes()),
+            }
+        })
+        .on_window_event(|event| {
+            let window = event.window();
+            let state = window.state::<Mutex<AppState>>();
+            let mut app_state = lock(&state);
+            let conversation = app_state
+                .chat_windows
+                .conversation(window.label())
+                .map(str::to_string);
+            match (event.event(), conversation) {
+                (WindowEvent::Focused(focused), None) => {
+                    app_state.window_focused = *focused;
+                    if let Some(conversation) = app_state.active_chat.clone().filter(|_| *focused) {
+                        if app_state.mark_read(&conversation) {
+                            drop(app_state);
+                            unread_changed(&window.app_handle());
+                            receipts::conversation_read(&window.app_handle(), &conversation);
+                        }
+                    }
+                }
+                (WindowEvent::Focused(focused), Some(conversation)) => {
+                    if *focused {
+                        app_state.focused_chat_window = Some(conversation.clone());
+                        if app_state.mark_read(&conversation) {
+                            drop(app_state);
+                            unread_changed(&window.app_handle());
+                            receipts::conversation_read(&window.app_handle(), &conversation);
+                        }
+                    } else if app_state.focused_chat_window.as_ref() == Some(&conversation) {
+                        app_state.focused_chat_window = None;
+                    }
+                }
+                (WindowEvent::Moved(_) | WindowEvent::Resized(_), Some(_)) => {
+                    app_state.chat_windows.record_geometry(window);
+                }
+                (WindowEvent::Destroyed, Some(conversation)) => {
+                    if app_state.focused_chat_window.as_ref() == Some(&conversation) {
+                        app_state.focused_chat_window = None;
+                    }
+                    if let Err(e) = app_state.chat_windows.closed(window.label()) {
+                        warn!("Couldn't save chat windows: {}", e);
+                    }
+                }
+                // Closing the contact list hides it in the tray, or quits and takes the chat
+                // windows with it.
+                (WindowEvent::CloseRequested { api, .. }, None) if window.label() == "main" => {
+                    if app_state.settings.quit_on_close {
+                        drop(app_state);
+                        quit(&window.app_handle());
+                    } else {
+                        api.prevent_close();
+                        if let Err(e) = window.hide() {
+                            warn!("Couldn't hide the contact list: {}", e);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        })
+        .invoke_handler(app_commands!(handlers))
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                // Let friends know we've gone instead of leaving us online in their lists.
+                lock(&app.state::<Mutex<AppState>>()).user.availability = Availability::Offline;
+                transport::broadcast_presence(app);
+            }
+        });
+}
+
+/// Changes the user's availability, tells friends about it and shows it in every window and in
+/// the tray.
+fn apply_availability(app: &AppHandle, availability: Availability) {
+    let user = {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = lock(&state);
+        app_state.user.availability = availability;
+        if let Err(e) = save_user(&data_path(USER_FILE), &app_state.user) {
+            warn!("Couldn't save your availability: {}", e);
+        }
+        app_state.user.clone()
+    };
+    tray::update(app);
+    emit_all(app, events::USER_CHANGED, user);
+    let handle = app.clone();
+    thread::spawn(move || transport::broadcast_presence(&handle));
+}
+
+/// Tells every window how many messages are unread, and counts them in window titles.
+fn unread_changed(app: &AppHandle) {
+    windows::show_unread(app);
+    let unread = lock(&app.state::<Mutex<AppState>>()).unread.clone();
+    emit_all(app, events::UNREAD_CHANGED, unread);
+}
+
+/// Quits the app, remembering which chat windows were open so they come back next time.
+fn quit(app: &AppHandle) {
+    if let Err(e) = lock(&app.state::<Mutex<AppState>>()).chat_windows.exiting() {
+        warn!("Couldn't save chat windows: {}", e);
+    }
+    app.exit(0);
 }
 
 #[command]
-fn get_user(state: App) -> Result<User, String> {
+fn get_user(state: App) -> Result<User, AppError> {
     info!("Getting user information");
-    let app = state.lock().expect("Failed to lock state");
+    let app = lock(&state);
     Ok(app.user.clone())
 }
 
 #[command]
-fn update_username(state: App, name: String) -> Result<User, String> {
+fn update_username(state: App, args: UpdateUsernameArgs) -> Result<User, AppError> {
+    let UpdateUsernameArgs { name } = args;
     info!("Updating username to: {}", name);
-    println!("Updating username to: {}", name);
-    let mut app = state.lock().expect("Failed to lock state");
-    app.user.name = name;
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::Validation("Your name can't be empty".to_string()));
+    }
+    let mut app = lock(&state);
+    app.user.name = name.to_string();
     Ok(app.user.clone())
 }
 
+/// Changes the user's display picture, or removes it when `source` is `None`, and tells friends.
+#[command(async)]
+fn set_display_picture(
+    app_handle: AppHandle,
+    state: App,
+    args: SetDisplayPictureArgs,
+) -> Result<User, AppError> {
+    let SetDisplayPictureArgs { source } = args;
+    info!("Changing display picture");
+    // Scaling a large picture takes a moment, so it happens without holding the lock.
+    let hash = source
+        .map(|source| AvatarStore::new(data_path(AVATARS_DIR)).store_source(&source))
+        .transpose()?;
+    let user = {
+        let mut app = lock(&state);
+        app.user.display_picture = hash;
+        save_user(&data_path(USER_FILE), &app.user)?;
+        app.user.clone()
+    };
+    thread::spawn(move || transport::broadcast_presence(&app_handle));
+    Ok(user)
+}
+
+/// Changes the user's availability from the sign-in page.
+#[command(async)]
+fn set_availability(app_handle: AppHandle, args: SetAvailabilityArgs) -> Result<User, AppError> {
+    let SetAvailabilityArgs { availability } = args;
+    info!("Setting availability to {}", availability);
+    apply_availability(&app_handle, availability);
+    Ok(lock(&app_handle.state::<Mutex<AppState>>()).user.clone())
+}
+
+/// Appears offline and closes every conversation, leaving the sign-in page to the window.
+#[command(async)]
+fn sign_out(app_handle: AppHandle) -> Result<(), AppError> {
+    info!("Signing out");
+    tray::sign_out(&app_handle);
+    Ok(())
+}
+
 #[command]
-fn get_friends(state: App) -> Result<(Vec<Friend>, Vec<Friend>), String> {
+fn get_friends(state: App) -> Result<(Vec<Friend>, Vec<Friend>), AppError> {
     info!("Getting friends list");
-    let app = state.lock().expect("Failed to lock state");
+    let app = lock(&state);
     Ok(app.friends_by_availability())
 }
 
 #[command]
-fn update_friend(
-    state: App,
-    email: String,
-    name: Option<String>,
-    status: Option<String>,
-    availability: Option<Availability>,
-) -> Result<Friend, String> {
+fn update_friend(state: App, args: UpdateFriendArgs) -> Result<Friend, AppError> {
+    let UpdateFriendArgs {
+        email,
+        name,
+        status,
+        availability,
+    } = args;
     info!("Updating friend: {}", email);
-    let mut app = state.lock().expect("Failed to lock state");
-    let friend_index = app
-        .friends
-        .iter()
-        .position(|f| f.email == email)
-        .ok_or("Friend not found")?;
-    app.friends[friend_index].update(name, status, availability);
-    Ok(app.friends[friend_index].clone())
+    let mut app = lock(&state);
+    let friend = app.friend_mut(&email)?;
+    friend.update(name, status, availability);
+    Ok(friend.clone())
 }
 
 #[command]
-fn add_friend(
-    state: App,
-    name: String,
-    email: String,
-    status: Option<String>,
-    availability: Option<Availability>,
-) -> Result<Friend, String> {
+fn add_friend(state: App, args: AddFriendArgs) -> Result<Friend, AppError> {
+    let AddFriendArgs {
+        name,
+        email,
+        status,
+        availability,
+    } = args;
     info!("Adding new friend: {} <{}>", name, email);
-    let mut app = state.lock().expect("Failed to lock state");
+    validate_email(&email)?;
+    let mut app = lock(&state);
+    if app.friend_mut(&email).is_ok() {
+        return Err(AppError::Validation(format!(
+            "{} is already a friend",
+            email
+        )));
+    }
     let friend = Friend::new(name, email, status, availability);
     app.friends.push(friend.clone());
+    save_friends_list(&data_path(FRIENDS_FILE), &app.friends)?;
+    Ok(friend)
+}
+
+#[command]
+fn update_contact(state: App, args: UpdateContactArgs) -> Result<Friend, AppError> {
+    let UpdateContactArgs { email, details } = args;
+    info!("Updating contact details for: {}", email);
+    let mut app = lock(&state);
+    let friend = app.friend_mut(&email)?;
+    friend.update_contact_details(details);
+    let friend = friend.clone();
+    save_friends_list(&data_path(FRIENDS_FILE), &app.friends)?;
     Ok(friend)
 }
+
+#[command]
+fn import_contacts(state: App, args: ImportContactsArgs) -> Result<ImportPreview, AppError> {
+    let ImportContactsArgs {
+        path,
+        format,
+        dry_run,
+    } = args;
+    info!("Importing contacts from: {} (dry run: {})", path, dry_run);
+    let imported = contacts::read_contacts(Path::new(&path), format)?;
+    let mut app = lock(&state);
+    let preview = contacts::preview_import(&app.friends, imported);
+    if !dry_run {
+        contacts::apply_import(preview.clone(), &mut app.friends);
+        save_friends_list(&data_path(FRIENDS_FILE), &app.friends)?;
+    }
+    Ok(preview)
+}
+
+#[command]
+fn export_contacts(state: App, args: ExportContactsArgs) -> Result<usize, AppError> {
+    let ExportContactsArgs { path, format } = args;
+    info!("Exporting contacts to: {}", path);
+    let app = lock(&state);
+    contacts::write_contacts(Path::new(&path), format, &app.friends)?;
+    Ok(app.friends.len())
+}
+
+#[command]
+fn get_conversation(state: App, args: GetConversationArgs) -> Result<Vec<ChatMessage>, AppError> {
+    let GetConversationArgs { email } = args;
+    info!("Getting conversation with: {}", email);
+    let mut app = lock(&state);
+    if is_group(&email) {
+        app.groups.get(&email)?;
+    } else {
+        app.friend_mut(&email)?;
+    }
+    Ok(app.history.conversation(&email)?.clone())
+}
+
+// Runs off the main thread since reaching the friend can take up to the connect timeout.
+#[command(async)]
+fn send_message(
+    app_handle: AppHandle,
+    state: App,
+    args: SendMessageArgs,
+) -> Result<ChatMessage, AppError> {
+    let SendMessageArgs { email, body } = args;
+    info!("Sending message to: {}", email);
+    if body.trim().is_empty() {
+        return Err(AppError::Validation(
+            "Can't send an empty message".to_string(),
+        ));
+    }
+    deliver(
+        &app_handle,
+        &state,
+        email,
+        MessageKind::Text,
+        body,
+        Attachment::None,
+    )
+}
+
+#[command(async)]
+fn send_nudge(
+    app_handle: AppHandle,
+    state: App,
+    args: SendNudgeArgs,
+) -> Result<ChatMessage, AppError> {
+    let SendNudgeArgs { email } = args;
+    info!("Sending nudge to: {}", email);
+    if too_soon(&lock(&state).nudges_sent, &email, NUDGE_INTERVAL) {
+        return Err(AppError::Validation(
+            "You can't send nudges that often, wait a few seconds and try again".to_string(),
+        ));
+    }
+    let message = deliver(
+        &app_handle,
+        &state,
+        email.clone(),
+        MessageKind::Nudge,
+        String::new(),
+        Attachment::None,
+    )?;
+    // Only a nudge that arrived counts, so one that couldn't be sent can be retried straight away.
+    lock(&state)
+        .nudges_sent
+        .insert(email.clone(), Instant::now());
+    emit_all(&app_handle, events::NUDGE, email);
+    Ok(message)
+}
+
+/// Tells a friend the user is writing to them, at most once every `TYPING_INTERVAL`.
+#[command(async)]
+fn send_typing(app_handle: AppHandle, state: App, args: SendTypingArgs) -> Result<(), AppError> {
+    let SendTypingArgs { email } = args;
+    if !throttle(&mut lock(&state).typing_sent, &email, TYPING_INTERVAL) {
+        return Ok(());
+    }
+    // Group conversations don't show who is typing.
+    if is_group(&email) {
+        return Ok(());
+    }
+    info!("Sending typing notification to: {}", email);
+    transport::send_to_friend(&app_handle, &email, PeerEvent::Typing)
+}
+
+/// What a message carries besides its text.
+enum Attachment {
+    None,
+    File(FileOffer),
+    Image(SharedImage),
+    Voice(VoiceClip),
+}
+
+/// Sends a message to a friend, or everyone in a group, and, once it arrived, adds it to the conversation's history.
+fn deliver(
+    app_handle: &AppHandle,
+    state: &Mutex<AppState>,
+    email: String,
+    kind: MessageKind,
+    body: String,
+    attachment: Attachment,
+) -> Result<ChatMessage, AppError> {
+    let (mut message, images, data) = {
+        let mut app = lock(state);
+        let (used, images) = app.emoticons_to_send(&email, &body)?;
+        let mut message = ChatMessage {
+            id: uuid::Uuid::new_v4().to_string(),
+            from: app.user.email.clone(),
+            body,
+            sent_at: unix_millis(),
+            style: Some(app.settings.text_style.clone()).filter(|_| kind == MessageKind::Text),
+            kind,
+            emoticons: used,
+            file: None,
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: None,
+        };
+        let data = match attachment {
+            Attachment::None => None,
+            Attachment::File(offer) => {
+                message.file = Some(offer);
+                None
+            }
+            Attachment::Image(image) => {
+                let data = app.images.image_for_transfer(&image.hash)?;
+                message.image = Some(image);
+                Some(data)
+            }
+            Attachment::Voice(clip) => {
+                let data = app.clips.clip_for_transfer(&clip.hash)?;
+                message.voice = Some(clip);
+                Some(data)
+            }
+        };
+        if !is_group(&email) {
+            app.receipts_ahead.sending(&email, &message.id);
+        }
+        (message, images, data)
+    };
+    let hashes: Vec<String> = images.iter().map(|image| image.hash.clone()).collect();
+    let event = PeerEvent::Message {
+        message: Box::new(message.clone()),
+        emoticons: images,
+        attachment: data,
+        group: Some(email.clone()).filter(|email| is_group(email)),
+    };
+    let sent = transport::send_to_conversation(app_handle, &email, event);
+    let mut app = lock(state);
+    let acknowledged = app.receipts_ahead.sent(&email, &message.id);
+    sent?;
+    app.emoticons_sent
+        .entry(email.clone())
+        .or_default()
+        .extend(hashes);
+    if !is_group(&email) {
+        message.status = acknowledged.or(Some(MessageStatus::Sent));
+    }
+    app.history.append(&email, message.clone())?;
+    // The next keystroke starts a new message, which the friend should hear about right away.
+    app.typing_sent.remove(&email);
+    drop(app);
+    emit_all(
+        app_handle,
+        events::CHAT_MESSAGE,
+        ChatMessageEvent {
+            conversation: email,
+            message: message.clone(),
+        },
+    );
+    Ok(message)
+}
+
+/// Changes the text of a message the user sent, for everyone in the conversation.
+#[command(async)]
+fn edit_message(
+    app_handle: AppHandle,
+    state: App,
+    args: EditMessageArgs,
+) -> Result<ChatMessage, AppError> {
+    let EditMessageArgs {
+        conversation,
+        message_id,
+        body,
+    } = args;
+    info!("Editing message {} in {}", message_id, conversation);
+    if body.trim().is_empty() {
+        return Err(AppError::Validation(
+            "A message can't be left empty, delete it instead".to_string(),
+        ));
+    }
+    let (used, images) = {
+        let mut app = lock(&state);
+        if app.own_message(&conversation, &message_id)?.kind != MessageKind::Text {
+            return Err(AppError::Validation(
+                "Only text messages can be edited".to_string(),
+            ));
+        }
+        app.emoticons_to_send(&conversation, &body)?
+    };
+    let hashes: Vec<String> = images.iter().map(|image| image.hash.clone()).collect();
+    let edited_at = unix_millis();
+    let event = PeerEvent::MessageEdit {
+        id: message_id.clone(),
+        body: body.clone(),
+        edited_at,
+        custom: used.clone(),
+        emoticons: images,
+        group: Some(conversation.clone()).filter(|c| is_group(c)),
+    };
+    transport::send_to_conversation(&app_handle, &conversation, event)?;
+    let mut app = lock(&state);
+    app.emoticons_sent
+        .entry(conversation.clone())
+        .or_default()
+        .extend(hashes);
+    let message = app.history.update(&conversation, &message_id, |message| {
+        message.body = body;
+        message.emoticons = used;
+        message.edited_at = Some(edited_at);
+    })?;
+    drop(app);
+    emit_all(
+        &app_handle,
+        events::MESSAGE_CHANGED,
+        ChatMessageEvent {
+            conversation,
+            message: message.clone(),
+        },
+    );
+    Ok(message)
+}
+
+/// Takes back a message the user sent, leaving a note that it was deleted in its place.
+#[command(async)]
+fn delete_message(
+    app_handle: AppHandle,
+    state: App,
+    args: DeleteMessageArgs,
+) -> Result<ChatMessage, AppError> {
+    let DeleteMessageArgs {
+        conversation,
+        message_id,
+    } = args;
+    info!("Deleting message {} in {}", message_id, conversation);
+    match lock(&state).own_message(&conversation, &message_id)?.kind {
+        MessageKind::File | MessageKind::Joined | MessageKind::Left => {
+            return Err(AppError::Validation(
+                "Only messages, nudges, pictures and voice clips can be deleted".to_string(),
+            ))
+        }
+        _ => {}
+    }
+    let event = PeerEvent::MessageDelete {
+        id: message_id.clone(),
+        group: Some(conversation.clone()).filter(|c| is_group(c)),
+    };
+    transport::send_to_conversation(&app_handle, &conversation, event)?;
+    let message = lock(&state)
+        .history
+        .update(&conversation, &message_id, ChatMessage::delete)?;
+    emit_all(
+        &app_handle,
+        events::MESSAGE_CHANGED,
+        ChatMessageEvent {
+            conversation,
+            message: message.clone(),
+        },
+    );
+    Ok(message)
+}
+
+/// Records which chat the user is looking at, so messages there don't raise a notification.
+#[command]
+fn focus_chat(app_handle: AppHandle, state: App, args: FocusChatArgs) -> Result<(), AppError> {
+    let FocusChatArgs { email } = args;
+    let mut app = lock(&state);
+    let read = email.as_ref().filter(|email| app.mark_read(email)).cloned();
+    app.active_chat = email;
+    drop(app);
+    if let Some(conversation) = read {
+        unread_changed(&app_handle);
+        receipts::conversation_read(&app_handle, &conversation);
+    }
+    Ok(())
+}
+
+/// How many messages arrived in each conversation while it wasn't on screen.
+#[command]
+fn get_unread_counts(state: App) -> Result<HashMap<String, u32>, AppError> {
+    Ok(lock(&state).unread.clone())
+}
+
+#[command]
+fn get_settings(state: App) -> Result<Settings, AppError> {
+    info!("Getting settings");
+    Ok(lock(&state).settings.clone())
+}
+
+#[command]
+fn update_settings(state: App, args: UpdateSettingsArgs) -> Result<Settings, AppError> {
+    let UpdateSettingsArgs { settings } = args;
+    info!("Updating settings");
+    let mut app = lock(&state);
+    if settings.sounds.volume > 100 {
+        return Err(AppError::Validation(
+            "Volume must be between 0 and 100".to_string(),
+        ));
+    }
+    if !app.sounds.packs().contains(&settings.sounds.pack) {
+        return Err(AppError::NotFound(format!(
+            "No sound pack named {}",
+            settings.sounds.pack
+        )));
+    }
+    settings.text_style.validate()?;
+    if let Some(dir) = &settings.download_dir {
+        if !Path::new(dir).is_dir() {
+            return Err(AppError::NotFound(format!("{} is not a folder", dir)));
+        }
+    }
+    storage::save(&data_path(SETTINGS_FILE), &SETTINGS_SCHEMA, &settings)?;
+    app.settings = settings;
+    Ok(app.settings.clone())
+}
+
+#[command]
+fn list_sound_packs(state: App) -> Result<Vec<String>, AppError> {
+    info!("Listing sound packs");
+    Ok(lock(&state).sounds.packs())
+}
+
+/// Previews a sound with the current pack and volume, even if its event is turned off.
+#[command]
+fn play_sound(state: App, args: PlaySoundArgs) -> Result<(), AppError> {
+    let PlaySoundArgs { event } = args;
+    info!("Playing {:?} sound", event);
+    let app = lock(&state);
+    app.sounds.play(&app.settings.sounds, event)
+}
+
+#[command]
+fn list_custom_emoticons(state: App) -> Result<Vec<CustomEmoticon>, AppError> {
+    info!("Listing custom emoticons");
+    Ok(lock(&state).emoticons.list().to_vec())
+}
+
+#[command]
+fn add_custom_emoticon(
+    state: App,
+    args: AddCustomEmoticonArgs,
+) -> Result<CustomEmoticon, AppError> {
+    let AddCustomEmoticonArgs { path, shortcut } = args;
+    info!("Adding custom emoticon {} from: {}", shortcut, path);
+    lock(&state).emoticons.add(Path::new(&path), &shortcut)
+}
+
+#[command]
+fn remove_custom_emoticon(state: App, args: RemoveCustomEmoticonArgs) -> Result<(), AppError> {
+    let RemoveCustomEmoticonArgs { shortcut } = args;
+    info!("Removing custom emoticon: {}", shortcut);
+    lock(&state).emoticons.remove(&shortcut)
+}
+
+/// Offers a file to a friend, who pulls it once they accept.
+#[command(async)]
+fn send_file(
+    app_handle: AppHandle,
+    state: App,
+    args: SendFileArgs,
+) -> Result<ChatMessage, AppError> {
+    let SendFileArgs { email, path } = args;
+    info!("Offering {} to: {}", path, email);
+    if is_group(&email) {
+        return Err(AppError::Validation(
+            "Files can only be offered to one friend at a time".to_string(),
+        ));
+    }
+    let offer = transfers::offer(Path::new(&path))?;
+    let message = deliver(
+        &app_handle,
+        &state,
+        email.clone(),
+        MessageKind::File,
+        String::new(),
+        Attachment::File(offer.clone()),
+    )?;
+    let transfer = FileTransfer {
+        id: message.id.clone(),
+        conversation: email,
+        incoming: false,
+        file: offer,
+        status: TransferStatus::Offered,
+        transferred: 0,
+        path: Some(path),
+        error: None,
+    };
+    lock(&state).transfers.add(transfer.clone())?;
+    emit_all(&app_handle, events::FILE_TRANSFER, transfer);
+    Ok(message)
+}
+
+#[command(async)]
+fn accept_file(app_handle: AppHandle, args: FileTransferArgs) -> Result<FileTransfer, AppError> {
+    let FileTransferArgs { id } = args;
+    info!("Accepting file transfer: {}", id);
+    transfers::accept(&app_handle, &id)
+}
+
+#[command(async)]
+fn decline_file(app_handle: AppHandle, args: FileTransferArgs) -> Result<FileTransfer, AppError> {
+    let FileTransferArgs { id } = args;
+    info!("Declining file transfer: {}", id);
+    transfers::decline(&app_handle, &id)
+}
+
+#[command(async)]
+fn resume_file(app_handle: AppHandle, args: FileTransferArgs) -> Result<FileTransfer, AppError> {
+    let FileTransferArgs { id } = args;
+    info!("Resuming file transfer: {}", id);
+    transfers::resume(&app_handle, &id)
+}
+
+#[command(async)]
+fn cancel_file(app_handle: AppHandle, args: FileTransferArgs) -> Result<FileTransfer, AppError> {
+    let FileTransferArgs { id } = args;
+    info!("Cancelling file transfer: {}", id);
+    transfers::cancel(&app_handle, &id)
+}
+
+#[command]
+fn list_file_transfers(
+    state: App,
+    args: ListFileTransfersArgs,
+) -> Result<Vec<FileTransfer>, AppError> {
+    let ListFileTransfersArgs { email } = args;
+    info!("Listing file transfers with: {}", email);
+    Ok(lock(&state).transfers.list(&email))
+}
+
+/// Shares a picture in a conversation, after checking it against the size limits.
+#[command(async)]
+fn send_image(
+    app_handle: AppHandle,
+    state: App,
+    args: SendImageArgs,
+) -> Result<ChatMessage, AppError> {
+    let SendImageArgs { email, source } = args;
+    info!("Sending picture to: {}", email);
+    // Decoding a large picture takes a moment, so it happens without holding the lock.
+    let images = ImageStore::new(data_path(HISTORY_DIR).join(IMAGES_DIR));
+    let image = match source {
+        ImageSource::Path(path) => images.store_file(Path::new(&path))?,
+        ImageSource::Data(data) => images.store_base64(&data)?,
+    };
+    deliver(
+        &app_handle,
+        &state,
+        email,
+        MessageKind::Image,
+        String::new(),
+        Attachment::Image(image),
+    )
+}
+
+/// Encodes a voice clip recorded in the chat window and sends it.
+#[command(async)]
+fn send_voice_clip(
+    app_handle: AppHandle,
+    state: App,
+    args: SendVoiceClipArgs,
+) -> Result<ChatMessage, AppError> {
+    let SendVoiceClipArgs {
+        email,
+        samples,
+        sample_rate,
+    } = args;
+    info!("Sending voice clip to: {}", email);
+    // Encoding takes a moment, so it happens without holding the lock.
+    let clip =
+        ClipStore::new(data_path(HISTORY_DIR).join(CLIPS_DIR)).record(&samples, sample_rate)?;
+    deliver(
+        &app_handle,
+        &state,
+        email,
+        MessageKind::Voice,
+        String::new(),
+        Attachment::Voice(clip),
+    )
+}
+
+#[command]
+fn list_groups(state: App) -> Result<Vec<Group>, AppError> {
+    info!("Listing group conversations");
+    Ok(lock(&state).groups.list())
+}
+
+/// Invites a friend into a conversation. Inviting someone into a chat with a single friend
+/// starts a new group with both of them, leaving the original chat as it was.
+#[command(async)]
+fn invite_to_conversation(
+    app_handle: AppHandle,
+    state: App,
+    args: InviteToConversationArgs,
+) -> Result<Group, AppError> {
+    let InviteToConversationArgs {
+        conversation,
+        email,
+    } = args;
+    info!("Inviting {} to: {}", email, conversation);
+    let (group, me) = {
+        let mut app = lock(&state);
+        let invitee = groups::participant(app.friend_mut(&email)?);
+        let mut group = if is_group(&conversation) {
+            app.groups.get(&conversation)?.clone()
+        } else {
+            let friend = groups::participant(app.friend_mut(&conversation)?);
+            let user = Participant {
+                email: app.user.email.clone(),
+                name: app.user.name.clone(),
+                address: Some(transport::listen_address()),
+            };
+            Group {
+                id: format!("{}{}", GROUP_PREFIX, uuid::Uuid::new_v4()),
+                participants: vec![user, friend],
+            }
+        };
+        if group.participant(&email).is_some() {
+            return Err(AppError::Validation(format!(
+                "{} is already in this conversation",
+                invitee.name
+            )));
+        }
+        group.participants.push(invitee);
+        (group, app.user.email.clone())
+    };
+    let created = !is_group(&conversation);
+    // The invitee hears about it first, so nothing changes if they can't be reached.
+    let update = PeerEvent::GroupUpdate {
+        group: group.clone(),
+    };
+    transport::send_to_friend(&app_handle, &email, update.clone())?;
+    if let Err(e) = transport::send_to_group(&app_handle, &group, Some(&email), update) {
+        warn!(
+            "Couldn't tell the rest of {} about {}: {}",
+            group.id, email, e
+        );
+    }
+    let joined: Vec<ChatMessage> = group
+        .others(&me)
+        .filter(|p| created || p.email == email)
+        .map(|p| groups::membership_message(MessageKind::Joined, p))
+        .collect();
+    {
+        let mut app = lock(&state);
+        app.groups.save_group(group.clone())?;
+        // The newcomer hasn't seen any of the custom emoticons used so far.
+        app.emoticons_sent.remove(&group.id);
+        for message in &joined {
+            app.history.append(&group.id, message.clone())?;
+        }
+    }
+    emit_all(&app_handle, events::GROUPS_CHANGED, ());
+    for message in joined {
+        emit_all(
+            &app_handle,
+            events::CHAT_MESSAGE,
+            ChatMessageEvent {
+                conversation: group.id.clone(),
+                message,
+            },
+        );
+    }
+    if created {
+        windows::open_chat(&app_handle, &group.id);
+    }
+    Ok(group)
+}
+
+/// Leaves a group conversation, telling the others. Its history is kept.
+#[command(async)]
+fn leave_conversation(
+    app_handle: AppHandle,
+    state: App,
+    args: LeaveConversationArgs,
+) -> Result<(), AppError> {
+    let LeaveConversationArgs { conversation } = args;
+    info!("Leaving group conversation: {}", conversation);
+    let group = lock(&state).groups.get(&conversation)?.clone();
+    let leave = PeerEvent::GroupLeave {
+        id: conversation.clone(),
+    };
+    if let Err(e) = transport::send_to_group(&app_handle, &group, None, leave) {
+        info!("Couldn't tell everyone in {} you left: {}", conversation, e);
+    }
+    let read = {
+        let mut app = lock(&state);
+        app.groups.remove(&conversation)?;
+        app.mark_read(&conversation)
+    };
+    emit_all(&app_handle, events::GROUPS_CHANGED, ());
+    if read {
+        unread_changed(&app_handle);
+    }
+    Ok(())
+}
+
+#[command(async)]
+fn open_chat_window(app_handle: AppHandle, args: ChatWindowArgs) -> Result<(), AppError> {
+    let ChatWindowArgs { conversation } = args;
+    info!("Opening a window for: {}", conversation);
+    windows::open(&app_handle, &conversation)
+}
+
+#[command(async)]
+fn close_chat_window(app_handle: AppHandle, args: ChatWindowArgs) -> Result<(), AppError> {
+    let ChatWindowArgs { conversation } = args;
+    info!("Closing the window for: {}", conversation);
+    windows::close(&app_handle, &conversation)
+}
+
+#[command]
+fn search_messages(state: App, args: SearchMessagesArgs) -> Result<Vec<SearchResult>, AppError> {
+    let SearchMessagesArgs { query } = args;
+    info!("Searching messages");
+    lock(&state).history.search(&query)
+}
+
+/// Opens a conversation at one of its messages, picked from search results.
+#[command(async)]
+fn show_message(app_handle: AppHandle, args: ShowMessageArgs) -> Result<(), AppError> {
+    let ShowMessageArgs {
+        conversation,
+        message_id,
+    } = args;
+    info!("Showing message {} in {}", message_id, conversation);
+    windows::show_message(&app_handle, &conversation, &message_id)
+}
+
+/// Saves a conversation to `path`, or every conversation into the folder at `path` when
+/// `conversation` is `None`, and returns how many messages were saved.
+#[command(async)]
+fn export_conversation(state: App, args: ExportConversationArgs) -> Result<usize, AppError> {
+    let ExportConversationArgs {
+        conversation,
+        path,
+        format,
+    } = args;
+    info!(
+        "Exporting {} to: {}",
+        conversation.as_deref().unwrap_or("every conversation"),
+        path
+    );
+    let mut app = lock(&state);
+    let conversations = match &conversation {
+        Some(conversation) => vec![conversation.clone()],
+        None => {
+            if !Path::new(&path).is_dir() {
+                return Err(AppError::NotFound(format!("{} is not a folder", path)));
+            }
+            app.friends
+                .iter()
+                .map(|f| f.email.clone())
+                .chain(app.groups.list().into_iter().map(|g| g.id))
+                .collect()
+        }
+    };
+    let names = app.known_names();
+    let mut exported = 0;
+    for id in conversations {
+        let title = app.conversation_title(&id)?;
+        let messages = app.history.conversation(&id)?.clone();
+        let file = match conversation {
+            Some(_) => PathBuf::from(&path),
+            // Leave out conversations that never got going rather than write empty logs.
+            None if messages.is_empty() => continue,
+            None => Path::new(&path).join(format!(
+                "{}.{}",
+                history::file_stem(&id),
+                format.extension()
+            )),
+        };
+        let log = logs::Log {
+            title,
+            names: &names,
+            me: &app.user.email,
+            messages: &messages,
+        };
+        logs::write_log(&file, format, &log)?;
+        exported += messages.len();
+    }
+    Ok(exported)
+}
+
+/// Adds the messages of an MSN Messenger log to the conversation with a friend. Messages sent
+/// under one of `my_names` become the user's, and the rest the friend's.
+#[command(async)]
+fn import_msn_log(state: App, args: ImportMsnLogArgs) -> Result<LogImportPreview, AppError> {
+    let ImportMsnLogArgs {
+        path,
+        conversation,
+        my_names,
+        dry_run,
+    } = args;
+    info!(
+        "Importing MSN Messenger log {} into {} (dry run: {})",
+        path, conversation, dry_run
+    );
+    let entries = logs::read_msn_log(Path::new(&path))?;
+    let mut app = lock(&state);
+    let friend = app.friend_mut(&conversation)?.email.clone();
+    let me = app.user.email.clone();
+    let mut preview = LogImportPreview::default();
+    for entry in &entries {
+        if !preview.senders.contains(&entry.from) {
+            preview.senders.push(entry.from.clone());
+        }
+    }
+    let existing = app.history.conversation(&conversation)?;
+    // Lines repeated within the same second get the same id, so they count as duplicates too.
+    let mut ids: HashSet<String> = existing.iter().map(|m| m.id.clone()).collect();
+    let (duplicates, new): (Vec<ChatMessage>, Vec<ChatMessage>) = entries
+        .iter()
+        .map(|entry| {
+            let from = if my_names.contains(&entry.from) {
+                &me
+            } else {
+                &friend
+            };
+            logs::imported_message(entry, from)
+        })
+        .partition(|message| !ids.insert(message.id.clone()));
+    preview.duplicates = duplicates.len();
+    preview.new = new.len();
+    if !dry_run {
+        app.history.import(&conversation, new)?;
+    }
+    Ok(preview)
+}
+
+#[cfg(test)]
+mod tests {
+    use emiessiene_models::commands;
+
+    macro_rules! names {
+        ($($cmd:ident),* $(,)?) => {
+            [$(stringify!($cmd)),*]
+        };
+    }
+
+    #[test]
+    fn every_listed_command_is_registered() {
+        let registered = app_commands!(names);
+        for name in commands::ALL {
+            assert!(registered.contains(name), "{} is not registered", name);
+        }
+        assert_eq!(registered.len(), commands::ALL.len());
+    }
+}
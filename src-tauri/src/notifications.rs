@@ -0,0 +1,111 @@
+//! Desktop notifications for friends signing in and messages arriving in the background.
+//!
+//! On Linux these go straight to the freedesktop notification server so clicking one can open the
+//! chat it is about. Elsewhere they use Tauri's notification API, which shows the toast but
+//! doesn't report clicks.
+
+use emiessiene_models::{format_size, ChatMessage, Friend, MessageKind};
+use tauri::AppHandle;
+
+/// Longest message preview shown in a toast, in characters.
+const PREVIEW_LENGTH: usize = 80;
+
+pub fn friend_signed_in(app: &AppHandle, friend: &Friend) {
+    show(
+        app,
+        &format!("{} has just signed in.", friend.display_name()),
+        &friend.status,
+        &friend.email,
+    );
+}
+
+/// `conversation` is the chat clicking the notification opens, the friend's own unless the
+/// message was sent to a group.
+pub fn message_received(
+    app: &AppHandle,
+    friend: &Friend,
+    conversation: &str,
+    message: &ChatMessage,
+) {
+    let name = friend.display_name();
+    let (title, body) = match message.kind {
+        MessageKind::Text => {
+            let mut preview: String = message.body.chars().take(PREVIEW_LENGTH).collect();
+            if preview.len() < message.body.len() {
+                preview.push('…');
+            }
+            (format!("{} says:", name), preview)
+        }
+        MessageKind::Nudge => (
+            format!("{} has just sent you a nudge!", name),
+            String::new(),
+        ),
+        MessageKind::File => (
+            format!("{} wants to send you a file", name),
+            message
+                .file
+                .as_ref()
+                .map(|file| format!("{} ({})", file.name, format_size(file.size)))
+                .unwrap_or_default(),
+        ),
+        MessageKind::Image => (
+            format!("{} has just sent you a picture", name),
+            String::new(),
+        ),
+        MessageKind::Voice => (
+            format!("{} has just sent you a voice clip", name),
+            String::new(),
+        ),
+        // Only ever recorded locally, never received as a message.
+        MessageKind::Joined | MessageKind::Left => return,
+    };
+    show(app, &title, &body, conversation);
+}
+
+#[cfg(target_os = "linux")]
+fn show(app: &AppHandle, title: &str, body: &str, conversation: &str) {
+    use log::warn;
+    use tauri::Manager;
+
+    let notification = notify_rust::Notification::new()
+        .appname(&app.package_info().name)
+        .summary(title)
+        .body(body)
+        .action("default", "Open chat")
+        .show();
+    let handle = match notification {
+        Ok(handle) => handle,
+        Err(e) => {
+            warn!("Failed to show notification: {}", e);
+            return;
+        }
+    };
+    let app = app.clone();
+    let conversation = conversation.to_string();
+    // Waiting blocks until the toast is clicked or closed, so it gets a thread of its own.
+    std::thread::spawn(move || {
+        handle.wait_for_action(|action| {
+            if action != "default" {
+                return;
+            }
+            if let Some(window) = app.get_window("main") {
+                let _ = window.unminimize();
+                let _ = window.show();
+                let _ = window.set_focus();
+            }
+            super::windows::open_chat(&app, &conversation);
+        })
+    });
+}
+
+#[cfg(not(target_os = "linux"))]
+fn show(app: &AppHandle, title: &str, body: &str, _conversation: &str) {
+    let identifier = &app.config().tauri.bundle.identifier;
+    if let Err(e) = tauri::api::notification::Notification::new(identifier)
+        .title(title)
+        .body(body)
+        .show()
+    {
+        log::warn!("Failed to show notification: {}", e);
+    }
+}
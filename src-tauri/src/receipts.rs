@@ -0,0 +1,288 @@
+//! Ticks showing whether messages sent to a friend reached them, and whether they were read.
+//!
+//! A friend's app acknowledges each message as soon as it is in their conversation, and again
+//! once they have looked at it, unless they turned read receipts off. Group conversations have
+//! none, just as they don't show who is typing.
+
+use std::{collections::HashMap, sync::Mutex, thread};
+
+use emiessiene_models::{events, AppError, ChatMessage, ChatMessageEvent, MessageStatus};
+use log::info;
+use tauri::{AppHandle, Manager};
+
+use super::{
+    emit_all,
+    history::History,
+    lock,
+    transport::{self, PeerEvent},
+    AppState,
+};
+
+/// Acknowledgements of messages still being sent, which aren't in the history yet.
+///
+/// A quick friend can acknowledge a message before sending it has returned and it has been added
+/// to the history, which then picks the status up from here. Only messages `deliver` is sending
+/// to that same friend are tracked, keyed by the friend's email and the message id.
+#[derive(Default)]
+pub struct ReceiptsAhead {
+    sending: HashMap<(String, String), Option<MessageStatus>>,
+}
+
+impl ReceiptsAhead {
+    /// Starts tracking a message about to be sent to a friend.
+    pub fn sending(&mut self, friend: &str, id: &str) {
+        self.sending
+            .insert((friend.to_string(), id.to_string()), None);
+    }
+
+    /// Stops tracking a message once sending it is over, returning what the friend acknowledged
+    /// in the meantime.
+    pub fn sent(&mut self, friend: &str, id: &str) -> Option<MessageStatus> {
+        self.sending
+            .remove(&(friend.to_string(), id.to_string()))
+            .flatten()
+    }
+
+    fn acknowledge(&mut self, friend: &str, id: &str, status: MessageStatus) {
+        if let Some(ahead) = self.sending.get_mut(&(friend.to_string(), id.to_string())) {
+            *ahead = (*ahead).max(Some(status));
+        }
+    }
+}
+
+fn acknowledge(app: &AppHandle, friend: &str, ids: Vec<String>, status: MessageStatus) {
+    if let Err(e) = transport::send_to_friend(app, friend, PeerEvent::Receipt { ids, status }) {
+        info!("Couldn't acknowledge messages from {}: {}", friend, e);
+    }
+}
+
+/// Acknowledges a message just received from a friend, as read if the user is looking at the
+/// conversation and otherwise once they do.
+pub fn received(app: &AppHandle, friend: &str, id: &str, focused: bool) {
+    let status = {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = lock(&state);
+        if focused && app_state.settings.send_read_receipts {
+            MessageStatus::Read
+        } else {
+            if !focused {
+                app_state
+                    .read_receipts_due
+                    .entry(friend.to_string())
+                    .or_default()
+                    .push(id.to_string());
+            }
+            MessageStatus::Delivered
+        }
+    };
+    acknowledge(app, friend, vec![id.to_string()], status);
+}
+
+/// Tells a friend the user has now seen the messages that arrived while their conversation
+/// wasn't on screen. Runs in the background, since reaching them can take a while.
+pub fn conversation_read(app: &AppHandle, conversation: &str) {
+    let ids = {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = lock(&state);
+        let ids = app_state.read_receipts_due.remove(conversation);
+        ids.filter(|_| app_state.settings.send_read_receipts)
+    };
+    let Some(ids) = ids else {
+        return;
+    };
+    let app = app.clone();
+    let friend = conversation.to_string();
+    thread::spawn(move || acknowledge(&app, &friend, ids, MessageStatus::Read));
+}
+
+/// Moves the user's messages to a friend on to the status the friend acknowledged.
+pub fn handle(
+    app: &AppHandle,
+    friend: &str,
+    ids: Vec<String>,
+    status: MessageStatus,
+) -> Result<(), AppError> {
+    let changed = {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = lock(&state);
+        let app_state = &mut *app_state;
+        apply(
+            &mut app_state.history,
+            &mut app_state.receipts_ahead,
+            friend,
+            &ids,
+            status,
+        )?
+    };
+    for message in changed {
+        emit_all(
+            app,
+            events::MESSAGE_CHANGED,
+            ChatMessageEvent {
+                conversation: friend.to_string(),
+                message,
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Moves messages to `status`, or holds on to it for those still being sent, and returns the
+/// messages that changed.
+fn apply(
+    history: &mut History,
+    ahead: &mut ReceiptsAhead,
+    friend: &str,
+    ids: &[String],
+    status: MessageStatus,
+) -> Result<Vec<ChatMessage>, AppError> {
+    let changed = history.acknowledge(friend, ids, status)?;
+    for id in ids {
+        ahead.acknowledge(friend, id, status);
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::{Path, PathBuf},
+    };
+
+    use emiessiene_models::MessageKind;
+
+    use super::*;
+    use crate::search::SearchIndex;
+
+    const ANN: &str = "ann@example.com";
+    const BOB: &str = "bob@example.com";
+
+    fn test_history() -> (History, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("emiessiene-test-{}", uuid::Uuid::new_v4()));
+        let (index, _) = SearchIndex::open(Path::new(":memory:")).unwrap();
+        (History::new(dir.clone(), index), dir)
+    }
+
+    fn sent(id: &str) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            from: "me@example.com".to_string(),
+            body: "hi".to_string(),
+            sent_at: 1_000_000,
+            kind: MessageKind::Text,
+            style: None,
+            emoticons: Vec::new(),
+            file: None,
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: Some(MessageStatus::Sent),
+        }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn status(history: &mut History, friend: &str, id: &str) -> Option<MessageStatus> {
+        history
+            .conversation(friend)
+            .unwrap()
+            .iter()
+            .find(|m| m.id == id)
+            .and_then(|m| m.status)
+    }
+
+    #[test]
+    fn status_only_moves_forward() {
+        let (mut history, dir) = test_history();
+        let mut ahead = ReceiptsAhead::default();
+        history.append(ANN, sent("1")).unwrap();
+        let read = apply(
+            &mut history,
+            &mut ahead,
+            ANN,
+            &ids(&["1"]),
+            MessageStatus::Read,
+        );
+        assert_eq!(read.unwrap().len(), 1);
+        let delivered = apply(
+            &mut history,
+            &mut ahead,
+            ANN,
+            &ids(&["1"]),
+            MessageStatus::Delivered,
+        );
+        assert!(delivered.unwrap().is_empty());
+        assert_eq!(status(&mut history, ANN, "1"), Some(MessageStatus::Read));
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn receipts_ahead_of_the_history_are_kept_until_sent() {
+        let (mut history, dir) = test_history();
+        let mut ahead = ReceiptsAhead::default();
+        ahead.sending(ANN, "1");
+        let changed = apply(
+            &mut history,
+            &mut ahead,
+            ANN,
+            &ids(&["1"]),
+            MessageStatus::Read,
+        );
+        assert!(changed.unwrap().is_empty());
+        apply(
+            &mut history,
+            &mut ahead,
+            ANN,
+            &ids(&["1"]),
+            MessageStatus::Delivered,
+        )
+        .unwrap();
+        assert_eq!(ahead.sent(ANN, "1"), Some(MessageStatus::Read));
+        // Once sent, the message is no longer tracked.
+        assert_eq!(ahead.sent(ANN, "1"), None);
+        assert!(ahead.sending.is_empty());
+
+        ahead.sending(ANN, "2");
+        assert_eq!(ahead.sent(ANN, "2"), None);
+        assert!(ahead.sending.is_empty());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn foreign_ids_are_ignored() {
+        let (mut history, dir) = test_history();
+        let mut ahead = ReceiptsAhead::default();
+        history.append(ANN, sent("1")).unwrap();
+        history.append(BOB, sent("2")).unwrap();
+        ahead.sending(ANN, "3");
+
+        // Bob can't acknowledge messages sent to Ann, whether in the history or being sent.
+        let changed = apply(
+            &mut history,
+            &mut ahead,
+            BOB,
+            &ids(&["1", "3", "unknown"]),
+            MessageStatus::Read,
+        );
+        assert!(changed.unwrap().is_empty());
+        assert_eq!(status(&mut history, ANN, "1"), Some(MessageStatus::Sent));
+        assert_eq!(ahead.sending.len(), 1);
+        assert_eq!(ahead.sent(ANN, "3"), None);
+
+        // Ids nobody is sending aren't remembered.
+        apply(
+            &mut history,
+            &mut ahead,
+            ANN,
+            &ids(&["unknown"]),
+            MessageStatus::Read,
+        )
+        .unwrap();
+        assert!(ahead.sending.is_empty());
+        fs::remove_dir_all(dir).ok();
+    }
+}
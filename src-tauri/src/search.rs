@@ -0,0 +1,296 @@
+//! Full-text search over every conversation.
+//!
+//! The history files stay the record of what was said. Alongside them, the text of each message
+//! is kept in an SQLite FTS5 table with just enough to find the message again, so a search
+//! doesn't have to read every conversation. The index can always be rebuilt from the history, and
+//! is whenever it is missing.
+
+use std::path::Path;
+
+use emiessiene_models::{
+    AppError, ChatMessage, MessageKind, SearchQuery, SearchResult, SnippetPart,
+};
+use log::info;
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Most results a search returns, best matches first.
+const MAX_RESULTS: u32 = 200;
+/// Words kept in a snippet around the matches.
+const SNIPPET_WORDS: u32 = 16;
+/// Put by SQLite around each match in a snippet, and taken out of messages before they are
+/// indexed so a snippet can't be made to show matches that aren't there.
+const MATCH_START: char = '\u{2}';
+const MATCH_END: char = '\u{3}';
+
+pub struct SearchIndex {
+    connection: Connection,
+}
+
+impl SearchIndex {
+    /// Opens the index at `path`, and tells whether it was just created and has to be filled
+    /// from the history.
+    pub fn open(path: &Path) -> Result<(Self, bool), AppError> {
+        info!("Opening search index at {}", path.display());
+        let connection = Connection::open(path).map_err(index_error)?;
+        let exists = connection
+            .query_row(
+                "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'messages'",
+                [],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(index_error)?
+            .is_some();
+        connection
+            .execute_batch(
+                "CREATE VIRTUAL TABLE IF NOT EXISTS messages USING fts5(
+                    body,
+                    conversation UNINDEXED,
+                    message_id UNINDEXED,
+                    sender UNINDEXED,
+                    sent_at UNINDEXED,
+                    tokenize = 'unicode61 remove_diacritics 2'
+                );",
+            )
+            .map_err(index_error)?;
+        Ok((Self { connection }, !exists))
+    }
+
+    /// Adds a message to the index. Only text is searchable, so other messages are left out.
+    pub fn add(&self, conversation: &str, message: &ChatMessage) -> Result<(), AppError> {
+        insert(&self.connection, conversation, message)
+    }
+
+    /// Adds a whole conversation to the index at once.
+    pub fn add_all(
+        &mut self,
+        conversation: &str,
+        messages: &[ChatMessage],
+    ) -> Result<(), AppError> {
+        let transaction = self.connection.transaction().map_err(index_error)?;
+        for message in messages {
+            insert(&transaction, conversation, message)?;
+        }
+        transaction.commit().map_err(index_error)
+    }
+
+    /// Indexes a message again after it was edited or deleted.
+    pub fn replace(&self, conversation: &str, message: &ChatMessage) -> Result<(), AppError> {
+        self.connection
+            .execute(
+                "DELETE FROM messages WHERE conversation = ?1 AND message_id = ?2",
+                params![conversation, message.id],
+            )
+            .map_err(index_error)?;
+        insert(&self.connection, conversation, message)
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>, AppError> {
+        let Some(expression) = match_expression(&query.text) else {
+            return Ok(Vec::new());
+        };
+        let mut statement = self
+            .connection
+            .prepare(
+                "SELECT conversation, message_id, sender, sent_at,
+                        snippet(messages, 0, char(2), char(3), '…', ?6)
+                 FROM messages
+                 WHERE messages MATCH ?1
+                   AND (?2 IS NULL OR conversation = ?2)
+                   AND (?3 IS NULL OR sent_at >= ?3)
+                   AND (?4 IS NULL OR sent_at <= ?4)
+                 ORDER BY rank
+                 LIMIT ?5",
+            )
+            .map_err(index_error)?;
+        let rows = statement
+            .query_map(
+                params![
+                    expression,
+                    query.conversation,
+                    query.since.map(|since| since as i64),
+                    query.until.map(|until| until as i64),
+                    MAX_RESULTS,
+                    SNIPPET_WORDS
+                ],
+                |row| {
+                    Ok(SearchResult {
+                        conversation: row.get(0)?,
+                        message_id: row.get(1)?,
+                        from: row.get(2)?,
+                        sent_at: row.get::<_, i64>(3)? as u64,
+                        snippet: snippet_parts(&row.get::<_, String>(4)?),
+                    })
+                },
+            )
+            .map_err(index_error)?;
+        rows.collect::<Result<_, _>>().map_err(index_error)
+    }
+}
+
+fn insert(
+    connection: &Connection,
+    conversation: &str,
+    message: &ChatMessage,
+) -> Result<(), AppError> {
+    if message.kind != MessageKind::Text || message.deleted || message.body.trim().is_empty() {
+        return Ok(());
+    }
+    connection
+        .execute(
+            "INSERT INTO messages (body, conversation, message_id, sender, sent_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                message.body.replace([MATCH_START, MATCH_END], " "),
+                conversation,
+                message.id,
+                message.from,
+                message.sent_at as i64
+            ],
+        )
+        .map_err(index_error)?;
+    Ok(())
+}
+
+/// Turns what the user typed into an FTS5 query for messages containing every word, or a longer
+/// word starting with it. Each word is quoted so FTS5's own syntax can't get in the way, and
+/// words with no letters or digits are left out since the index has nothing to match them with.
+fn match_expression(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Splits a snippet from SQLite into the text between matches and the matches themselves.
+fn snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut matched = false;
+    for c in snippet.chars() {
+        let now_matched = match c {
+            MATCH_START => true,
+            MATCH_END => false,
+            c => {
+                text.push(c);
+                continue;
+            }
+        };
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: std::mem::take(&mut text),
+                matched,
+            });
+        }
+        matched = now_matched;
+    }
+    if !text.is_empty() {
+        parts.push(SnippetPart { text, matched });
+    }
+    parts
+}
+
+fn index_error(e: rusqlite::Error) -> AppError {
+    AppError::Storage(format!("Search index error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parts(snippet: &str) -> Vec<(String, bool)> {
+        snippet_parts(snippet)
+            .into_iter()
+            .map(|part| (part.text, part.matched))
+            .collect()
+    }
+
+    fn expected(parts: &[(&str, bool)]) -> Vec<(String, bool)> {
+        parts
+            .iter()
+            .map(|(text, matched)| (text.to_string(), *matched))
+            .collect()
+    }
+
+    fn text_message(id: &str, body: &str) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            from: "ann@example.com".to_string(),
+            body: body.to_string(),
+            sent_at: 1_000_000,
+            kind: MessageKind::Text,
+            style: None,
+            emoticons: Vec::new(),
+            file: None,
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: None,
+        }
+    }
+
+    #[test]
+    fn words_are_quoted_prefixes() {
+        assert_eq!(
+            match_expression("  hello wor ").as_deref(),
+            Some("\"hello\"* \"wor\"*")
+        );
+        assert_eq!(
+            match_expression("say \"hi\" OR NEAR(").as_deref(),
+            Some("\"say\"* \"\"\"hi\"\"\"* \"OR\"* \"NEAR(\"*")
+        );
+    }
+
+    #[test]
+    fn punctuation_and_empty_input_match_nothing() {
+        assert_eq!(match_expression(""), None);
+        assert_eq!(match_expression("   "), None);
+        assert_eq!(match_expression("... ?! \"\""), None);
+        assert_eq!(match_expression("?! ok").as_deref(), Some("\"ok\"*"));
+    }
+
+    #[test]
+    fn snippet_is_split_at_the_markers() {
+        assert_eq!(
+            parts("…the \u{2}cat\u{3} and the \u{2}cats\u{3}"),
+            expected(&[
+                ("…the ", false),
+                ("cat", true),
+                (" and the ", false),
+                ("cats", true)
+            ])
+        );
+        assert_eq!(parts("\u{2}all\u{3}"), expected(&[("all", true)]));
+        assert_eq!(parts("no match"), expected(&[("no match", false)]));
+        assert!(parts("").is_empty());
+    }
+
+    #[test]
+    fn markers_in_messages_are_not_indexed() {
+        let (index, created) = SearchIndex::open(Path::new(":memory:")).unwrap();
+        assert!(created);
+        index
+            .add(
+                "ann@example.com",
+                &text_message("1", "fake\u{3} \u{2}match of a cat"),
+            )
+            .unwrap();
+        let results = index
+            .search(&SearchQuery {
+                text: "cat".to_string(),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        let matches: Vec<&str> = results[0]
+            .snippet
+            .iter()
+            .filter(|part| part.matched)
+            .map(|part| part.text.as_str())
+            .collect();
+        assert_eq!(matches, ["cat"]);
+    }
+}
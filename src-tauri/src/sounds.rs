@@ -0,0 +1,133 @@
+//! Plays the sounds attached to messenger events.
+//!
+//! A sound pack is a directory holding one file per `SoundEvent`, named after its
+//! `file_stem()`. Packs are looked up in the user's `sounds` data directory first and then in the
+//! ones bundled with the app, and any sound a pack leaves out falls back to the default pack.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::BufReader,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Sender},
+        Mutex,
+    },
+    thread,
+};
+
+use emiessiene_models::{AppError, Availability, SoundEvent, SoundSettings};
+use log::{info, warn};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, Sink};
+use tauri::{AppHandle, Manager};
+
+use super::{lock, AppState};
+
+const EXTENSIONS: [&str; 3] = ["wav", "ogg", "mp3"];
+
+struct PlayRequest {
+    path: PathBuf,
+    volume: f32,
+}
+
+pub struct SoundPlayer {
+    /// Pack directories, searched in order.
+    pack_dirs: Vec<PathBuf>,
+    sender: Sender<PlayRequest>,
+}
+
+impl SoundPlayer {
+    pub fn new(pack_dirs: Vec<PathBuf>) -> Self {
+        let (sender, receiver) = mpsc::channel::<PlayRequest>();
+        // The output stream can't leave the thread that opened it, so it lives on its own thread
+        // for as long as the app runs.
+        thread::spawn(move || {
+            let (_stream, handle) = match OutputStream::try_default() {
+                Ok(output) => output,
+                Err(e) => {
+                    warn!("No audio output available, sounds are disabled: {}", e);
+                    return;
+                }
+            };
+            for request in receiver {
+                if let Err(e) = play_file(&handle, &request) {
+                    warn!("Failed to play {}: {}", request.path.display(), e);
+                }
+            }
+        });
+        Self { pack_dirs, sender }
+    }
+
+    pub fn add_pack_dir(&mut self, dir: PathBuf) {
+        self.pack_dirs.push(dir);
+    }
+
+    pub fn packs(&self) -> Vec<String> {
+        let mut packs = BTreeSet::new();
+        for dir in &self.pack_dirs {
+            let Ok(entries) = fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if entry.path().is_dir() {
+                    packs.insert(entry.file_name().to_string_lossy().into_owned());
+                }
+            }
+        }
+        packs.into_iter().collect()
+    }
+
+    /// Plays the sound for `event` from the configured pack, whatever the event toggles say.
+    pub fn play(&self, settings: &SoundSettings, event: SoundEvent) -> Result<(), AppError> {
+        let path = self
+            .sound_path(&settings.pack, event)
+            .or_else(|| self.sound_path(SoundSettings::DEFAULT_PACK, event))
+            .ok_or_else(|| {
+                AppError::NotFound(format!("No {} sound in any pack", event.file_stem()))
+            })?;
+        let request = PlayRequest {
+            path,
+            volume: f32::from(settings.volume.min(100)) / 100.0,
+        };
+        self.sender
+            .send(request)
+            .map_err(|_| AppError::NotFound("No audio output device".to_string()))
+    }
+
+    fn sound_path(&self, pack: &str, event: SoundEvent) -> Option<PathBuf> {
+        self.pack_dirs.iter().find_map(|dir| {
+            EXTENSIONS
+                .iter()
+                .map(|extension| {
+                    dir.join(pack)
+                        .join(event.file_stem())
+                        .with_extension(extension)
+                })
+                .find(|path| path.is_file())
+        })
+    }
+}
+
+fn play_file(handle: &OutputStreamHandle, request: &PlayRequest) -> Result<(), String> {
+    let file = File::open(&request.path).map_err(|e| e.to_string())?;
+    let source = Decoder::new(BufReader::new(file)).map_err(|e| e.to_string())?;
+    let sink = Sink::try_new(handle).map_err(|e| e.to_string())?;
+    sink.set_volume(request.volume);
+    sink.append(source);
+    sink.detach();
+    Ok(())
+}
+
+/// Plays the sound for something that just happened, unless the user turned it off or is Busy.
+pub fn play_event(app: &AppHandle, event: SoundEvent) {
+    let state = app.state::<Mutex<AppState>>();
+    let app_state = lock(&state);
+    let settings = &app_state.settings.sounds;
+    if !settings.plays(event) || app_state.user.availability == Availability::Busy {
+        info!("Not playing {:?} sound", event);
+        return;
+    }
+    if let Err(e) = app_state.sounds.play(settings, event) {
+        warn!("Couldn't play {:?} sound: {}", event, e);
+    }
+}
@@ -0,0 +1,240 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use emiessiene_models::AppError;
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+/// Upgrades the `data` of a file by exactly one version.
+pub type Migration = fn(Value) -> Result<Value, AppError>;
+
+/// Describes one kind of persisted file and how to bring old copies up to date.
+///
+/// `migrations[n]` upgrades data from version `n` to `n + 1`, so the current version is the
+/// length of the chain. Version 0 is the original layout, written before files had an envelope.
+pub struct Schema {
+    pub name: &'static str,
+    pub migrations: &'static [Migration],
+}
+
+impl Schema {
+    pub fn current_version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope<T> {
+    version: u32,
+    data: T,
+}
+
+/// Loads a file, migrating it to the current version of its schema first if needed.
+///
+/// When a migration runs, the original file is copied next to itself with a `.bak` suffix
+/// before the upgraded version is written back.
+pub fn load<T: DeserializeOwned>(path: &Path, schema: &Schema) -> Result<T, AppError> {
+    let contents = fs::read_to_string(path)?;
+    let (version, data) = upgrade(parse_json(&contents, schema)?, schema)?;
+
+    if version < schema.current_version() {
+        let backup = backup_path(path, version);
+        info!(
+            "Migrating {} {} from v{} to v{}, backing up original to {}",
+            schema.name,
+            path.display(),
+            version,
+            schema.current_version(),
+            backup.display()
+        );
+        fs::copy(path, &backup)?;
+        save(path, schema, &data)?;
+    }
+
+    serde_json::from_value(data).map_err(|e| {
+        AppError::Storage(format!(
+            "Invalid {} in {}: {}",
+            schema.name,
+            path.display(),
+            e
+        ))
+    })
+}
+
+/// Writes a file in the current version of its schema.
+pub fn save<T: Serialize>(path: &Path, schema: &Schema, data: &T) -> Result<(), AppError> {
+    let json = to_string(schema, data)?;
+    // Write next to the target and rename over it so a crash never leaves a half-written file.
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Parses a document of any known version without touching the disk, e.g. an imported file.
+pub fn from_str<T: DeserializeOwned>(contents: &str, schema: &Schema) -> Result<T, AppError> {
+    let (_, data) = upgrade(parse_json(contents, schema)?, schema)?;
+    serde_json::from_value(data)
+        .map_err(|e| AppError::Storage(format!("Invalid {}: {}", schema.name, e)))
+}
+
+pub fn to_string<T: Serialize>(schema: &Schema, data: &T) -> Result<String, AppError> {
+    let envelope = Envelope {
+        version: schema.current_version(),
+        data,
+    };
+    serde_json::to_string_pretty(&envelope)
+        .map_err(|e| AppError::Storage(format!("Failed to serialize {}: {}", schema.name, e)))
+}
+
+fn parse_json(contents: &str, schema: &Schema) -> Result<Value, AppError> {
+    serde_json::from_str(contents)
+        .map_err(|e| AppError::Storage(format!("{} is not valid JSON: {}", schema.name, e)))
+}
+
+/// Runs the migration chain on a parsed document, returning the version it started at.
+fn upgrade(json: Value, schema: &Schema) -> Result<(u32, Value), AppError> {
+    let (version, mut data) = split_envelope(json)?;
+    let current = schema.current_version();
+    if version > current {
+        return Err(AppError::Storage(format!(
+            "{} was written by a newer version of the app (v{}, this build reads up to v{})",
+            schema.name, version, current
+        )));
+    }
+    for (from, migrate) in schema.migrations.iter().enumerate().skip(version as usize) {
+        data = migrate(data).map_err(|e| {
+            AppError::Storage(format!(
+                "Failed to migrate {} from v{}: {}",
+                schema.name,
+                from,
+                e.message()
+            ))
+        })?;
+    }
+    Ok((version, data))
+}
+
+fn split_envelope(json: Value) -> Result<(u32, Value), AppError> {
+    match json {
+        Value::Object(mut map) if map.contains_key("version") => {
+            let version = map
+                .get("version")
+                .and_then(Value::as_u64)
+                .and_then(|v| u32::try_from(v).ok())
+                .ok_or_else(|| {
+                    AppError::Storage(
+                        "File version must be a non-negative integer below 2^32".to_string(),
+                    )
+                })?;
+            let data = map
+                .remove("data")
+                .ok_or_else(|| AppError::Storage("Versioned file has no data".to_string()))?;
+            Ok((version, data))
+        }
+        legacy => Ok((0, legacy)),
+    }
+}
+
+fn backup_path(path: &Path, version: u32) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_else(|e| {
+            warn!("System clock is before the Unix epoch: {}", e);
+            0
+        });
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".v{}.{}.bak", version, timestamp));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn add_name(mut data: Value) -> Result<Value, AppError> {
+        data["name"] = json!("unnamed");
+        Ok(data)
+    }
+
+    fn count_to_list(mut data: Value) -> Result<Value, AppError> {
+        let count = data["count"].as_u64().unwrap_or_default();
+        data["items"] = json!(vec![0; count as usize]);
+        Ok(data)
+    }
+
+    const TEST_SCHEMA: Schema = Schema {
+        name: "test file",
+        migrations: &[add_name, count_to_list],
+    };
+
+    #[test]
+    fn document_without_envelope_is_version_zero() {
+        let legacy = json!({ "count": 2 });
+        assert_eq!(split_envelope(legacy.clone()).unwrap(), (0, legacy));
+        assert_eq!(split_envelope(json!([1, 2])).unwrap(), (0, json!([1, 2])));
+    }
+
+    #[test]
+    fn every_migration_step_runs_from_the_stored_version() {
+        let (version, data) = upgrade(json!({ "count": 2 }), &TEST_SCHEMA).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(
+            data,
+            json!({ "count": 2, "name": "unnamed", "items": [0, 0] })
+        );
+
+        let v1 = json!({ "version": 1, "data": { "count": 1, "name": "kept" } });
+        let (version, data) = upgrade(v1, &TEST_SCHEMA).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(data, json!({ "count": 1, "name": "kept", "items": [0] }));
+
+        let current = json!({ "version": 2, "data": { "items": [] } });
+        assert_eq!(
+            upgrade(current, &TEST_SCHEMA).unwrap(),
+            (2, json!({ "items": [] }))
+        );
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let newer = json!({ "version": 3, "data": {} });
+        assert!(matches!(
+            upgrade(newer, &TEST_SCHEMA),
+            Err(AppError::Storage(_))
+        ));
+    }
+
+    #[test]
+    fn invalid_version_is_rejected() {
+        for version in [json!(-1), json!("1"), json!(u64::from(u32::MAX) + 1)] {
+            let envelope = json!({ "version": version, "data": {} });
+            assert!(matches!(
+                split_envelope(envelope),
+                Err(AppError::Storage(_))
+            ));
+        }
+        assert!(matches!(
+            split_envelope(json!({ "version": 1 })),
+            Err(AppError::Storage(_))
+        ));
+    }
+
+    #[test]
+    fn backup_is_named_after_the_original_version() {
+        let backup = backup_path(Path::new("/data/friends.json"), 1);
+        let name = backup.file_name().unwrap().to_str().unwrap();
+        let timestamp = name
+            .strip_prefix("friends.json.v1.")
+            .and_then(|rest| rest.strip_suffix(".bak"))
+            .unwrap();
+        assert!(timestamp.parse::<u64>().unwrap() > 0);
+        assert_eq!(backup.parent(), Some(Path::new("/data")));
+    }
+}
@@ -0,0 +1,822 @@
+//! Files sent to and received from friends.
+//!
+//! A file is offered with a `MessageKind::File` message and, once accepted, pulled by the
+//! receiver one chunk at a time: each `FileRequest` names the offset it wants and is answered
+//! with a single `FileChunk`. Packets can arrive in any order, so pulling keeps chunks in
+//! sequence, and resuming is just asking for the offset the partial file has reached. When the
+//! last chunk is in, the receiver checks the whole file against the offered hash.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use emiessiene_models::{events, AppError, ChatMessage, FileOffer, FileTransfer, TransferStatus};
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use tauri::{AppHandle, Manager};
+
+use super::{
+    data_path, emit_all, lock,
+    storage::{self, Schema},
+    transport::{self, PeerEvent},
+    AppState,
+};
+
+/// Largest chunk sent in one packet, in bytes.
+const CHUNK_SIZE: u64 = 256 * 1024;
+/// Where received files go if the user hasn't picked a folder and the system has no downloads
+/// folder.
+const DOWNLOADS_DIR: &str = "downloads";
+
+const TRANSFERS_SCHEMA: Schema = Schema {
+    name: "file transfers",
+    migrations: &[],
+};
+
+pub struct Transfers {
+    path: PathBuf,
+    transfers: Vec<FileTransfer>,
+}
+
+impl Transfers {
+    pub fn load(path: PathBuf) -> Result<Self, AppError> {
+        let mut transfers: Vec<FileTransfer> = if path.exists() {
+            info!("Loading file transfers from {}", path.display());
+            storage::load(&path, &TRANSFERS_SCHEMA)?
+        } else {
+            Vec::new()
+        };
+        // Nothing is moving right after a restart, but whatever was can pick up where it stopped.
+        for transfer in &mut transfers {
+            if transfer.status == TransferStatus::Transferring {
+                transfer.status = TransferStatus::Interrupted;
+            }
+        }
+        Ok(Self { path, transfers })
+    }
+
+    pub fn list(&self, conversation: &str) -> Vec<FileTransfer> {
+        self.transfers
+            .iter()
+            .filter(|t| t.conversation == conversation)
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Result<&FileTransfer, AppError> {
+        self.transfers
+            .iter()
+            .find(|t| t.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("No file transfer {}", id)))
+    }
+
+    fn get_mut(&mut self, id: &str) -> Result<&mut FileTransfer, AppError> {
+        self.transfers
+            .iter_mut()
+            .find(|t| t.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("No file transfer {}", id)))
+    }
+
+    pub fn add(&mut self, transfer: FileTransfer) -> Result<(), AppError> {
+        self.transfers.push(transfer);
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        storage::save(&self.path, &TRANSFERS_SCHEMA, &self.transfers)
+    }
+}
+
+/// Describes a file on disk so it can be offered to a friend.
+pub fn offer(path: &Path) -> Result<FileOffer, AppError> {
+    if !path.is_file() {
+        return Err(AppError::NotFound(format!(
+            "{} is not a file",
+            path.display()
+        )));
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::Validation(format!("{} has no file name", path.display())))?;
+    Ok(FileOffer {
+        name,
+        size: fs::metadata(path)?.len(),
+        hash: hash_file(path)?,
+    })
+}
+
+/// Records a file a friend has just offered, returning the new transfer.
+pub fn offered(
+    transfers: &mut Transfers,
+    email: &str,
+    message: &mut ChatMessage,
+) -> Result<FileTransfer, AppError> {
+    let file = message
+        .file
+        .as_mut()
+        .ok_or_else(|| AppError::Validation("File message without a file".to_string()))?;
+    if file.hash.len() != 64 || !file.hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AppError::Validation(format!(
+            "{} is not a file hash",
+            file.hash
+        )));
+    }
+    if uuid::Uuid::parse_str(&message.id).is_err() {
+        return Err(AppError::Validation(format!(
+            "{} is not a file transfer id",
+            message.id
+        )));
+    }
+    if transfers.get(&message.id).is_ok() {
+        return Err(AppError::Validation(format!(
+            "File transfer {} already exists",
+            message.id
+        )));
+    }
+    // The name ends up in a path on this machine, so only its last component is kept.
+    file.name = safe_file_name(&file.name);
+    let transfer = FileTransfer {
+        id: message.id.clone(),
+        conversation: email.to_string(),
+        incoming: true,
+        file: file.clone(),
+        status: TransferStatus::Offered,
+        transferred: 0,
+        path: None,
+        error: None,
+    };
+    transfers.add(transfer.clone())?;
+    Ok(transfer)
+}
+
+/// Accepts a file offered by a friend and asks for its first chunk.
+pub fn accept(app: &AppHandle, id: &str) -> Result<FileTransfer, AppError> {
+    let dir = download_dir(&lock(&app.state::<Mutex<AppState>>()));
+    let transfer = update(app, id, |t| {
+        if !t.incoming || t.status != TransferStatus::Offered {
+            return Err(AppError::Validation(
+                "That file can't be accepted anymore".to_string(),
+            ));
+        }
+        fs::create_dir_all(&dir)?;
+        // Named locally rather than after the id, which came from the friend.
+        let part = dir.join(format!(".{}.part", uuid::Uuid::new_v4()));
+        t.path = Some(part.to_string_lossy().into_owned());
+        t.status = TransferStatus::Transferring;
+        Ok(())
+    })?;
+    request(app, transfer)
+}
+
+pub fn decline(app: &AppHandle, id: &str) -> Result<FileTransfer, AppError> {
+    let transfer = update(app, id, |t| {
+        if !t.incoming || t.status != TransferStatus::Offered {
+            return Err(AppError::Validation(
+                "That file can't be declined anymore".to_string(),
+            ));
+        }
+        t.status = TransferStatus::Declined;
+        Ok(())
+    })?;
+    let event = PeerEvent::FileDeclined { id: id.to_string() };
+    transport::send_to_friend(app, &transfer.conversation, event)?;
+    Ok(transfer)
+}
+
+/// Picks up an interrupted transfer from where it stopped, from either end.
+pub fn resume(app: &AppHandle, id: &str) -> Result<FileTransfer, AppError> {
+    let transfer = update(app, id, |t| {
+        if t.status != TransferStatus::Interrupted {
+            return Err(AppError::Validation(
+                "Only interrupted transfers can be resumed".to_string(),
+            ));
+        }
+        t.status = TransferStatus::Transferring;
+        t.error = None;
+        Ok(())
+    })?;
+    if transfer.incoming {
+        request(app, transfer)
+    } else {
+        send_chunk(app, &transfer.conversation, id, transfer.transferred)?;
+        Ok(lock(&app.state::<Mutex<AppState>>())
+            .transfers
+            .get(id)?
+            .clone())
+    }
+}
+
+/// Stops a transfer at either end, removing whatever part of the file was received.
+pub fn cancel(app: &AppHandle, id: &str) -> Result<FileTransfer, AppError> {
+    let transfer = update(app, id, |t| {
+        if t.status.is_finished() {
+            return Err(AppError::Validation(
+                "That transfer has already finished".to_string(),
+            ));
+        }
+        t.status = TransferStatus::Cancelled;
+        remove_partial_file(t);
+        Ok(())
+    })?;
+    let event = PeerEvent::FileCancelled { id: id.to_string() };
+    if let Err(e) = transport::send_to_friend(app, &transfer.conversation, event) {
+        warn!(
+            "Couldn't tell {} about the cancelled transfer: {}",
+            transfer.conversation, e
+        );
+    }
+    Ok(transfer)
+}
+
+/// Handles a transfer packet from a friend.
+pub fn handle_event(app: &AppHandle, email: &str, event: PeerEvent) -> Result<(), AppError> {
+    match event {
+        PeerEvent::FileRequest { id, offset } => {
+            update(app, &id, |t| {
+                check_peer(t, email, false)?;
+                if t.status.is_finished() {
+                    return Err(AppError::Validation(format!(
+                        "Transfer {} has already finished",
+                        id
+                    )));
+                }
+                t.status = TransferStatus::Transferring;
+                Ok(())
+            })?;
+            send_chunk(app, email, &id, offset)
+        }
+        PeerEvent::FileChunk { id, offset, data } => receive_chunk(app, email, &id, offset, &data),
+        PeerEvent::FileFinished { id, error } => {
+            update(app, &id, |t| {
+                check_peer(t, email, false)?;
+                match error {
+                    None => {
+                        t.status = TransferStatus::Completed;
+                        t.transferred = t.file.size;
+                    }
+                    Some(error) => {
+                        t.status = TransferStatus::Failed;
+                        t.error = Some(error);
+                    }
+                }
+                Ok(())
+            })?;
+            Ok(())
+        }
+        PeerEvent::FileDeclined { id } => {
+            update(app, &id, |t| {
+                check_peer(t, email, false)?;
+                t.status = TransferStatus::Declined;
+                Ok(())
+            })?;
+            Ok(())
+        }
+        PeerEvent::FileCancelled { id } => {
+            update(app, &id, |t| {
+                if t.conversation != email {
+                    return Err(AppError::Auth(format!(
+                        "{} is not part of transfer {}",
+                        email, t.id
+                    )));
+                }
+                if !t.status.is_finished() {
+                    t.status = TransferStatus::Cancelled;
+                    remove_partial_file(t);
+                }
+                Ok(())
+            })?;
+            Ok(())
+        }
+        _ => Err(AppError::Validation(
+            "Not a file transfer packet".to_string(),
+        )),
+    }
+}
+
+/// Asks the sender for the chunk after the last byte of the partial file.
+fn request(app: &AppHandle, transfer: FileTransfer) -> Result<FileTransfer, AppError> {
+    let offset = resume_offset(&transfer);
+    let transfer = update(app, &transfer.id, |t| {
+        t.transferred = offset;
+        Ok(())
+    })?;
+    let event = PeerEvent::FileRequest {
+        id: transfer.id.clone(),
+        offset,
+    };
+    if let Err(e) = transport::send_to_friend(app, &transfer.conversation, event) {
+        interrupt(app, &transfer.id, &e);
+        return Err(e);
+    }
+    Ok(transfer)
+}
+
+/// How much of an incoming file is already on disk, which is where receiving it carries on.
+fn resume_offset(transfer: &FileTransfer) -> u64 {
+    transfer
+        .path
+        .as_ref()
+        .and_then(|part| fs::metadata(part).ok())
+        .map_or(0, |metadata| metadata.len())
+}
+
+/// Reads the chunk of an offered file starting at `offset` and sends it to the receiver.
+fn send_chunk(app: &AppHandle, email: &str, id: &str, offset: u64) -> Result<(), AppError> {
+    let transfer = lock(&app.state::<Mutex<AppState>>())
+        .transfers
+        .get(id)?
+        .clone();
+    let chunk = transfer
+        .path
+        .as_ref()
+        .ok_or_else(|| AppError::NotFound(format!("Transfer {} has no file", id)))
+        .and_then(|path| read_chunk(Path::new(path), offset, &transfer.file));
+    let chunk = match chunk {
+        Ok(chunk) => chunk,
+        Err(e) => {
+            update(app, id, |t| {
+                t.status = TransferStatus::Failed;
+                t.error = Some(format!("The file couldn't be read: {}", e));
+                Ok(())
+            })?;
+            let event = PeerEvent::FileCancelled { id: id.to_string() };
+            transport::send_to_friend(app, email, event)?;
+            return Err(e);
+        }
+    };
+    update(app, id, |t| {
+        t.transferred = offset + chunk.len() as u64;
+        Ok(())
+    })?;
+    let event = PeerEvent::FileChunk {
+        id: id.to_string(),
+        offset,
+        data: STANDARD.encode(&chunk),
+    };
+    if let Err(e) = transport::send_to_friend(app, email, event) {
+        // The chunk didn't leave, so resuming has to send it again.
+        let _ = update(app, id, |t| {
+            t.transferred = offset;
+            Ok(())
+        });
+        interrupt(app, id, &e);
+        return Err(e);
+    }
+    Ok(())
+}
+
+fn read_chunk(path: &Path, offset: u64, offer: &FileOffer) -> Result<Vec<u8>, AppError> {
+    if offset > offer.size {
+        return Err(AppError::Validation(format!(
+            "Offset {} is past the end of {}",
+            offset, offer.name
+        )));
+    }
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let length = CHUNK_SIZE.min(offer.size - offset);
+    let mut chunk = Vec::new();
+    file.take(length).read_to_end(&mut chunk)?;
+    // A file that shrank since it was offered would otherwise be sent as empty chunks forever.
+    if (chunk.len() as u64) < length {
+        return Err(AppError::Validation(format!(
+            "{} has changed since it was offered",
+            offer.name
+        )));
+    }
+    Ok(chunk)
+}
+
+/// Appends a chunk to the partial file, then asks for the next one or checks the finished file.
+fn receive_chunk(
+    app: &AppHandle,
+    email: &str,
+    id: &str,
+    offset: u64,
+    data: &str,
+) -> Result<(), AppError> {
+    let chunk = STANDARD
+        .decode(data)
+        .map_err(|e| AppError::Validation(format!("Invalid file chunk: {}", e)))?;
+    let mut appended = false;
+    let mut rejected = None;
+    // The lock is held while writing so two copies of the same chunk can't both be appended.
+    let transfer = update(app, id, |t| {
+        check_peer(t, email, true)?;
+        if t.status != TransferStatus::Transferring {
+            return Ok(());
+        }
+        match append_chunk(t, offset, &chunk) {
+            Ok(done) => appended = done,
+            Err(AppError::Validation(e)) => {
+                t.status = TransferStatus::Failed;
+                t.error = Some(e.clone());
+                remove_partial_file(t);
+                rejected = Some(AppError::Validation(e));
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    })?;
+    if let Some(e) = rejected {
+        let event = PeerEvent::FileFinished {
+            id: id.to_string(),
+            error: Some(e.message().to_string()),
+        };
+        transport::send_to_friend(app, email, event)?;
+        return Err(e);
+    }
+    if !appended {
+        info!("Ignoring chunk of {} at {}", id, offset);
+        return Ok(());
+    }
+    if transfer.transferred < transfer.file.size {
+        request(app, transfer)?;
+        return Ok(());
+    }
+    finish(app, transfer)
+}
+
+/// Appends `chunk` to the partial file if it is the next one, returning whether it was.
+///
+/// Chunks for any other offset are copies of ones already received and are left out.
+fn append_chunk(transfer: &mut FileTransfer, offset: u64, chunk: &[u8]) -> Result<bool, AppError> {
+    let part = transfer
+        .path
+        .clone()
+        .ok_or_else(|| AppError::NotFound(format!("Transfer {} has no file", transfer.id)))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(&part)?;
+    let length = file.metadata()?.len();
+    if offset != length {
+        return Ok(false);
+    }
+    if chunk.is_empty() && length < transfer.file.size {
+        return Err(AppError::Validation(format!(
+            "{} stopped sending before the end of {}",
+            transfer.conversation, transfer.file.name
+        )));
+    }
+    if length + chunk.len() as u64 > transfer.file.size {
+        return Err(AppError::Validation(format!(
+            "{} sent more than the {} offered",
+            transfer.conversation, transfer.file.name
+        )));
+    }
+    file.write_all(chunk)?;
+    transfer.transferred = length + chunk.len() as u64;
+    Ok(true)
+}
+
+/// Checks a fully received file against its hash and moves it into the download folder.
+fn finish(app: &AppHandle, transfer: FileTransfer) -> Result<(), AppError> {
+    let part = PathBuf::from(transfer.path.clone().unwrap_or_default());
+    let saved = hash_file(&part).and_then(|hash| {
+        if hash != transfer.file.hash {
+            return Err(AppError::Validation(format!(
+                "{} arrived damaged, its contents don't match what was sent",
+                transfer.file.name
+            )));
+        }
+        let destination = unique_path(part.parent().unwrap_or(Path::new(".")), &transfer.file.name);
+        fs::rename(&part, &destination)?;
+        Ok(destination)
+    });
+    let error = saved.as_ref().err().map(|e| e.message().to_string());
+    update(app, &transfer.id, |t| {
+        match &saved {
+            Ok(destination) => {
+                info!("Received {} into {}", t.file.name, destination.display());
+                t.status = TransferStatus::Completed;
+                t.path = Some(destination.to_string_lossy().into_owned());
+            }
+            Err(e) => {
+                t.status = TransferStatus::Failed;
+                t.error = Some(e.message().to_string());
+                remove_partial_file(t);
+            }
+        }
+        Ok(())
+    })?;
+    let event = PeerEvent::FileFinished {
+        id: transfer.id.clone(),
+        error,
+    };
+    transport::send_to_friend(app, &transfer.conversation, event)
+}
+
+/// Applies a change to a transfer, then saves it and tells the frontend.
+fn update(
+    app: &AppHandle,
+    id: &str,
+    change: impl FnOnce(&mut FileTransfer) -> Result<(), AppError>,
+) -> Result<FileTransfer, AppError> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = lock(&state);
+    let transfer = app_state.transfers.get_mut(id)?;
+    change(transfer)?;
+    let transfer = transfer.clone();
+    app_state.transfers.save()?;
+    drop(app_state);
+    emit_all(app, events::FILE_TRANSFER, transfer.clone());
+    Ok(transfer)
+}
+
+fn interrupt(app: &AppHandle, id: &str, error: &AppError) {
+    warn!("Transfer {} interrupted: {}", id, error);
+    let interrupted = update(app, id, |t| {
+        if t.status == TransferStatus::Transferring {
+            t.status = TransferStatus::Interrupted;
+            t.error = Some(error.message().to_string());
+        }
+        Ok(())
+    });
+    if let Err(e) = interrupted {
+        warn!("Couldn't mark transfer {} as interrupted: {}", id, e);
+    }
+}
+
+/// Makes sure a packet about a transfer comes from the friend on its other end.
+fn check_peer(transfer: &FileTransfer, email: &str, incoming: bool) -> Result<(), AppError> {
+    if transfer.conversation != email || transfer.incoming != incoming {
+        return Err(AppError::Auth(format!(
+            "{} is not sending or receiving transfer {}",
+            email, transfer.id
+        )));
+    }
+    Ok(())
+}
+
+fn remove_partial_file(transfer: &FileTransfer) {
+    if !transfer.incoming || transfer.status == TransferStatus::Completed {
+        return;
+    }
+    if let Some(part) = &transfer.path {
+        if let Err(e) = fs::remove_file(part) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Couldn't remove {}: {}", part, e);
+            }
+        }
+    }
+}
+
+fn download_dir(app_state: &AppState) -> PathBuf {
+    app_state
+        .settings
+        .download_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .or_else(tauri::api::path::download_dir)
+        .unwrap_or_else(|| data_path(DOWNLOADS_DIR))
+}
+
+fn hash_file(path: &Path) -> Result<String, AppError> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Keeps only the last component of a file name sent by a friend, so it can't point anywhere
+/// else on disk.
+fn safe_file_name(name: &str) -> String {
+    let name: String = name
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| match c {
+            ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = name.trim().trim_start_matches('.');
+    if name.is_empty() {
+        "file".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// A path for `name` in `dir` that doesn't overwrite anything, adding ` (1)`, ` (2)`… if needed.
+fn unique_path(dir: &Path, name: &str) -> PathBuf {
+    let path = dir.join(name);
+    if !path.exists() {
+        return path;
+    }
+    let name = Path::new(name);
+    let stem = name
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = name
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|n| dir.join(format!("{} ({}){}", stem, n, extension)))
+        .find(|path| !path.exists())
+        .expect("some numbered name is free")
+}
+
+#[cfg(test)]
+mod tests {
+    use emiessiene_models::MessageKind;
+
+    use super::*;
+
+    const HASH: &str = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    /// An empty directory of its own for a test to write files in.
+    fn test_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("emiessiene-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn offer_of(name: &str, size: u64) -> FileOffer {
+        FileOffer {
+            name: name.to_string(),
+            size,
+            hash: HASH.to_string(),
+        }
+    }
+
+    fn file_message(id: &str, file: FileOffer) -> ChatMessage {
+        ChatMessage {
+            id: id.to_string(),
+            from: "ann@example.com".to_string(),
+            body: String::new(),
+            sent_at: 1_000_000,
+            kind: MessageKind::File,
+            style: None,
+            emoticons: Vec::new(),
+            file: Some(file),
+            image: None,
+            voice: None,
+            edited_at: None,
+            deleted: false,
+            status: None,
+        }
+    }
+
+    fn incoming(path: Option<&Path>, size: u64) -> FileTransfer {
+        FileTransfer {
+            id: uuid::Uuid::new_v4().to_string(),
+            conversation: "ann@example.com".to_string(),
+            incoming: true,
+            file: offer_of("notes.txt", size),
+            status: TransferStatus::Transferring,
+            transferred: 0,
+            path: path.map(|p| p.to_string_lossy().into_owned()),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn file_names_keep_only_their_last_component() {
+        assert_eq!(safe_file_name("../../etc/passwd"), "passwd");
+        assert_eq!(safe_file_name("C:\\Users\\ann\\a:b?.txt"), "a_b_.txt");
+        assert_eq!(safe_file_name("line\nbreak.txt"), "line_break.txt");
+        assert_eq!(safe_file_name(" .hidden "), "hidden");
+        assert_eq!(safe_file_name(".."), "file");
+        assert_eq!(safe_file_name("dir/"), "file");
+        assert_eq!(safe_file_name(""), "file");
+    }
+
+    #[test]
+    fn unique_path_numbers_names_that_are_taken() {
+        let dir = test_dir();
+        assert_eq!(unique_path(&dir, "a.txt"), dir.join("a.txt"));
+        fs::write(dir.join("a.txt"), "").unwrap();
+        assert_eq!(unique_path(&dir, "a.txt"), dir.join("a (1).txt"));
+        fs::write(dir.join("a (1).txt"), "").unwrap();
+        assert_eq!(unique_path(&dir, "a.txt"), dir.join("a (2).txt"));
+        fs::write(dir.join("notes"), "").unwrap();
+        assert_eq!(unique_path(&dir, "notes"), dir.join("notes (1)"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn offered_files_are_checked_and_recorded() {
+        let dir = test_dir();
+        let mut transfers = Transfers::load(dir.join("transfers.json")).unwrap();
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let mut message = file_message(&id, offer_of("../secret.txt", 5));
+        let transfer = offered(&mut transfers, "ann@example.com", &mut message).unwrap();
+        assert_eq!(transfer.file.name, "secret.txt");
+        assert_eq!(message.file.as_ref().unwrap().name, "secret.txt");
+        assert_eq!(transfer.status, TransferStatus::Offered);
+        assert_eq!(transfers.list("ann@example.com").len(), 1);
+
+        let mut again = file_message(&id, offer_of("other.txt", 5));
+        assert!(matches!(
+            offered(&mut transfers, "ann@example.com", &mut again),
+            Err(AppError::Validation(_))
+        ));
+        let mut not_uuid = file_message("../1", offer_of("a.txt", 5));
+        assert!(matches!(
+            offered(&mut transfers, "ann@example.com", &mut not_uuid),
+            Err(AppError::Validation(_))
+        ));
+        let mut bad_hash = file_message(
+            &uuid::Uuid::new_v4().to_string(),
+            FileOffer {
+                hash: "not a hash".to_string(),
+                ..offer_of("a.txt", 5)
+            },
+        );
+        assert!(matches!(
+            offered(&mut transfers, "ann@example.com", &mut bad_hash),
+            Err(AppError::Validation(_))
+        ));
+        let mut no_file = file_message(&uuid::Uuid::new_v4().to_string(), offer_of("a.txt", 5));
+        no_file.file = None;
+        assert!(matches!(
+            offered(&mut transfers, "ann@example.com", &mut no_file),
+            Err(AppError::Validation(_))
+        ));
+        assert_eq!(transfers.list("ann@example.com").len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resuming_starts_after_the_bytes_on_disk() {
+        let dir = test_dir();
+        let part = dir.join(".part");
+        assert_eq!(resume_offset(&incoming(None, 10)), 0);
+        assert_eq!(resume_offset(&incoming(Some(&part), 10)), 0);
+        fs::write(&part, b"hello").unwrap();
+        assert_eq!(resume_offset(&incoming(Some(&part), 10)), 5);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn only_the_next_chunk_is_appended() {
+        let dir = test_dir();
+        let part = dir.join(".part");
+        let mut transfer = incoming(Some(&part), 10);
+        assert!(append_chunk(&mut transfer, 0, b"hello").unwrap());
+        assert!(!append_chunk(&mut transfer, 0, b"hello").unwrap());
+        assert!(!append_chunk(&mut transfer, 8, b"xx").unwrap());
+        assert_eq!(transfer.transferred, 5);
+        assert!(matches!(
+            append_chunk(&mut transfer, 5, b""),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            append_chunk(&mut transfer, 5, b"too long"),
+            Err(AppError::Validation(_))
+        ));
+        assert!(append_chunk(&mut transfer, 5, b"world").unwrap());
+        assert_eq!(transfer.transferred, 10);
+        assert_eq!(fs::read(&part).unwrap(), b"helloworld");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn empty_files_finish_with_an_empty_chunk() {
+        let dir = test_dir();
+        let mut transfer = incoming(Some(&dir.join(".part")), 0);
+        assert!(append_chunk(&mut transfer, 0, b"").unwrap());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn chunks_stop_at_the_offered_size() {
+        let dir = test_dir();
+        let path = dir.join("notes.txt");
+        fs::write(&path, b"hello").unwrap();
+        let offer = offer_of("notes.txt", 5);
+        assert_eq!(read_chunk(&path, 0, &offer).unwrap(), b"hello");
+        assert_eq!(read_chunk(&path, 3, &offer).unwrap(), b"lo");
+        assert!(read_chunk(&path, 5, &offer).unwrap().is_empty());
+        assert!(matches!(
+            read_chunk(&path, 6, &offer),
+            Err(AppError::Validation(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn files_that_shrank_since_the_offer_fail_to_send() {
+        let dir = test_dir();
+        let path = dir.join("notes.txt");
+        fs::write(&path, b"hi").unwrap();
+        let offer = offer_of("notes.txt", 5);
+        assert!(matches!(
+            read_chunk(&path, 0, &offer),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            read_chunk(&path, 2, &offer),
+            Err(AppError::Validation(_))
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,721 @@
+//! Peer-to-peer connection between two running copies of the app.
+//!
+//! Every instance listens on a TCP address and reaches friends at the `address` stored on their
+//! `Friend` entry. Each packet is a single JSON document sent over its own short-lived
+//! connection, which keeps both sides stateless and makes two local instances easy to test.
+
+use std::{
+    env,
+    io::{Read, Write},
+    net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use emiessiene_models::{
+    emoticons::CustomEmoticon, events, is_group, AppError, Availability, ChatMessage,
+    ChatMessageEvent, Group, MessageKind, MessageStatus, SoundEvent, TypingEvent,
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use super::{
+    emit_all,
+    emoticons::{is_hash, EmoticonImage},
+    groups, lock, notifications, receipts, sounds, throttle, transfers, unread_changed, windows,
+    AppState, NUDGE_INTERVAL,
+};
+
+pub const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:7001";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+/// Packets larger than this are dropped instead of being buffered.
+const MAX_PACKET_SIZE: u64 = 16 * 1024 * 1024;
+/// How long a friend counts as typing after their last typing notification.
+const TYPING_TIMEOUT: Duration = Duration::from_secs(6);
+
+#[derive(Serialize, Deserialize)]
+pub struct Packet {
+    /// Email of the sending user, used to find them in the receiver's friends list.
+    pub from: String,
+    pub event: PeerEvent,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PeerEvent {
+    Presence {
+        name: String,
+        status: String,
+        availability: Availability,
+        /// Hash of the sender's display picture, which the receiver asks for if it doesn't
+        /// have it yet.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_picture: Option<String>,
+    },
+    /// The receiver of a presence update wants the display picture it announced.
+    DisplayPictureRequest {
+        hash: String,
+    },
+    DisplayPicture {
+        hash: String,
+        /// The PNG, base64-encoded.
+        data: String,
+    },
+    Message {
+        /// Boxed to keep the other, much smaller events small.
+        message: Box<ChatMessage>,
+        /// Images of custom emoticons in the message the receiver hasn't been sent before.
+        #[serde(default)]
+        emoticons: Vec<EmoticonImage>,
+        /// The picture or voice clip the message carries, base64-encoded.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        attachment: Option<String>,
+        /// The group conversation the message was sent to, if it wasn't just to the receiver.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
+    /// The sender changed the text of a message they sent before.
+    MessageEdit {
+        id: String,
+        body: String,
+        edited_at: u64,
+        /// The sender's custom emoticons that appear in the new text.
+        #[serde(default)]
+        custom: Vec<CustomEmoticon>,
+        /// Images of those emoticons the receiver hasn't been sent before.
+        #[serde(default)]
+        emoticons: Vec<EmoticonImage>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
+    /// The sender took back a message they sent before.
+    MessageDelete {
+        id: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        group: Option<String>,
+    },
+    /// The sender's app has the messages with these ids, or the sender has read them.
+    Receipt {
+        ids: Vec<String>,
+        status: MessageStatus,
+    },
+    /// The sender invited someone into a group conversation, or started one with the receiver.
+    /// Members are only ever added this way, never removed.
+    GroupUpdate {
+        group: Group,
+    },
+    /// The sender left a group conversation.
+    GroupLeave {
+        id: String,
+    },
+    /// The sender is writing a message to the receiver.
+    Typing,
+    /// The receiver of a file wants the chunk starting at `offset`.
+    FileRequest {
+        id: String,
+        offset: u64,
+    },
+    FileChunk {
+        id: String,
+        offset: u64,
+        /// The chunk, base64-encoded.
+        data: String,
+    },
+    /// The receiver has the whole file, or rejected it for the reason in `error`.
+    FileFinished {
+        id: String,
+        error: Option<String>,
+    },
+    FileDeclined {
+        id: String,
+    },
+    /// Either end stopped the transfer.
+    FileCancelled {
+        id: String,
+    },
+}
+
+impl PeerEvent {
+    /// The group conversation the event is about, which lets members who aren't the receiver's
+    /// friends send it.
+    fn group(&self) -> Option<&str> {
+        match self {
+            PeerEvent::Message { group, .. }
+            | PeerEvent::MessageEdit { group, .. }
+            | PeerEvent::MessageDelete { group, .. } => group.as_deref(),
+            PeerEvent::GroupUpdate { group } => Some(&group.id),
+            PeerEvent::GroupLeave { id } => Some(id),
+            _ => None,
+        }
+    }
+}
+
+/// The address this instance listens on, set with `EMIESSIENE_ADDRESS` to run several at once.
+pub fn listen_address() -> String {
+    env::var("EMIESSIENE_ADDRESS").unwrap_or_else(|_| DEFAULT_LISTEN_ADDRESS.to_string())
+}
+
+pub fn send(address: &str, packet: &Packet) -> Result<(), AppError> {
+    let transport_error = |e: std::io::Error| AppError::Transport(format!("{}: {}", address, e));
+    let socket_address = address
+        .to_socket_addrs()
+        .map_err(transport_error)?
+        .next()
+        .ok_or_else(|| AppError::Transport(format!("{} doesn't resolve to an address", address)))?;
+    let mut stream =
+        TcpStream::connect_timeout(&socket_address, CONNECT_TIMEOUT).map_err(transport_error)?;
+    let json = serde_json::to_vec(packet)
+        .map_err(|e| AppError::Transport(format!("Failed to encode packet: {}", e)))?;
+    stream.write_all(&json).map_err(transport_error)?;
+    stream.shutdown(Shutdown::Write).map_err(transport_error)?;
+    Ok(())
+}
+
+/// Sends a packet to a friend by email, looking up their address in the app state.
+pub fn send_to_friend(app: &AppHandle, email: &str, event: PeerEvent) -> Result<(), AppError> {
+    let (from, address) = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = lock(&state);
+        let friend = app_state
+            .friends
+            .iter()
+            .find(|f| f.email == email)
+            .ok_or_else(|| AppError::NotFound(format!("No friend with email {}", email)))?;
+        let address = friend.address.clone().ok_or_else(|| {
+            AppError::Transport(format!(
+                "{} has no address set, add one in Edit contact",
+                friend.display_name()
+            ))
+        })?;
+        (app_state.user.email.clone(), address)
+    };
+    send(&address, &Packet { from, event })
+}
+
+/// Sends a packet to everyone else in a conversation, which is just the friend unless it is a
+/// group conversation.
+pub fn send_to_conversation(
+    app: &AppHandle,
+    conversation: &str,
+    event: PeerEvent,
+) -> Result<(), AppError> {
+    if !is_group(conversation) {
+        return send_to_friend(app, conversation, event);
+    }
+    let group = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = lock(&state);
+        app_state.groups.get(conversation)?.clone()
+    };
+    send_to_group(app, &group, None, event)
+}
+
+/// Sends a packet to every member of a group but the user and `skip`. Members who can't be
+/// reached are only logged, unless none of them could be.
+pub fn send_to_group(
+    app: &AppHandle,
+    group: &Group,
+    skip: Option<&str>,
+    event: PeerEvent,
+) -> Result<(), AppError> {
+    let (from, recipients) = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = lock(&state);
+        let from = app_state.user.email.clone();
+        // Friends' own entries are more up to date than what the inviter knew about them.
+        let recipients: Vec<(String, Option<String>)> = group
+            .others(&from)
+            .filter(|p| Some(p.email.as_str()) != skip)
+            .map(|p| {
+                let address = app_state
+                    .friends
+                    .iter()
+                    .find(|f| f.email == p.email)
+                    .and_then(|f| f.address.clone())
+                    .or_else(|| p.address.clone());
+                (p.email.clone(), address)
+            })
+            .collect();
+        (from, recipients)
+    };
+    let packet = Packet { from, event };
+    let mut delivered = recipients.is_empty();
+    let mut last_error = None;
+    for (email, address) in recipients {
+        let result = address
+            .ok_or_else(|| AppError::Transport(format!("{} has no address set", email)))
+            .and_then(|address| send(&address, &packet));
+        match result {
+            Ok(()) => delivered = true,
+            Err(e) => {
+                info!("Couldn't reach {} in {}: {}", email, group.id, e);
+                last_error = Some(e);
+            }
+        }
+    }
+    match last_error {
+        Some(e) if !delivered => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Tells every friend with a known address about the user's current presence.
+pub fn broadcast_presence(app: &AppHandle) {
+    let (packet_from, presence, addresses) = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = lock(&state);
+        let user = &app_state.user;
+        let addresses: Vec<String> = app_state
+            .friends
+            .iter()
+            .filter_map(|f| f.address.clone())
+            .collect();
+        (
+            user.email.clone(),
+            (
+                user.name.clone(),
+                user.status.clone(),
+                user.availability.clone(),
+                user.display_picture.clone(),
+            ),
+            addresses,
+        )
+    };
+    for address in addresses {
+        let (name, status, availability, display_picture) = presence.clone();
+        let packet = Packet {
+            from: packet_from.clone(),
+            event: PeerEvent::Presence {
+                name,
+                status,
+                availability,
+                display_picture,
+            },
+        };
+        if let Err(e) = send(&address, &packet) {
+            info!("Couldn't send presence to {}: {}", address, e);
+        }
+    }
+}
+
+pub fn spawn_listener(app: AppHandle, address: String) {
+    thread::spawn(move || {
+        let listener = match TcpListener::bind(&address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                error!("Couldn't listen for peers on {}: {}", address, e);
+                return;
+            }
+        };
+        info!("Listening for peers on {}", address);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let app = app.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = receive(&app, stream) {
+                            warn!("Dropped packet: {}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept peer connection: {}", e),
+            }
+        }
+    });
+}
+
+fn receive(app: &AppHandle, stream: TcpStream) -> Result<(), AppError> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut json = Vec::new();
+    stream.take(MAX_PACKET_SIZE).read_to_end(&mut json)?;
+    let packet: Packet = serde_json::from_slice(&json)
+        .map_err(|e| AppError::Validation(format!("Malformed packet: {}", e)))?;
+    handle_packet(app, packet)
+}
+
+fn handle_packet(app: &AppHandle, packet: Packet) -> Result<(), AppError> {
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = lock(&state);
+    let friend = match app_state.friends.iter().find(|f| f.email == packet.from) {
+        Some(friend) => friend.clone(),
+        // Members of a group the user hasn't added are only heard from about that group.
+        None => packet
+            .event
+            .group()
+            .and_then(|id| app_state.groups.get(id).ok())
+            .and_then(|group| group.participant(&packet.from))
+            .map(groups::stranger)
+            .ok_or_else(|| AppError::Auth(format!("{} is not in the friends list", packet.from)))?,
+    };
+
+    match packet.event {
+        PeerEvent::Presence {
+            name,
+            status,
+            availability,
+            display_picture,
+        } => {
+            let signed_in = app_state.update_presence(&friend.email, name, status, availability)?;
+            let missing_picture = app_state.update_display_picture(
+                &friend.email,
+                display_picture.filter(|hash| is_hash(hash)),
+            )?;
+            drop(app_state);
+            emit_all(app, events::FRIENDS_CHANGED, ());
+            if let Some(hash) = missing_picture {
+                let request = PeerEvent::DisplayPictureRequest { hash };
+                if let Err(e) = send_to_friend(app, &friend.email, request) {
+                    info!(
+                        "Couldn't ask {} for their display picture: {}",
+                        friend.email, e
+                    );
+                }
+            }
+            if let Some(friend) = signed_in {
+                sounds::play_event(app, SoundEvent::ContactOnline);
+                notifications::friend_signed_in(app, &friend);
+            }
+        }
+        PeerEvent::DisplayPictureRequest { hash } => {
+            // Only the current picture is handed out, whatever else is in the store.
+            if app_state.user.display_picture.as_ref() != Some(&hash) {
+                return Err(AppError::NotFound(format!(
+                    "{} is not the current display picture",
+                    hash
+                )));
+            }
+            let data = app_state.avatars.avatar_for_transfer(&hash)?;
+            drop(app_state);
+            send_to_friend(app, &friend.email, PeerEvent::DisplayPicture { hash, data })?;
+        }
+        PeerEvent::DisplayPicture { hash, data } => {
+            app_state.avatars.store_received(&hash, &data)?;
+            app_state.update_display_picture(&friend.email, Some(hash))?;
+            drop(app_state);
+            emit_all(app, events::FRIENDS_CHANGED, ());
+        }
+        PeerEvent::Message {
+            message,
+            emoticons,
+            attachment,
+            group,
+        } => {
+            // Attribute the message to the packet's sender, so it can't claim to be from
+            // someone else in the conversation. Packets aren't authenticated, so this is only
+            // as trustworthy as the `from` the peer put on the packet.
+            let mut message = *message;
+            message.from = friend.email.clone();
+            // A message can't arrive already changed, and how far it got is only up to the sender.
+            message.edited_at = None;
+            message.deleted = false;
+            message.status = None;
+            let conversation = conversation_of(&app_state, &friend.email, group)?;
+            // The id is the sender's to choose, and reusing one would let them change a message
+            // that is already in the conversation.
+            if app_state.history.contains(&conversation, &message.id)? {
+                return Err(AppError::Validation(format!(
+                    "{} sent message {} again",
+                    friend.email, message.id
+                )));
+            }
+            for image in &emoticons {
+                if let Err(e) = app_state.emoticons.store_received(image) {
+                    warn!("Dropped emoticon from {}: {}", friend.email, e);
+                }
+            }
+            let nudge = message.kind == MessageKind::Nudge;
+            if nudge
+                && !throttle(
+                    &mut app_state.nudges_received,
+                    &friend.email,
+                    NUDGE_INTERVAL,
+                )
+            {
+                info!(
+                    "Ignoring nudge from {}, the last one was too recent",
+                    friend.email
+                );
+                return Ok(());
+            }
+            let missing =
+                |what: &str| AppError::Validation(format!("Message without its {}", what));
+            match message.kind {
+                MessageKind::Image => {
+                    let (Some(shared), Some(data)) = (&message.image, &attachment) else {
+                        return Err(missing("picture"));
+                    };
+                    message.image = Some(app_state.images.store_received(shared, data)?);
+                }
+                MessageKind::Voice => {
+                    let (Some(clip), Some(data)) = (&message.voice, &attachment) else {
+                        return Err(missing("voice clip"));
+                    };
+                    message.voice = Some(app_state.clips.store_received(clip, data)?);
+                }
+                MessageKind::File if is_group(&conversation) => {
+                    return Err(AppError::Validation(
+                        "Files can't be offered to a group conversation".to_string(),
+                    ));
+                }
+                MessageKind::Joined | MessageKind::Left => {
+                    return Err(AppError::Validation(format!(
+                        "{:?} messages are only recorded, never sent",
+                        message.kind
+                    )));
+                }
+                _ => {}
+            }
+            let offer = if message.kind == MessageKind::File {
+                Some(transfers::offered(
+                    &mut app_state.transfers,
+                    &friend.email,
+                    &mut message,
+                )?)
+            } else {
+                None
+            };
+            app_state.history.append(&conversation, message.clone())?;
+            let was_typing = app_state.typing_received.remove(&conversation).is_some();
+            let focused = app_state.is_chat_focused(&conversation);
+            if !focused {
+                *app_state.unread.entry(conversation.clone()).or_default() += 1;
+            }
+            drop(app_state);
+            if !focused {
+                unread_changed(app);
+            }
+            if was_typing {
+                emit_typing(app, &friend.email, false);
+            }
+            if !is_group(&conversation) {
+                receipts::received(app, &friend.email, &message.id, focused);
+            }
+            if !friend.muted {
+                let sound = if nudge {
+                    SoundEvent::Nudge
+                } else {
+                    SoundEvent::NewMessage
+                };
+                sounds::play_event(app, sound);
+                if !focused {
+                    notifications::message_received(app, &friend, &conversation, &message);
+                }
+            }
+            emit_all(
+                app,
+                events::CHAT_MESSAGE,
+                ChatMessageEvent {
+                    conversation: conversation.clone(),
+                    message,
+                },
+            );
+            if let Some(transfer) = offer {
+                emit_all(app, events::FILE_TRANSFER, transfer);
+            }
+            if nudge {
+                windows::open_chat(app, &conversation);
+                emit_all(app, events::NUDGE, conversation);
+            }
+        }
+        PeerEvent::MessageEdit {
+            id,
+            body,
+            edited_at,
+            custom,
+            emoticons,
+            group,
+        } => {
+            let conversation = conversation_of(&app_state, &friend.email, group)?;
+            if sent_by(&mut app_state, &conversation, &id, &friend.email)?.kind != MessageKind::Text
+            {
+                return Err(AppError::Validation(format!(
+                    "Message {} isn't text and can't be edited",
+                    id
+                )));
+            }
+            for image in &emoticons {
+                if let Err(e) = app_state.emoticons.store_received(image) {
+                    warn!("Dropped emoticon from {}: {}", friend.email, e);
+                }
+            }
+            let message = app_state.history.update(&conversation, &id, |message| {
+                message.body = body;
+                message.emoticons = custom;
+                message.edited_at = Some(edited_at);
+            })?;
+            drop(app_state);
+            emit_message_changed(app, conversation, message);
+        }
+        PeerEvent::Receipt { ids, status } => {
+            drop(app_state);
+            receipts::handle(app, &friend.email, ids, status)?;
+        }
+        PeerEvent::MessageDelete { id, group } => {
+            let conversation = conversation_of(&app_state, &friend.email, group)?;
+            sent_by(&mut app_state, &conversation, &id, &friend.email)?;
+            let message = app_state
+                .history
+                .update(&conversation, &id, ChatMessage::delete)?;
+            drop(app_state);
+            emit_message_changed(app, conversation, message);
+        }
+        PeerEvent::GroupUpdate { group } => {
+            let me = app_state.user.email.clone();
+            if !is_group(&group.id)
+                || group.participant(&me).is_none()
+                || group.participant(&friend.email).is_none()
+            {
+                return Err(AppError::Validation(format!(
+                    "Group update for {} leaves out its sender or the user",
+                    group.id
+                )));
+            }
+            let previous = app_state.groups.get(&group.id).ok().cloned();
+            let mut updated = previous.clone().unwrap_or(Group {
+                id: group.id.clone(),
+                participants: Vec::new(),
+            });
+            let mut joined = Vec::new();
+            for participant in group.participants {
+                if updated.participant(&participant.email).is_some() {
+                    continue;
+                }
+                if participant.email != me {
+                    joined.push(groups::membership_message(
+                        MessageKind::Joined,
+                        &participant,
+                    ));
+                }
+                updated.participants.push(participant);
+            }
+            app_state.groups.save_group(updated.clone())?;
+            for message in &joined {
+                app_state.history.append(&updated.id, message.clone())?;
+            }
+            drop(app_state);
+            emit_all(app, events::GROUPS_CHANGED, ());
+            for message in joined {
+                emit_all(
+                    app,
+                    events::CHAT_MESSAGE,
+                    ChatMessageEvent {
+                        conversation: updated.id.clone(),
+                        message,
+                    },
+                );
+            }
+            if previous.is_none() {
+                windows::open_chat(app, &updated.id);
+            }
+        }
+        PeerEvent::GroupLeave { id } => {
+            let mut group = app_state.groups.get(&id)?.clone();
+            let Some(participant) = group.participant(&friend.email).cloned() else {
+                return Ok(());
+            };
+            group.participants.retain(|p| p.email != participant.email);
+            app_state.groups.save_group(group)?;
+            let message = groups::membership_message(MessageKind::Left, &participant);
+            app_state.history.append(&id, message.clone())?;
+            drop(app_state);
+            emit_all(app, events::GROUPS_CHANGED, ());
+            emit_all(
+                app,
+                events::CHAT_MESSAGE,
+                ChatMessageEvent {
+                    conversation: id,
+                    message,
+                },
+            );
+        }
+        PeerEvent::Typing => {
+            let received_at = Instant::now();
+            app_state
+                .typing_received
+                .insert(friend.email.clone(), received_at);
+            drop(app_state);
+            emit_typing(app, &friend.email, true);
+            let app = app.clone();
+            thread::spawn(move || {
+                thread::sleep(TYPING_TIMEOUT);
+                let state = app.state::<Mutex<AppState>>();
+                let mut app_state = lock(&state);
+                // A later notification or a message may have replaced this one in the meantime.
+                if app_state.typing_received.get(&friend.email) == Some(&received_at) {
+                    app_state.typing_received.remove(&friend.email);
+                    drop(app_state);
+                    emit_typing(&app, &friend.email, false);
+                }
+            });
+        }
+        event => {
+            drop(app_state);
+            transfers::handle_event(app, &friend.email, event)?;
+        }
+    }
+    Ok(())
+}
+
+/// The conversation a message from `from` belongs to: the group it names, as long as they are
+/// in it, or else the one with them.
+fn conversation_of(
+    app_state: &AppState,
+    from: &str,
+    group: Option<String>,
+) -> Result<String, AppError> {
+    let Some(id) = group else {
+        return Ok(from.to_string());
+    };
+    if app_state.groups.get(&id)?.participant(from).is_none() {
+        return Err(AppError::Auth(format!(
+            "{} is not in the group conversation {}",
+            from, id
+        )));
+    }
+    Ok(id)
+}
+
+/// A message in a conversation, as long as `from` sent it and hasn't deleted it yet, which is
+/// all it takes for them to change it. The time they have to do so is up to them.
+fn sent_by(
+    app_state: &mut AppState,
+    conversation: &str,
+    id: &str,
+    from: &str,
+) -> Result<ChatMessage, AppError> {
+    app_state
+        .history
+        .conversation(conversation)?
+        .iter()
+        .find(|m| m.id == id && !m.deleted)
+        .filter(|m| m.from == from)
+        .cloned()
+        .ok_or_else(|| {
+            AppError::Auth(format!(
+                "{} has no message {} to change in {}",
+                from, id, conversation
+            ))
+        })
+}
+
+fn emit_message_changed(app: &AppHandle, conversation: String, message: ChatMessage) {
+    emit_all(
+        app,
+        events::MESSAGE_CHANGED,
+        ChatMessageEvent {
+            conversation,
+            message,
+        },
+    );
+}
+
+fn emit_typing(app: &AppHandle, email: &str, typing: bool) {
+    let event = TypingEvent {
+        conversation: email.to_string(),
+        typing,
+    };
+    emit_all(app, events::TYPING, event);
+}
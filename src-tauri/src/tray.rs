@@ -0,0 +1,196 @@
+//! The icon in the system tray, with a menu to change availability and sign out.
+//!
+//! The icon is the app's own with a dot in the corner showing the user's availability, drawn here
+//! rather than shipped as one file per status. While messages are waiting to be read, the dot
+//! blinks and the tooltip says how many there are.
+
+use std::{sync::Mutex, thread, time::Duration};
+
+use emiessiene_models::{events, Availability};
+use image::{Rgba, RgbaImage};
+use log::warn;
+use tauri::{
+    AppHandle, CustomMenuItem, Icon, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem, SystemTraySubmenu,
+};
+
+use super::{apply_availability, emit_all, lock, quit, windows, AppState};
+
+const BASE_ICON: &[u8] = include_bytes!("../icons/32x32.png");
+const BLINK_INTERVAL: Duration = Duration::from_millis(700);
+const UNREAD_COLOR: Rgba<u8> = Rgba([0x1e, 0x90, 0xff, 0xff]);
+
+const OPEN_CONTACT_LIST: &str = "open-contact-list";
+const SIGN_OUT: &str = "sign-out";
+const QUIT: &str = "quit";
+/// Prefix of the status items' ids, followed by the `Availability` they set.
+const STATUS_PREFIX: &str = "status-";
+
+/// Statuses offered in the menu, in the order the sign-in page lists them.
+const STATUSES: [(Availability, &str); 4] = [
+    (Availability::Online, "Online"),
+    (Availability::Busy, "Busy"),
+    (Availability::Away, "Away"),
+    (Availability::Offline, "Appear offline"),
+];
+
+pub fn system_tray() -> SystemTray {
+    let statuses = STATUSES
+        .iter()
+        .fold(SystemTrayMenu::new(), |menu, (availability, label)| {
+            menu.add_item(CustomMenuItem::new(status_id(availability), *label))
+        });
+    let menu = SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new(OPEN_CONTACT_LIST, "Open contact list"))
+        .add_submenu(SystemTraySubmenu::new("Status", statuses))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(SIGN_OUT, "Sign out"))
+        .add_item(CustomMenuItem::new(QUIT, "Quit"));
+    SystemTray::new().with_menu(menu)
+}
+
+pub fn handle_event(app: &AppHandle, event: SystemTrayEvent) {
+    match event {
+        SystemTrayEvent::LeftClick { .. } | SystemTrayEvent::DoubleClick { .. } => {
+            show_contact_list(app)
+        }
+        SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
+            OPEN_CONTACT_LIST => show_contact_list(app),
+            SIGN_OUT => sign_out(app),
+            QUIT => quit(app),
+            id => match STATUSES.iter().find(|(status, _)| status_id(status) == id) {
+                Some((availability, _)) => apply_availability(app, availability.clone()),
+                None => warn!("Unknown tray menu item {}", id),
+            },
+        },
+        _ => {}
+    }
+}
+
+/// Shows the user's availability in the tray icon and ticks it in the status menu.
+pub fn update(app: &AppHandle) {
+    let availability = lock(&app.state::<Mutex<AppState>>())
+        .user
+        .availability
+        .clone();
+    let tray = app.tray_handle();
+    if let Err(e) = tray.set_icon(icon(availability_color(&availability))) {
+        warn!("Couldn't update the tray icon: {}", e);
+    }
+    for (status, _) in &STATUSES {
+        if let Err(e) = tray
+            .get_item(&status_id(status))
+            .set_selected(*status == availability)
+        {
+            warn!("Couldn't update the tray menu: {}", e);
+        }
+    }
+}
+
+/// Blinks the tray icon for as long as there are unread messages, and keeps the tooltip's count
+/// of them up to date.
+pub fn spawn_blinker(app: AppHandle) {
+    thread::spawn(move || {
+        let mut lit = false;
+        let mut shown_unread = None;
+        loop {
+            thread::sleep(BLINK_INTERVAL);
+            let (unread, availability) = {
+                let state = app.state::<Mutex<AppState>>();
+                let app_state = lock(&state);
+                (
+                    app_state.unread.values().sum::<u32>(),
+                    app_state.user.availability.clone(),
+                )
+            };
+            let tray = app.tray_handle();
+            if shown_unread != Some(unread) {
+                shown_unread = Some(unread);
+                let tooltip = match unread {
+                    0 => "EmiEssiEne".to_string(),
+                    1 => "EmiEssiEne - 1 unread message".to_string(),
+                    n => format!("EmiEssiEne - {} unread messages", n),
+                };
+                if let Err(e) = tray.set_tooltip(&tooltip) {
+                    warn!("Couldn't update the tray tooltip: {}", e);
+                }
+            }
+            // Once everything is read the icon is left showing the availability again.
+            if unread == 0 && !lit {
+                continue;
+            }
+            lit = unread > 0 && !lit;
+            let color = if lit {
+                UNREAD_COLOR
+            } else {
+                availability_color(&availability)
+            };
+            if let Err(e) = tray.set_icon(icon(color)) {
+                warn!("Couldn't update the tray icon: {}", e);
+            }
+        }
+    });
+}
+
+/// Brings the contact list back from the tray.
+pub fn show_contact_list(app: &AppHandle) {
+    let Some(window) = app.get_window("main") else {
+        return;
+    };
+    let _ = window.unminimize();
+    if let Err(e) = window.show().and_then(|_| window.set_focus()) {
+        warn!("Couldn't show the contact list: {}", e);
+    }
+}
+
+/// Appears offline, closes every conversation and goes back to the sign-in page.
+pub fn sign_out(app: &AppHandle) {
+    apply_availability(app, Availability::Offline);
+    let conversations = lock(&app.state::<Mutex<AppState>>())
+        .chat_windows
+        .open_conversations();
+    for conversation in conversations {
+        if let Err(e) = windows::close(app, &conversation) {
+            warn!("Couldn't close the window for {}: {}", conversation, e);
+        }
+    }
+    show_contact_list(app);
+    emit_all(app, events::SIGNED_OUT, ());
+}
+
+fn status_id(availability: &Availability) -> String {
+    format!("{}{}", STATUS_PREFIX, availability)
+}
+
+fn availability_color(availability: &Availability) -> Rgba<u8> {
+    match availability {
+        Availability::Online => Rgba([0x3c, 0xb3, 0x4a, 0xff]),
+        Availability::Busy => Rgba([0xd6, 0x3a, 0x2f, 0xff]),
+        Availability::Away => Rgba([0xf2, 0xa9, 0x1e, 0xff]),
+        Availability::Offline => Rgba([0x9a, 0xa4, 0xae, 0xff]),
+    }
+}
+
+/// The app icon with a dot of `color` in its bottom right corner.
+fn icon(color: Rgba<u8>) -> Icon {
+    let mut image = image::load_from_memory(BASE_ICON)
+        .map(|image| image.to_rgba8())
+        .unwrap_or_else(|_| RgbaImage::new(32, 32));
+    let (width, height) = image.dimensions();
+    let radius = width.min(height) as f32 / 4.0;
+    let (center_x, center_y) = (width as f32 - radius, height as f32 - radius);
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let distance = (x as f32 + 0.5 - center_x).hypot(y as f32 + 0.5 - center_y);
+        if distance <= radius - 1.0 {
+            *pixel = color;
+        } else if distance <= radius {
+            // A white ring keeps the dot visible against the icon and dark panels alike.
+            *pixel = Rgba([0xff, 0xff, 0xff, 0xff]);
+        }
+    }
+    Icon::Rgba {
+        width,
+        height,
+        rgba: image.into_raw(),
+    }
+}
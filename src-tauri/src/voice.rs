@@ -0,0 +1,298 @@
+//! Voice clips recorded in the chat window.
+//!
+//! The webview records raw samples and hands them over as 16-bit PCM, which is resampled to
+//! 48 kHz and encoded here as Ogg Opus, the format the webview plays back. Clips are stored
+//! next to the history under the hex SHA-256 of the encoded file, and the webview loads them
+//! through the `voice` protocol registered in `main`.
+
+use std::{fs, path::PathBuf};
+
+use audiopus::{coder::Encoder, Application, Bitrate, Channels, SampleRate};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use emiessiene_models::{AppError, VoiceClip};
+use ogg::{PacketWriteEndInfo, PacketWriter};
+use sha2::{Digest, Sha256};
+
+use super::emoticons::is_hash;
+
+/// Longest clip that can be recorded, in seconds.
+pub const MAX_CLIP_SECONDS: u32 = 60;
+/// Opus always decodes at 48 kHz, so clips are encoded at that rate.
+const OPUS_RATE: u32 = 48_000;
+/// 20 ms frames, the usual choice for speech.
+const FRAME_SAMPLES: usize = 960;
+const BITRATE: i32 = 24_000;
+/// Largest encoded clip accepted from a friend, in bytes, well above a minute at `BITRATE`.
+const MAX_CLIP_BYTES: usize = 1024 * 1024;
+/// Number of bars in a clip's waveform.
+const WAVEFORM_BARS: usize = 40;
+/// Clips shorter than this are taken to be an accidental click on the record button.
+const MIN_CLIP_MS: u32 = 300;
+
+pub struct ClipStore {
+    dir: PathBuf,
+}
+
+impl ClipStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Encodes a recording made in the webview and stores it.
+    pub fn record(&self, pcm: &str, sample_rate: u32) -> Result<VoiceClip, AppError> {
+        if !(8_000..=192_000).contains(&sample_rate) {
+            return Err(AppError::Validation(format!(
+                "{} Hz is not a sample rate voice clips can be recorded at",
+                sample_rate
+            )));
+        }
+        let bytes = STANDARD
+            .decode(pcm)
+            .map_err(|e| AppError::Validation(format!("Invalid recording: {}", e)))?;
+        let samples: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let samples = resample(&samples, sample_rate);
+        let duration_ms = (samples.len() as u64 * 1000 / u64::from(OPUS_RATE)) as u32;
+        if duration_ms < MIN_CLIP_MS {
+            return Err(AppError::Validation(
+                "Hold 📞 while you speak to record a voice clip".to_string(),
+            ));
+        }
+        if duration_ms > MAX_CLIP_SECONDS * 1000 {
+            return Err(AppError::Validation(format!(
+                "Voice clips can be at most {} seconds long",
+                MAX_CLIP_SECONDS
+            )));
+        }
+        let encoded = encode(&samples)?;
+        Ok(VoiceClip {
+            hash: self.store(&encoded)?,
+            duration_ms,
+            waveform: waveform(&samples),
+        })
+    }
+
+    /// Stores a clip received from a friend, checking it matches the hash it was sent with.
+    pub fn store_received(&self, clip: &VoiceClip, data: &str) -> Result<VoiceClip, AppError> {
+        let bytes = STANDARD
+            .decode(data)
+            .map_err(|e| AppError::Validation(format!("Invalid voice clip data: {}", e)))?;
+        if bytes.len() > MAX_CLIP_BYTES || !bytes.starts_with(b"OggS") {
+            return Err(AppError::Validation(
+                "Voice clip is not an Ogg file of a reasonable size".to_string(),
+            ));
+        }
+        if format!("{:x}", Sha256::digest(&bytes)) != clip.hash {
+            return Err(AppError::Validation(format!(
+                "Voice clip doesn't match its hash {}",
+                clip.hash
+            )));
+        }
+        let hash = self.store(&bytes)?;
+        // The rest is only used for display, so it is kept within bounds rather than rejected.
+        Ok(VoiceClip {
+            hash,
+            duration_ms: clip.duration_ms.min(MAX_CLIP_SECONDS * 1000),
+            waveform: clip
+                .waveform
+                .iter()
+                .take(WAVEFORM_BARS)
+                .map(|bar| (*bar).min(100))
+                .collect(),
+        })
+    }
+
+    pub fn read(&self, hash: &str) -> Result<Vec<u8>, AppError> {
+        if !is_hash(hash) {
+            return Err(AppError::Validation(format!(
+                "{} is not a voice clip",
+                hash
+            )));
+        }
+        let path = self.dir.join(hash);
+        if !path.is_file() {
+            return Err(AppError::NotFound(format!("No voice clip {}", hash)));
+        }
+        Ok(fs::read(path)?)
+    }
+
+    pub fn clip_for_transfer(&self, hash: &str) -> Result<String, AppError> {
+        Ok(STANDARD.encode(self.read(hash)?))
+    }
+
+    fn store(&self, bytes: &[u8]) -> Result<String, AppError> {
+        let hash = format!("{:x}", Sha256::digest(bytes));
+        fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(&hash);
+        if !path.exists() {
+            fs::write(path, bytes)?;
+        }
+        Ok(hash)
+    }
+}
+
+/// Converts samples to `OPUS_RATE` by linear interpolation, which is plenty for speech.
+fn resample(samples: &[i16], sample_rate: u32) -> Vec<i16> {
+    if sample_rate == OPUS_RATE || samples.is_empty() {
+        return samples.to_vec();
+    }
+    let step = f64::from(sample_rate) / f64::from(OPUS_RATE);
+    let length = (samples.len() as f64 / step) as usize;
+    (0..length)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let next = samples.get(index + 1).unwrap_or(&samples[index]);
+            let fraction = position - index as f64;
+            (f64::from(samples[index]) * (1.0 - fraction) + f64::from(*next) * fraction) as i16
+        })
+        .collect()
+}
+
+/// Encodes 48 kHz mono samples as an Ogg Opus file, as laid out in RFC 7845.
+fn encode(samples: &[i16]) -> Result<Vec<u8>, AppError> {
+    let opus_error = |e: audiopus::Error| AppError::Storage(format!("Couldn't encode clip: {}", e));
+    let ogg_error = |e: std::io::Error| AppError::Storage(format!("Couldn't write clip: {}", e));
+    let mut encoder =
+        Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Voip).map_err(opus_error)?;
+    encoder
+        .set_bitrate(Bitrate::BitsPerSecond(BITRATE))
+        .map_err(opus_error)?;
+    let pre_skip = encoder.lookahead().map_err(opus_error)?;
+
+    let serial = 1;
+    let mut writer = PacketWriter::new(Vec::new());
+    let mut head = b"OpusHead".to_vec();
+    head.push(1); // version
+    head.push(1); // channels
+    head.extend_from_slice(&(pre_skip as u16).to_le_bytes());
+    head.extend_from_slice(&OPUS_RATE.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // output gain
+    head.push(0); // channel mapping family
+    writer
+        .write_packet(head.into(), serial, PacketWriteEndInfo::EndPage, 0)
+        .map_err(ogg_error)?;
+    let vendor = env!("CARGO_PKG_NAME").as_bytes();
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&0u32.to_le_bytes()); // no user comments
+    writer
+        .write_packet(tags.into(), serial, PacketWriteEndInfo::EndPage, 0)
+        .map_err(ogg_error)?;
+
+    let frames = samples.len().div_ceil(FRAME_SAMPLES);
+    let mut output = [0u8; 4000];
+    for (i, frame) in samples.chunks(FRAME_SAMPLES).enumerate() {
+        // The last frame is padded with silence, which the final granule position trims off.
+        let mut input = [0i16; FRAME_SAMPLES];
+        input[..frame.len()].copy_from_slice(frame);
+        let length = encoder.encode(&input, &mut output).map_err(opus_error)?;
+        let last = i + 1 == frames;
+        let (end, position) = if last {
+            (PacketWriteEndInfo::EndStream, samples.len())
+        } else {
+            (PacketWriteEndInfo::NormalPacket, (i + 1) * FRAME_SAMPLES)
+        };
+        writer
+            .write_packet(
+                output[..length].to_vec().into(),
+                serial,
+                end,
+                u64::from(pre_skip) + position as u64,
+            )
+            .map_err(ogg_error)?;
+    }
+    Ok(writer.into_inner())
+}
+
+/// The peak level of each of `WAVEFORM_BARS` equal slices of the clip.
+fn waveform(samples: &[i16]) -> Vec<u8> {
+    let slice = samples.len().div_ceil(WAVEFORM_BARS).max(1);
+    samples
+        .chunks(slice)
+        .map(|chunk| {
+            let peak = chunk.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+            (u32::from(peak) * 100 / u32::from(i16::MAX.unsigned_abs())).min(100) as u8
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ogg::PacketReader;
+
+    use super::*;
+
+    #[test]
+    fn resample_keeps_48_khz_and_converts_other_rates() {
+        let samples = [0, 100, 200, 300];
+        assert_eq!(resample(&samples, OPUS_RATE), samples);
+        assert!(resample(&[], 16_000).is_empty());
+        // Every third output sample lands on an input one, the others are in between.
+        assert_eq!(
+            resample(&samples, 16_000),
+            [0, 33, 66, 100, 133, 166, 200, 233, 266, 300, 300, 300]
+        );
+        assert_eq!(resample(&samples, 96_000), [0, 200]);
+    }
+
+    #[test]
+    fn waveform_has_one_peak_per_bar() {
+        assert!(waveform(&[]).is_empty());
+        let mut samples = vec![0i16; WAVEFORM_BARS * 10];
+        samples[5] = i16::MAX;
+        samples[15] = -i16::MAX / 2;
+        samples[25] = i16::MIN;
+        let bars = waveform(&samples);
+        assert_eq!(bars.len(), WAVEFORM_BARS);
+        assert_eq!(bars[..4], [100, 49, 100, 0]);
+        assert_eq!(waveform(&[i16::MAX; 3]), [100, 100, 100]);
+    }
+
+    #[test]
+    fn encode_writes_opus_headers_and_the_final_position() {
+        let samples = vec![0i16; FRAME_SAMPLES * 2 + 100];
+        let encoded = encode(&samples).unwrap();
+        let mut reader = PacketReader::new(Cursor::new(encoded));
+
+        let head = reader.read_packet_expected().unwrap();
+        assert_eq!(&head.data[..8], b"OpusHead");
+        assert_eq!(head.data.len(), 19);
+        assert_eq!(head.data[8..10], [1, 1]);
+        let pre_skip = u16::from_le_bytes([head.data[10], head.data[11]]);
+        assert_eq!(
+            u32::from_le_bytes(head.data[12..16].try_into().unwrap()),
+            OPUS_RATE
+        );
+        assert_eq!(head.data[16..], [0, 0, 0]);
+        assert!(head.last_in_page());
+        assert_eq!(head.absgp_page(), 0);
+
+        let tags = reader.read_packet_expected().unwrap();
+        let vendor = env!("CARGO_PKG_NAME").as_bytes();
+        assert_eq!(&tags.data[..8], b"OpusTags");
+        assert_eq!(
+            u32::from_le_bytes(tags.data[8..12].try_into().unwrap()) as usize,
+            vendor.len()
+        );
+        assert_eq!(&tags.data[12..12 + vendor.len()], vendor);
+        assert_eq!(tags.data[12 + vendor.len()..], [0, 0, 0, 0]);
+
+        let mut audio = Vec::new();
+        while let Some(packet) = reader.read_packet().unwrap() {
+            audio.push(packet);
+        }
+        assert_eq!(audio.len(), 3);
+        let last = audio.last().unwrap();
+        assert!(last.last_in_stream());
+        assert_eq!(
+            last.absgp_page(),
+            u64::from(pre_skip) + samples.len() as u64
+        );
+    }
+}
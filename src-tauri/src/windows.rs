@@ -0,0 +1,292 @@
+//! Conversations opened in windows of their own, and where those windows were left.
+//!
+//! Window labels can't contain the characters emails and group ids are made of, so each chat
+//! window gets a numbered label and this keeps track of which conversation it shows. Positions
+//! and sizes are kept in logical pixels and saved with whether the window was still open when
+//! the app quit, so the same chats come back in the same places.
+
+use std::{collections::HashMap, path::PathBuf, sync::Mutex};
+
+use emiessiene_models::{events, AppError, ChatLayout, ShowMessageEvent};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Window, WindowBuilder, WindowUrl};
+
+use super::{
+    emit_all, lock,
+    storage::{self, Schema},
+    tray, AppState,
+};
+
+const WINDOWS_SCHEMA: Schema = Schema {
+    name: "chat windows",
+    migrations: &[],
+};
+
+const LABEL_PREFIX: &str = "chat-";
+/// Title of the contact list, as set in `tauri.conf.json`.
+const MAIN_TITLE: &str = "EmiEssiEne - alpha version";
+const DEFAULT_WIDTH: f64 = 640.0;
+const DEFAULT_HEIGHT: f64 = 560.0;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct WindowState {
+    conversation: String,
+    x: Option<f64>,
+    y: Option<f64>,
+    width: f64,
+    height: f64,
+    open: bool,
+}
+
+pub struct ChatWindows {
+    path: PathBuf,
+    states: Vec<WindowState>,
+    /// Conversation shown in each open chat window, keyed by the window's label.
+    labels: HashMap<String, String>,
+    next_label: u32,
+    /// Set once the app is quitting, so the windows closing with it stay marked as open.
+    exiting: bool,
+}
+
+impl ChatWindows {
+    pub fn load(path: PathBuf) -> Result<Self, AppError> {
+        let states = if path.exists() {
+            info!("Loading chat windows from {}", path.display());
+            storage::load(&path, &WINDOWS_SCHEMA)?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path,
+            states,
+            labels: HashMap::new(),
+            next_label: 0,
+            exiting: false,
+        })
+    }
+
+    /// The conversation a chat window shows, or `None` for any other window.
+    pub fn conversation(&self, label: &str) -> Option<&str> {
+        self.labels.get(label).map(String::as_str)
+    }
+
+    /// Conversations that have a window open right now.
+    pub fn open_conversations(&self) -> Vec<String> {
+        self.labels.values().cloned().collect()
+    }
+
+    fn label(&self, conversation: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(_, c)| *c == conversation)
+            .map(|(label, _)| label.as_str())
+    }
+
+    /// Conversations whose windows were open when the app last quit.
+    pub fn left_open(&self) -> Vec<String> {
+        self.states
+            .iter()
+            .filter(|s| s.open)
+            .map(|s| s.conversation.clone())
+            .collect()
+    }
+
+    fn state_mut(&mut self, conversation: &str) -> &mut WindowState {
+        let index = match self
+            .states
+            .iter()
+            .position(|s| s.conversation == conversation)
+        {
+            Some(index) => index,
+            None => {
+                self.states.push(WindowState {
+                    conversation: conversation.to_string(),
+                    x: None,
+                    y: None,
+                    width: DEFAULT_WIDTH,
+                    height: DEFAULT_HEIGHT,
+                    open: false,
+                });
+                self.states.len() - 1
+            }
+        };
+        &mut self.states[index]
+    }
+
+    /// Remembers where a chat window is after it was moved or resized.
+    pub fn record_geometry(&mut self, window: &Window) {
+        let Some(conversation) = self.conversation(window.label()).map(str::to_string) else {
+            return;
+        };
+        let scale = window.scale_factor().unwrap_or(1.0);
+        let (Ok(position), Ok(size)) = (window.outer_position(), window.inner_size()) else {
+            return;
+        };
+        let position = position.to_logical::<f64>(scale);
+        let size = size.to_logical::<f64>(scale);
+        let state = self.state_mut(&conversation);
+        state.x = Some(position.x);
+        state.y = Some(position.y);
+        state.width = size.width;
+        state.height = size.height;
+    }
+
+    /// Forgets a chat window the user closed, unless the whole app is quitting.
+    pub fn closed(&mut self, label: &str) -> Result<(), AppError> {
+        let Some(conversation) = self.labels.remove(label) else {
+            return Ok(());
+        };
+        if !self.exiting {
+            self.state_mut(&conversation).open = false;
+        }
+        self.save()
+    }
+
+    /// Saves which chat windows are open, before they are closed by the app quitting.
+    pub fn exiting(&mut self) -> Result<(), AppError> {
+        self.exiting = true;
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), AppError> {
+        storage::save(&self.path, &WINDOWS_SCHEMA, &self.states)
+    }
+}
+
+/// Brings up a conversation the way the user has chosen to see them: its own window, or a tab
+/// in the main window.
+pub fn open_chat(app: &AppHandle, conversation: &str) {
+    let layout = lock(&app.state::<Mutex<AppState>>()).settings.chat_layout;
+    match layout {
+        ChatLayout::Tabs => emit_all(app, events::OPEN_CHAT, conversation),
+        ChatLayout::Windows => {
+            if let Err(e) = open(app, conversation) {
+                warn!("Couldn't open a window for {}: {}", conversation, e);
+            }
+        }
+    }
+}
+
+/// Opens a window for a conversation, or brings it to the front if it already has one.
+pub fn open(app: &AppHandle, conversation: &str) -> Result<(), AppError> {
+    open_at(app, conversation, None)
+}
+
+/// Brings up a conversation scrolled to one of its messages. Chats already on screen are told to
+/// scroll through `events::SHOW_MESSAGE`, and a new chat window finds the message in its URL.
+pub fn show_message(app: &AppHandle, conversation: &str, message_id: &str) -> Result<(), AppError> {
+    let event = ShowMessageEvent {
+        conversation: conversation.to_string(),
+        message_id: message_id.to_string(),
+    };
+    let layout = lock(&app.state::<Mutex<AppState>>()).settings.chat_layout;
+    match layout {
+        ChatLayout::Tabs => tray::show_contact_list(app),
+        ChatLayout::Windows => open_at(app, conversation, Some(message_id))?,
+    }
+    emit_all(app, events::SHOW_MESSAGE, event);
+    Ok(())
+}
+
+fn open_at(app: &AppHandle, conversation: &str, message_id: Option<&str>) -> Result<(), AppError> {
+    let window_error = |e: tauri::Error| AppError::Window(e.to_string());
+    // Windows are built without holding the lock, since building one sends it events.
+    let (label, title, state) = {
+        let state = app.state::<Mutex<AppState>>();
+        let mut app_state = lock(&state);
+        if let Some(window) = app_state
+            .chat_windows
+            .label(conversation)
+            .and_then(|label| app.get_window(label))
+        {
+            drop(app_state);
+            let _ = window.unminimize();
+            window.show().map_err(window_error)?;
+            return window.set_focus().map_err(window_error);
+        }
+        let title = app_state.conversation_title(conversation)?;
+        let windows = &mut app_state.chat_windows;
+        let label = format!("{}{}", LABEL_PREFIX, windows.next_label);
+        windows.next_label += 1;
+        (label, title, windows.state_mut(conversation).clone())
+    };
+    let mut url = format!("chat?conversation={}", encode(conversation));
+    if let Some(message_id) = message_id {
+        url.push_str(&format!("&message={}", encode(message_id)));
+    }
+    let mut builder = WindowBuilder::new(app, label.clone(), WindowUrl::App(url.into()))
+        .title(title)
+        .inner_size(state.width, state.height);
+    if let (Some(x), Some(y)) = (state.x, state.y) {
+        builder = builder.position(x, y);
+    }
+    builder.build().map_err(window_error)?;
+    let state = app.state::<Mutex<AppState>>();
+    let mut app_state = lock(&state);
+    let windows = &mut app_state.chat_windows;
+    windows.labels.insert(label, conversation.to_string());
+    windows.state_mut(conversation).open = true;
+    windows.save()
+}
+
+/// Counts unread messages in the title of the contact list, and in the title of each chat window
+/// for its own conversation.
+pub fn show_unread(app: &AppHandle) {
+    let titles = {
+        let state = app.state::<Mutex<AppState>>();
+        let app_state = lock(&state);
+        let total = app_state.unread.values().sum();
+        let mut titles = vec![("main".to_string(), with_unread(MAIN_TITLE, total))];
+        for (label, conversation) in &app_state.chat_windows.labels {
+            let unread = app_state.unread.get(conversation).copied().unwrap_or(0);
+            match app_state.conversation_title(conversation) {
+                Ok(title) => titles.push((label.clone(), with_unread(&title, unread))),
+                Err(e) => warn!("Couldn't name the window for {}: {}", conversation, e),
+            }
+        }
+        titles
+    };
+    for (label, title) in titles {
+        if let Some(Err(e)) = app
+            .get_window(&label)
+            .map(|window| window.set_title(&title))
+        {
+            warn!("Couldn't retitle window {}: {}", label, e);
+        }
+    }
+}
+
+fn with_unread(title: &str, unread: u32) -> String {
+    match unread {
+        0 => title.to_string(),
+        n => format!("({}) {}", n, title),
+    }
+}
+
+/// Closes a conversation's window, if it has one.
+pub fn close(app: &AppHandle, conversation: &str) -> Result<(), AppError> {
+    let label = lock(&app.state::<Mutex<AppState>>())
+        .chat_windows
+        .label(conversation)
+        .map(str::to_string);
+    if let Some(window) = label.and_then(|label| app.get_window(&label)) {
+        window
+            .close()
+            .map_err(|e| AppError::Window(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Percent-encodes a conversation id for the chat window's URL.
+fn encode(conversation: &str) -> String {
+    conversation
+        .bytes()
+        .map(|b| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
@@ -1,6 +1,22 @@
+pub mod avatar_component;
 pub mod chat_component;
+pub mod chat_window_component;
+pub mod contact_editor_component;
+pub mod contacts_transfer_component;
+pub mod emoticon_manager_component;
+pub mod emoticon_picker_component;
+pub mod file_transfer_component;
 pub mod friend_component;
+pub mod image_viewer_component;
+pub mod invite_picker_component;
 pub mod loginpage_component;
+pub mod logs_transfer_component;
 pub mod mainpage_component;
 pub mod message_component;
+pub mod message_options_component;
 pub mod models;
+pub mod search_panel_component;
+pub mod sound_options_component;
+pub mod text_style_options_component;
+pub mod unread_badge_component;
+pub mod voice_clip_component;
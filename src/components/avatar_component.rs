@@ -0,0 +1,28 @@
+use crate::app::convert_file_src;
+use leptos::*;
+
+/// URL of a display picture, served by the backend's `avatar` protocol.
+pub fn avatar_src(hash: &str) -> String {
+    convert_file_src(hash, "avatar")
+}
+
+/// A display picture filling the slot it is put in, or the first letter of `name` for someone
+/// who hasn't set one.
+#[component]
+pub fn Avatar(
+    #[prop(into)] picture: Signal<Option<String>>,
+    #[prop(into)] name: Signal<String>,
+) -> impl IntoView {
+    move || match picture.get() {
+        Some(hash) => view! {
+            <img class="avatar_picture" src=avatar_src(&hash) alt=move || name.get() />
+        }
+        .into_view(),
+        None => view! {
+            <span class="avatar_initial">
+                {move || name.get().chars().next().map(|c| c.to_uppercase().to_string())}
+            </span>
+        }
+        .into_view(),
+    }
+}
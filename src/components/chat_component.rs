@@ -2,30 +2,489 @@ use leptos::*;
 use leptos::ev::SubmitEvent;
 use leptos::web_sys;
 use leptos::ev::KeyboardEvent;
+use leptos::web_sys::ClipboardEvent;
 use wasm_bindgen::JsCast;
+use serde_wasm_bindgen::to_value;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use js_sys::Uint8Array;
+use wasm_bindgen_futures::JsFuture;
+use std::collections::HashMap;
 
 
 use models::User;
 use models::Friend;
+use models::{events, is_group, ChatMessage, ChatMessageEvent, DialogFilter, DialogOptions, FileTransfer, Group, ImageSource, MessageKind, TypingEvent};
+use crate::app::open_dialog;
+use crate::components::avatar_component::Avatar;
+use crate::components::file_transfer_component::FileTransferCard;
+use crate::components::invite_picker_component::InvitePicker;
 use crate::components::message_component::Message;
+use crate::components::message_options_component::MessageOptions;
+use crate::components::sound_options_component::SoundOptions;
+use crate::components::emoticon_picker_component::EmoticonPicker;
+use crate::components::text_style_options_component::TextStyleOptions;
+use crate::ipc;
+use crate::recorder::Recorder;
 
 use super::models;
 
+/// Files with these extensions are sent as pictures when picked or dropped onto the chat.
+const IMAGE_EXTENSIONS: [&str; 4] = ["png", "gif", "jpg", "jpeg"];
+
+/// Colours telling apart the authors in a group conversation, in the order they joined.
+const AUTHOR_COLORS: [&str; 6] = ["#1f5f99", "#a33b20", "#2d7d32", "#7b3fa0", "#b5651d", "#00796b"];
+
+fn find_friend(friends: &(Vec<Friend>, Vec<Friend>), email: &str) -> Option<Friend> {
+    friends.0.iter().chain(friends.1.iter()).find(|f| f.email == email).cloned()
+}
+
+/// Id of the element around a message, for search results to scroll to.
+fn message_anchor(id: &str) -> String {
+    format!("message-{}", id)
+}
+
+/// Names everyone in a group but the user, as the user knows them.
+pub fn group_name(group: &Group, me: &str, friends: &(Vec<Friend>, Vec<Friend>)) -> String {
+    group
+        .others(me)
+        .map(|p| find_friend(friends, &p.email).map(|f| f.display_name()).unwrap_or_else(|| p.name.clone()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[component]
 pub fn Chat(
-    show: WriteSignal<bool>,
+    /// Goes back to the contact list, for a chat shown as a tab in the main window.
+    #[prop(optional)]
+    show: Option<WriteSignal<bool>>,
     user: ReadSignal<User>,
     friends: ReadSignal<(Vec<Friend>, Vec<Friend>)>,
-    friend: ReadSignal<usize>,
-    close: impl Fn(usize) + 'static,
+    groups: ReadSignal<Vec<Group>>,
+    /// The friend's email, or the id of a group conversation.
+    friend: ReadSignal<String>,
+    close: impl Fn(String) + 'static,
+    /// A message to scroll to and highlight once the conversation is loaded, picked from search
+    /// results.
+    #[prop(optional, into)]
+    scroll_to: Option<Signal<Option<String>>>,
 ) -> impl IntoView {
+    let close = store_value(close);
     let (msg, set_msg) = create_signal(String::new());
-    let (message_list, set_message_list) = create_signal(Vec::new());
+    let (message_list, set_message_list) = create_signal(Vec::<ChatMessage>::new());
+    let (error, set_error) = create_signal(None::<String>);
+    let (show_sound_options, set_show_sound_options) = create_signal(false);
+    let (show_emoticons, set_show_emoticons) = create_signal(false);
+    let (show_text_style, set_show_text_style) = create_signal(false);
+    let (friend_typing, set_friend_typing) = create_signal(false);
+    let (transfers, set_transfers) = create_signal(HashMap::<String, FileTransfer>::new());
+    let (show_invite, set_show_invite) = create_signal(false);
+    let (found, set_found) = create_signal(None::<String>);
+    let (show_message_options, set_show_message_options) = create_signal(false);
+    // The message being edited in the input box instead of a new one being written.
+    let (editing, set_editing) = create_signal(None::<String>);
+    let (edit_window, set_edit_window) = create_signal(None::<u32>);
+
+    let load_edit_window = move || {
+        spawn_local(async move {
+            match ipc::get_settings().await {
+                Ok(settings) => set_edit_window.set(settings.edit_window_minutes),
+                Err(e) => logging::warn!("Couldn't load your settings: {}", e),
+            }
+        });
+    };
+    load_edit_window();
+
+    let current_friend = move || {
+        friends.with(|(online, offline)| {
+            online
+                .iter()
+                .chain(offline.iter())
+                .find(|f| f.email == friend.get())
+                .cloned()
+        })
+    };
+
+    let current_group = move || {
+        groups.with(|groups| groups.iter().find(|g| g.id == friend.get()).cloned())
+    };
+
+    let conversation_name = move || match current_group() {
+        Some(group) => friends.with(|friends| group_name(&group, &user.get().email, friends)),
+        None => current_friend().map(|f| f.display_name()).unwrap_or_else(|| friend.get()),
+    };
+
+    let add_message = move |message: ChatMessage| {
+        set_message_list.update(|msg_list| {
+            if !msg_list.iter().any(|m| m.id == message.id) {
+                msg_list.push(message);
+            }
+        });
+    };
+
+    let replace_message = move |message: ChatMessage| {
+        set_message_list.update(|msg_list| {
+            if let Some(existing) = msg_list.iter_mut().find(|m| m.id == message.id) {
+                *existing = message;
+            }
+        });
+    };
+
+    create_effect(move |_| {
+        let email = friend.get();
+        set_message_list.set(Vec::new());
+        set_editing.set(None);
+        set_friend_typing.set(false);
+        set_transfers.set(HashMap::new());
+        spawn_local(async move {
+            match ipc::get_conversation(&email).await {
+                Ok(history) => set_message_list.set(history),
+                Err(e) => set_error.set(Some(format!("Couldn't load your conversation: {}", e))),
+            }
+            match ipc::list_file_transfers(&email).await {
+                Ok(list) => set_transfers.set(list.into_iter().map(|t| (t.id.clone(), t)).collect()),
+                Err(e) => set_error.set(Some(format!("Couldn't load your file transfers: {}", e))),
+            }
+        });
+    });
+
+    // Runs again as the history loads, until the message is there to scroll to.
+    create_effect(move |_| {
+        let Some(id) = scroll_to.and_then(|scroll_to| scroll_to.get()) else { return };
+        if found.get_untracked().as_ref() == Some(&id) || !message_list.with(|list| list.iter().any(|m| m.id == id)) {
+            return;
+        }
+        set_found.set(Some(id.clone()));
+        request_animation_frame(move || {
+            if let Some(element) = document().get_element_by_id(&message_anchor(&id)) {
+                element.scroll_into_view();
+            }
+        });
+    });
+
+    ipc::listen(events::CHAT_MESSAGE, move |event: ChatMessageEvent| {
+        if event.conversation == friend.get_untracked() {
+            add_message(event.message);
+        }
+    });
+
+    ipc::listen(events::FILE_TRANSFER, move |transfer: FileTransfer| {
+        if transfer.conversation == friend.get_untracked() {
+            set_transfers.update(|transfers| {
+                transfers.insert(transfer.id.clone(), transfer);
+            });
+        }
+    });
+
+    ipc::listen(events::TYPING, move |event: TypingEvent| {
+        if event.conversation == friend.get_untracked() {
+            set_friend_typing.set(event.typing);
+        }
+    });
+
+    let author_name = move |from: &str| {
+        if from == user.get().email {
+            return user.get().name;
+        }
+        if let Some(f) = friends.with(|friends| find_friend(friends, from)) {
+            return f.display_name();
+        }
+        current_group()
+            .and_then(|group| group.participant(from).map(|p| p.name.clone()))
+            .unwrap_or_else(|| from.to_string())
+    };
+
+    let author_color = move |from: &str| {
+        current_group()
+            .and_then(|group| group.participants.iter().position(|p| p.email == from))
+            .map(|i| AUTHOR_COLORS[i % AUTHOR_COLORS.len()])
+    };
+
+    let system_line = move |m: &ChatMessage| match m.kind {
+        MessageKind::Joined => format!("{} has joined the conversation.", author_name(&m.from)),
+        MessageKind::Left => format!("{} has left the conversation.", author_name(&m.from)),
+        _ if m.from == user.get().email => "You have just sent a nudge!".to_string(),
+        _ => format!("{} has just sent you a nudge!", author_name(&m.from)),
+    };
+
+    let invite_candidates = Signal::derive(move || {
+        let taken: Vec<String> = match current_group() {
+            Some(group) => group.participants.iter().map(|p| p.email.clone()).collect(),
+            None => vec![friend.get()],
+        };
+        friends.with(|(online, _)| {
+            online
+                .iter()
+                .filter(|f| f.address.is_some() && !taken.contains(&f.email))
+                .cloned()
+                .collect()
+        })
+    });
+
+    let invite = move |email: String| {
+        spawn_local(async move {
+            // Inviting someone into a chat with one friend opens a new group chat for it.
+            match ipc::invite_to_conversation(&friend.get_untracked(), &email).await {
+                Ok(_) => set_error.set(None),
+                Err(e) => set_error.set(Some(format!("Couldn't invite them: {}", e))),
+            }
+        });
+    };
+
+    let leave = move |_| {
+        spawn_local(async move {
+            let conversation = friend.get_untracked();
+            match ipc::leave_conversation(&conversation).await {
+                Ok(()) => close.with_value(|close| close(conversation)),
+                Err(e) => set_error.set(Some(format!("Couldn't leave the conversation: {}", e))),
+            }
+        });
+    };
+
+    let send_nudge = move |_| {
+        spawn_local(async move {
+            match ipc::send_nudge(&friend.get_untracked()).await {
+                Ok(message) => {
+                    add_message(message);
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(format!("Your nudge couldn't be sent: {}", e))),
+            }
+        });
+    };
+
+    let send_file = move |_| {
+        spawn_local(async move {
+            let options = DialogOptions {
+                title: "Send a file",
+                filters: Vec::new(),
+                directory: false,
+            };
+            let Some(path) = open_dialog(to_value(&options).unwrap()).await.as_string() else {
+                return;
+            };
+            match ipc::send_file(&friend.get_untracked(), &path).await {
+                Ok(message) => {
+                    add_message(message);
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(format!("Your file couldn't be offered: {}", e))),
+            }
+        });
+    };
+
+    let recorder = store_value(None::<Recorder>);
+    let (recording, set_recording) = create_signal(false);
+
+    // 📞 records for as long as it is held down, like a walkie-talkie.
+    let start_recording = move |_| {
+        if recording.get_untracked() {
+            return;
+        }
+        set_recording.set(true);
+        spawn_local(async move {
+            match Recorder::start().await {
+                // Released before the microphone was ready.
+                Ok(started) if !recording.get_untracked() => {
+                    started.stop();
+                }
+                Ok(started) => recorder.set_value(Some(started)),
+                Err(e) => {
+                    set_recording.set(false);
+                    set_error.set(Some(format!("Couldn't record a voice clip: {}", e)));
+                }
+            }
+        });
+    };
+
+    let stop_recording = move |_| {
+        if !recording.get_untracked() {
+            return;
+        }
+        set_recording.set(false);
+        let Some(started) = recorder.try_update_value(|r| r.take()).flatten() else {
+            return;
+        };
+        let clip = started.stop();
+        spawn_local(async move {
+            match ipc::send_voice_clip(&friend.get_untracked(), &clip.samples, clip.sample_rate).await {
+                Ok(message) => {
+                    add_message(message);
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(format!("Your voice clip couldn't be sent: {}", e))),
+            }
+        });
+    };
+
+    let send_picture = move |source: ImageSource| {
+        spawn_local(async move {
+            match ipc::send_image(&friend.get_untracked(), source).await {
+                Ok(message) => {
+                    add_message(message);
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(format!("Your picture couldn't be sent: {}", e))),
+            }
+        });
+    };
+
+    let choose_picture = move |_| {
+        spawn_local(async move {
+            let options = DialogOptions {
+                title: "Send a picture",
+                filters: vec![DialogFilter {
+                    name: "Pictures",
+                    extensions: &IMAGE_EXTENSIONS,
+                }],
+                directory: false,
+            };
+            if let Some(path) = open_dialog(to_value(&options).unwrap()).await.as_string() {
+                send_picture(ImageSource::Path(path));
+            }
+        });
+    };
+
+    let paste_picture = move |ev: leptos::ev::Event| {
+        let files = ev
+            .dyn_ref::<ClipboardEvent>()
+            .and_then(|ev| ev.clipboard_data())
+            .and_then(|data| data.files());
+        let Some(files) = files else {
+            return;
+        };
+        let pictures: Vec<web_sys::File> = (0..files.length())
+            .filter_map(|i| files.get(i))
+            .filter(|file| file.type_().starts_with("image/"))
+            .collect();
+        if pictures.is_empty() {
+            return;
+        }
+        ev.prevent_default();
+        for picture in pictures {
+            spawn_local(async move {
+                match JsFuture::from(picture.array_buffer()).await {
+                    Ok(buffer) => {
+                        let bytes = Uint8Array::new(&buffer).to_vec();
+                        send_picture(ImageSource::Data(STANDARD.encode(bytes)));
+                    }
+                    Err(e) => logging::warn!("Couldn't read the pasted picture: {:?}", e),
+                }
+            });
+        }
+    };
+
+    // Pictures dropped on the window are shown in the conversation, anything else is offered
+    // as a file.
+    ipc::listen(ipc::FILE_DROP, move |paths: Vec<String>| {
+        for path in paths {
+            let extension = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+            if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+                send_picture(ImageSource::Path(path));
+                continue;
+            }
+            spawn_local(async move {
+                match ipc::send_file(&friend.get_untracked(), &path).await {
+                    Ok(message) => {
+                        add_message(message);
+                        set_error.set(None);
+                    }
+                    Err(e) => set_error.set(Some(format!("Your file couldn't be offered: {}", e))),
+                }
+            });
+        }
+    });
+
+    let insert_emoticon = move |shortcut: &str| {
+        if let Some(input) = document().get_element_by_id("message-input") {
+            if let Some(input_element) = input.dyn_ref::<web_sys::HtmlTextAreaElement>() {
+                let mut value = input_element.value();
+                if !value.is_empty() && !value.ends_with(' ') {
+                    value.push(' ');
+                }
+                value.push_str(shortcut);
+                input_element.set_value(&value);
+                let _ = input_element.focus();
+                set_msg.set(value);
+            }
+        }
+    };
+
     let update_msg = move |ev| {
         let m = event_target_value(&ev);
+        if !m.trim().is_empty() {
+            spawn_local(async move {
+                // The backend throttles these, so every keystroke can report it.
+                if let Err(e) = ipc::send_typing(&friend.get_untracked()).await {
+                    logging::warn!("Couldn't send typing notification: {}", e);
+                }
+            });
+        }
         set_msg.set(m);
     };
 
+    let set_input = move |text: String| {
+        if let Some(input) = document().get_element_by_id("message-input") {
+            if let Some(input_element) = input.dyn_ref::<web_sys::HtmlTextAreaElement>() {
+                input_element.set_value(&text);
+                let _ = input_element.focus();
+            }
+        }
+        set_msg.set(text);
+    };
+
+    let start_editing = move |message: &ChatMessage| {
+        set_editing.set(Some(message.id.clone()));
+        set_input(message.body.clone());
+    };
+
+    let stop_editing = move || {
+        set_editing.set(None);
+        set_input(String::new());
+    };
+
+    let delete_message = move |id: String| {
+        let confirmed = window()
+            .confirm_with_message("Delete this message for everyone in the conversation?")
+            .unwrap_or(false);
+        if !confirmed {
+            return;
+        }
+        spawn_local(async move {
+            match ipc::delete_message(&friend.get_untracked(), &id).await {
+                Ok(message) => {
+                    if editing.get_untracked().as_ref() == Some(&message.id) {
+                        stop_editing();
+                    }
+                    replace_message(message);
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(format!("Your message couldn't be deleted: {}", e))),
+            }
+        });
+    };
+
+    ipc::listen(events::MESSAGE_CHANGED, move |event: ChatMessageEvent| {
+        if event.conversation == friend.get_untracked() {
+            if event.message.deleted && editing.get_untracked().as_ref() == Some(&event.message.id) {
+                stop_editing();
+            }
+            replace_message(event.message);
+        }
+    });
+
+    // Only the user's own messages can be changed, and only for as long as they chose.
+    let changeable = move |m: &ChatMessage| {
+        m.check_editable(&user.get_untracked().email, js_sys::Date::now() as u64, edit_window.get())
+            .is_ok()
+    };
+
+    let cancel_on_escape = move |ev: KeyboardEvent| {
+        if ev.key() == "Escape" && editing.get_untracked().is_some() {
+            ev.prevent_default();
+            stop_editing();
+        }
+    };
+
     let submit_on_enter = move |ev: KeyboardEvent| {
         spawn_local(async move {
             if ev.key() == "Enter" && !ev.shift_key() {
@@ -46,7 +505,27 @@ pub fn Chat(
             if msg.trim().is_empty() {
                 return;
             }
-            set_message_list.update(|msg_list| msg_list.push(msg.clone()));
+            if let Some(id) = editing.get_untracked() {
+                match ipc::edit_message(&friend.get_untracked(), &id, &msg).await {
+                    Ok(message) => {
+                        replace_message(message);
+                        set_error.set(None);
+                        stop_editing();
+                    }
+                    Err(e) => set_error.set(Some(format!("Your message couldn't be edited: {}", e))),
+                }
+                return;
+            }
+            match ipc::send_message(&friend.get_untracked(), &msg).await {
+                Ok(message) => {
+                    add_message(message);
+                    set_error.set(None);
+                }
+                Err(e) => {
+                    set_error.set(Some(format!("Your message couldn't be delivered: {}", e)));
+                    return;
+                }
+            }
             set_msg.set(String::new());
             if let Some(input) = document().get_element_by_id("message-input") {
                 if let Some(input_element) = input.dyn_ref::<web_sys::HtmlTextAreaElement>() {
@@ -60,36 +539,76 @@ pub fn Chat(
         <main class="container">
             <div class="chat_container">
                 <div class="chat_receiver-bar">
-                    <button
-                        class="back-button"
-                        on:click=move |_| {
-                            show.set(false);
+                    {show.map(|show| view! {
+                        <button
+                            class="back-button"
+                            on:click=move |_| {
+                                show.set(false);
+                            }
+                        >
+                            { "⬅️" }
+                        </button>
+                    })}
+                    <span
+                        class="chat_receiver"
+                        title=move || {
+                            current_friend().and_then(|f| f.alias.as_ref().map(|_| f.name.clone()))
                         }
                     >
-                        { "⬅️" }
-                    </button>
-                    <span class="chat_receiver">{ "👤" } {&friends.get().0[friend.get()].name}</span>
+                        {move || if is_group(&friend.get()) { "👥" } else { "👤" }}
+                        {conversation_name}
+                    </span>
                     <span class="chat_receiver-status-message">
-                        {&friends.get().0[friend.get()].status}
-                        <span class="ml-1">{format!("<{}>", &friends.get().0[friend.get()].email)}</span>
+                        {move || current_friend().map(|f| f.status)}
+                        {move || (!is_group(&friend.get())).then(|| view! {
+                            <span class="ml-1">{format!("<{}>", friend.get())}</span>
+                        })}
                     </span>
                     <button
                         class="close-button"
                         on:click=move |_| {
-                            close(friend.get());
+                            close.with_value(|close| close(friend.get_untracked()));
                         }
                     >
                         { "❌" }
                     </button>
                 </div>
                 <div class="chat_top-bar chat_icon-bar main_bordered">
-                    <div class="chat_config-btn">{"⚙️"}</div>
-                    <div class="chat_invite-btn">{"👤"}</div>
-                    <div class="chat_files-btn">{"📁"}</div>
+                    <div
+                        class="chat_config-btn"
+                        title="Message options"
+                        on:click=move |_| set_show_message_options.set(true)
+                    >
+                        {"⚙️"}
+                    </div>
+                    <div
+                        class="chat_invite-btn"
+                        title="Invite someone to this conversation"
+                        on:click=move |_| set_show_invite.update(|shown| *shown = !*shown)
+                    >
+                        {"👤"}
+                    </div>
+                    <div class="chat_files-btn" title="Send a file" on:click=send_file>{"📁"}</div>
                     <div class="chat_webcam-btn">{"📷"}</div>
-                    <div class="chat_voice-btn">{"📞"}</div>
+                    <div
+                        class="chat_voice-btn"
+                        class:recording=move || recording.get()
+                        title="Hold to record a voice clip"
+                        on:mousedown=start_recording
+                        on:mouseup=stop_recording
+                        on:mouseleave=stop_recording
+                    >
+                        {"📞"}
+                    </div>
                     <div class="chat_block-btn">{"🚫"}</div>
                 </div>
+                <Show when=move || show_invite.get()>
+                    <InvitePicker
+                        candidates=invite_candidates
+                        on_pick=invite
+                        on_close=move || set_show_invite.set(false)
+                    />
+                </Show>
                 <div class="chat-and-avatars flex-row">
                     <div class="left-column">
                         <div class="chat_window">
@@ -98,22 +617,113 @@ pub fn Chat(
                                     message_list
                                         .get()
                                         .iter()
-                                        .map(|m| {
-                                            view! {
-                                                <Message user=user content=create_signal(m.clone()).0 />
+                                        .map(|m| match m.kind {
+                                            MessageKind::Nudge | MessageKind::Joined | MessageKind::Left if !m.deleted => view! {
+                                                <div class="message_system">{system_line(m)}</div>
+                                            }.into_view(),
+                                            MessageKind::File => {
+                                                let id = m.id.clone();
+                                                view! {
+                                                    <FileTransferCard
+                                                        file=m.file.clone().unwrap_or_default()
+                                                        transfer=Signal::derive(move || transfers.with(|t| t.get(&id).cloned()))
+                                                        friend_name=Signal::derive(move || author_name(&friend.get()))
+                                                        incoming=m.from != user.get_untracked().email
+                                                    />
+                                                }.into_view()
+                                            }
+                                            _ => {
+                                                let id = m.id.clone();
+                                                let edit = m.clone();
+                                                let delete = m.id.clone();
+                                                view! {
+                                                    <div
+                                                        id=message_anchor(&m.id)
+                                                        class="message_anchor"
+                                                        class:message_found=move || found.get().as_ref() == Some(&id)
+                                                        class:message_being-edited=editing.get().as_ref() == Some(&m.id)
+                                                    >
+                                                        {changeable(m).then(|| view! {
+                                                            <span class="message_actions">
+                                                                {(m.kind == MessageKind::Text).then(|| view! {
+                                                                    <a title="Edit" on:click=move |_| start_editing(&edit)>"✏️"</a>
+                                                                })}
+                                                                <a title="Delete" on:click=move |_| delete_message(delete.clone())>"🗑️"</a>
+                                                            </span>
+                                                        })}
+                                                        <Message
+                                                            author=create_signal(author_name(&m.from)).0
+                                                            author_color=author_color(&m.from)
+                                                            content=create_signal(m.body.clone()).0
+                                                            emoticons=create_signal(m.emoticons.clone()).0
+                                                            style=create_signal(m.style.clone()).0
+                                                            image=create_signal(m.image.clone()).0
+                                                            voice=create_signal(m.voice.clone()).0
+                                                            edited=m.edited_at.is_some()
+                                                            deleted=m.deleted
+                                                            status=m.status
+                                                        />
+                                                    </div>
+                                                }.into_view()
                                             }
                                         })
                                         .collect::<Vec<_>>()
                                 }}
                             </div>
+                            <Show when=move || recording.get()>
+                                <div class="chat_recording">"Recording… release 📞 to send"</div>
+                            </Show>
+                            <Show when=move || friend_typing.get()>
+                                <div class="chat_typing">
+                                    {move || current_friend().map(|f| f.display_name())}
+                                    " is writing a message..."
+                                </div>
+                            </Show>
+                            {move || error.get().map(|message| view! {
+                                <div class="error-text">{message}</div>
+                            })}
                         </div>
+                        <Show when=move || show_emoticons.get()>
+                            <EmoticonPicker
+                                on_pick=insert_emoticon
+                                on_close=move || set_show_emoticons.set(false)
+                            />
+                        </Show>
                         <div class="chat_mid-bar chat_icon-bar main_bordered">
-                            <div class="chat_font-btn">{"🔤"}</div>
-                            <div class="chat_emote-btn">{"😊"}</div>
-                            <div class="chat_audio-btn">{"📢"}</div>
-                            <div class="chat_image-btn">{"🖼️"}</div>
-                            <div class="chat_nudge-btn">{"😵‍💫"}</div>
+                            <div
+                                class="chat_font-btn"
+                                title="Change font"
+                                on:click=move |_| set_show_text_style.set(true)
+                            >
+                                {"🔤"}
+                            </div>
+                            <div
+                                class="chat_emote-btn"
+                                title="Emoticons"
+                                on:click=move |_| set_show_emoticons.update(|shown| *shown = !*shown)
+                            >
+                                {"😊"}
+                            </div>
+                            <div
+                                class="chat_audio-btn"
+                                title="Sounds"
+                                on:click=move |_| set_show_sound_options.set(true)
+                            >
+                                {"📢"}
+                            </div>
+                            <div class="chat_image-btn" title="Send a picture" on:click=choose_picture>
+                                {"🖼️"}
+                            </div>
+                            <div class="chat_nudge-btn" title="Send a nudge" on:click=send_nudge>
+                                {"😵‍💫"}
+                            </div>
                         </div>
+                        {move || editing.get().map(|_| view! {
+                            <div class="chat_editing">
+                                "Editing your message. "
+                                <a on:click=move |_| stop_editing()>"Cancel"</a>
+                            </div>
+                        })}
                         <div class="chat_message-input">
                             <form id="message-form" class="chat_message-form" on:submit=send_msg>
                                 <textarea
@@ -122,29 +732,95 @@ pub fn Chat(
                                     placeholder="Enter a message..."
                                     on:input=update_msg
                                     on:keypress=submit_on_enter
+                                    on:keydown=cancel_on_escape
+                                    on:paste=paste_picture
                                 ></textarea>
-                                <button type="submit">{ "Send" }</button>
+                                <button type="submit">{move || if editing.get().is_some() { "Save" } else { "Send" }}</button>
                             </form>
                         </div>
                     </div>
                     <div class="right-column flex-col">
-                        <div class="top-user flex-col user-block">
-                            <div class="top-avatar avatar"></div>
-                            <div class="below-avatar flex-row justify-between">
-                                <div class="webcam-icon">{"🎦"}</div>
-                                <div class="options-arrow">{"🔽"}</div>
-                            </div>
-                        </div>
-                        <div class="bottom-user flex-col user-block">
-                            <div class="bottom-avatar avatar"></div>
-                            <div class="below-avatar flex-row justify-between">
-                                <div class="webcam-icon">{"🎦"}</div>
-                                <div class="options-arrow">{"🔽"}</div>
-                            </div>
-                        </div>
+                        {move || match current_group() {
+                            Some(group) => view! {
+                                <div class="participants flex-col user-block">
+                                    <div class="participants_title">"In this conversation"</div>
+                                    {group
+                                        .participants
+                                        .into_iter()
+                                        .map(|p| {
+                                            let email = p.email.clone();
+                                            let name = p.name.clone();
+                                            let picture = Signal::derive(move || {
+                                                if email == user.get().email {
+                                                    user.get().display_picture
+                                                } else {
+                                                    friends.with(|friends| find_friend(friends, &email)).and_then(|f| f.display_picture)
+                                                }
+                                            });
+                                            let label = author_name(&p.email);
+                                            view! {
+                                                <div class="participant flex-row">
+                                                    <div class="participant_avatar">
+                                                        <Avatar picture=picture name=Signal::derive(move || name.clone()) />
+                                                    </div>
+                                                    <span class="participant_name" style=author_color(&p.email).map(|c| format!("color: {}", c))>{label}</span>
+                                                </div>
+                                            }
+                                        })
+                                        .collect_view()}
+                                    <button class="participants_leave" on:click=leave>"Leave conversation"</button>
+                                </div>
+                            }.into_view(),
+                            None => view! {
+                                <>
+                                <div class="top-user flex-col user-block">
+                                    <div class="top-avatar avatar">
+                                        <Avatar
+                                            picture=Signal::derive(move || {
+                                                current_friend().and_then(|f| f.display_picture)
+                                            })
+                                            name=Signal::derive(move || {
+                                                current_friend()
+                                                    .map(|f| f.display_name())
+                                                    .unwrap_or_else(|| friend.get())
+                                            })
+                                        />
+                                    </div>
+                                    <div class="below-avatar flex-row justify-between">
+                                        <div class="webcam-icon">{"🎦"}</div>
+                                        <div class="options-arrow">{"🔽"}</div>
+                                    </div>
+                                </div>
+                                <div class="bottom-user flex-col user-block">
+                                    <div class="bottom-avatar avatar">
+                                        <Avatar
+                                            picture=Signal::derive(move || user.get().display_picture)
+                                            name=Signal::derive(move || user.get().name)
+                                        />
+                                    </div>
+                                    <div class="below-avatar flex-row justify-between">
+                                        <div class="webcam-icon">{"🎦"}</div>
+                                        <div class="options-arrow">{"🔽"}</div>
+                                    </div>
+                                </div>
+                                </>
+                            }.into_view(),
+                        }}
                     </div>
                 </div>
             </div>
+            <Show when=move || show_sound_options.get()>
+                <SoundOptions on_close=move || set_show_sound_options.set(false) />
+            </Show>
+            <Show when=move || show_message_options.get()>
+                <MessageOptions on_close=move || {
+                    set_show_message_options.set(false);
+                    load_edit_window();
+                } />
+            </Show>
+            <Show when=move || show_text_style.get()>
+                <TextStyleOptions on_close=move || set_show_text_style.set(false) />
+            </Show>
         </main>
     }
 }
\ No newline at end of file
@@ -0,0 +1,87 @@
+use crate::components::chat_component::Chat;
+use crate::ipc;
+use leptos::*;
+use leptos_router::use_query_map;
+use std::time::Duration;
+
+use super::models::{events, Availability, Group, ShowMessageEvent, User};
+
+/// How long the window shakes for a nudge, matching the `shake` animation in styles.css.
+const NUDGE_SHAKE_DURATION: Duration = Duration::from_millis(600);
+
+/// A conversation in a window of its own, opened by the backend at `/chat?conversation=<id>`,
+/// with `&message=<id>` added to scroll to a message found by searching.
+#[component]
+pub fn ChatWindow() -> impl IntoView {
+    let query = use_query_map();
+    let (conversation, _) =
+        create_signal(query.with_untracked(|q| q.get("conversation").cloned().unwrap_or_default()));
+    let (scroll_to, set_scroll_to) =
+        create_signal(query.with_untracked(|q| q.get("message").cloned()));
+    let (user, set_user) = create_signal(User {
+        name: String::new(),
+        email: String::new(),
+        status: String::new(),
+        availability: Availability::Online,
+        display_picture: None,
+    });
+    let (friends, set_friends) = create_signal((Vec::new(), Vec::new()));
+    let (groups, set_groups) = create_signal(Vec::<Group>::new());
+    let (shaking, set_shaking) = create_signal(false);
+
+    let load = move || {
+        spawn_local(async move {
+            match ipc::get_user().await {
+                Ok(loaded) => set_user.set(loaded),
+                Err(e) => logging::warn!("Couldn't load your profile: {}", e),
+            }
+            match ipc::get_friends().await {
+                Ok(loaded) => set_friends.set(loaded),
+                Err(e) => logging::warn!("Couldn't load your friends: {}", e),
+            }
+            match ipc::list_groups().await {
+                Ok(loaded) => set_groups.set(loaded),
+                Err(e) => logging::warn!("Couldn't load your group chats: {}", e),
+            }
+        });
+    };
+    load();
+
+    ipc::listen(events::FRIENDS_CHANGED, move |_: ()| load());
+    ipc::listen(events::GROUPS_CHANGED, move |_: ()| load());
+    ipc::listen(events::USER_CHANGED, move |changed: User| {
+        set_user.set(changed)
+    });
+    ipc::listen(events::SHOW_MESSAGE, move |event: ShowMessageEvent| {
+        if event.conversation == conversation.get_untracked() {
+            set_scroll_to.set(Some(event.message_id));
+        }
+    });
+    ipc::listen(events::NUDGE, move |nudged: String| {
+        if nudged == conversation.get_untracked() {
+            set_shaking.set(true);
+            set_timeout(move || set_shaking.set(false), NUDGE_SHAKE_DURATION);
+        }
+    });
+
+    let close = move |conversation: String| {
+        spawn_local(async move {
+            if let Err(e) = ipc::close_chat_window(&conversation).await {
+                logging::warn!("Couldn't close the chat window: {}", e);
+            }
+        });
+    };
+
+    view! {
+        <div id="chat-window" class:shake=shaking>
+            <Chat
+                user=user
+                friends=friends
+                groups=groups
+                friend=conversation
+                close=close
+                scroll_to=scroll_to
+            />
+        </div>
+    }
+}
@@ -0,0 +1,93 @@
+use crate::ipc;
+use leptos::*;
+
+use super::models::{ContactDetails, Friend};
+
+#[component]
+pub fn ContactEditor(
+    friend: Friend,
+    on_close: impl Fn() + Copy + 'static,
+    on_saved: impl Fn(Friend) + Copy + 'static,
+) -> impl IntoView {
+    let (alias, set_alias) = create_signal(friend.alias.clone().unwrap_or_default());
+    let (notes, set_notes) = create_signal(friend.notes.clone());
+    let (address, set_address) = create_signal(friend.address.clone().unwrap_or_default());
+    let (muted, set_muted) = create_signal(friend.muted);
+    let (error, set_error) = create_signal(String::new());
+    let email = friend.email.clone();
+
+    let save = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let email = email.clone();
+        spawn_local(async move {
+            let details = ContactDetails {
+                alias: Some(alias.get_untracked()),
+                notes: notes.get_untracked(),
+                address: Some(address.get_untracked()),
+                muted: muted.get_untracked(),
+            };
+            match ipc::update_contact(&email, details).await {
+                Ok(updated_friend) => {
+                    on_saved(updated_friend);
+                    on_close();
+                }
+                Err(e) => set_error.set(format!("Couldn't save contact: {}", e)),
+            }
+        });
+    };
+
+    view! {
+        <div id="contact-editor" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Edit contact"</span>
+            <span class="mt-1">{friend.name.clone()} " <" {friend.email.clone()} ">"</span>
+            <form class="flex-col" on:submit=save>
+                <label class="mt-1" for="contact-editor_alias">
+                    "Nickname (only you can see it)"
+                </label>
+                <input
+                    type="text"
+                    id="contact-editor_alias"
+                    class="border-1b"
+                    placeholder=friend.name.clone()
+                    prop:value=alias
+                    on:input=move |ev| set_alias.set(event_target_value(&ev))
+                />
+                <label class="mt-1" for="contact-editor_notes">
+                    "Notes"
+                </label>
+                <textarea
+                    id="contact-editor_notes"
+                    class="border-1b"
+                    prop:value=notes
+                    on:input=move |ev| set_notes.set(event_target_value(&ev))
+                ></textarea>
+                <label class="mt-1" for="contact-editor_address">
+                    "Address (where their app listens, e.g. 192.168.1.20:7001)"
+                </label>
+                <input
+                    type="text"
+                    id="contact-editor_address"
+                    class="border-1b"
+                    placeholder="host:port"
+                    prop:value=address
+                    on:input=move |ev| set_address.set(event_target_value(&ev))
+                />
+                <label class="mt-1">
+                    <input
+                        type="checkbox"
+                        prop:checked=muted
+                        on:change=move |ev| set_muted.set(event_target_checked(&ev))
+                    />
+                    " Mute notifications"
+                </label>
+                <div class="flex-row mt-1">
+                    <button type="submit">"Save"</button>
+                    <button type="button" class="ml-05" on:click=move |_| on_close()>
+                        "Cancel"
+                    </button>
+                </div>
+                <span class="error-text">{error}</span>
+            </form>
+        </div>
+    }
+}
@@ -0,0 +1,193 @@
+use crate::app::{open_dialog, save_dialog};
+use crate::ipc;
+use leptos::*;
+use serde_wasm_bindgen::to_value;
+
+use super::models;
+use models::{ContactFormat, DialogFilter, DialogOptions, Friend, ImportPreview};
+
+fn dialog_options(title: &str, format: ContactFormat) -> DialogOptions<'_> {
+    let (name, extensions): (&str, &[&str]) = match format {
+        ContactFormat::Json => ("JSON", &["json"]),
+        ContactFormat::VCard => ("vCard", &["vcf", "vcard"]),
+        ContactFormat::Csv => ("CSV", &["csv"]),
+    };
+    DialogOptions {
+        title,
+        filters: vec![DialogFilter { name, extensions }],
+        directory: false,
+    }
+}
+
+fn contact_line(friend: &Friend) -> String {
+    format!("{} <{}>", friend.display_name(), friend.email)
+}
+
+#[component]
+pub fn ContactsTransfer(
+    on_close: impl Fn() + Copy + 'static,
+    on_imported: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    let (format, set_format) = create_signal(ContactFormat::VCard);
+    let (import_path, set_import_path) = create_signal(None::<String>);
+    let (preview, set_preview) = create_signal(None::<ImportPreview>);
+    let (result, set_result) = create_signal(String::new());
+
+    let update_format = move |ev| {
+        set_format.set(match event_target_value(&ev).as_str() {
+            "Json" => ContactFormat::Json,
+            "Csv" => ContactFormat::Csv,
+            _ => ContactFormat::VCard,
+        });
+    };
+
+    let pick_import_file = move |_| {
+        spawn_local(async move {
+            let format = format.get_untracked();
+            let options = dialog_options("Import contacts", format);
+            let Some(path) = open_dialog(to_value(&options).unwrap()).await.as_string() else {
+                return;
+            };
+            match ipc::import_contacts(&path, format, true).await {
+                Ok(import_preview) => {
+                    set_import_path.set(Some(path));
+                    set_preview.set(Some(import_preview));
+                }
+                Err(e) => set_result.set(format!("Couldn't read {}: {}", path, e)),
+            }
+        });
+    };
+
+    let confirm_import = move |_| {
+        spawn_local(async move {
+            let Some(path) = import_path.get_untracked() else {
+                return;
+            };
+            match ipc::import_contacts(&path, format.get_untracked(), false).await {
+                Ok(import_preview) => {
+                    set_result.set(format!(
+                        "Imported {} new and {} updated contacts",
+                        import_preview.new.len(),
+                        import_preview.updated.len()
+                    ));
+                    set_preview.set(None);
+                    set_import_path.set(None);
+                    on_imported();
+                }
+                Err(e) => set_result.set(format!("Import failed: {}", e)),
+            }
+        });
+    };
+
+    let export = move |_| {
+        spawn_local(async move {
+            let format = format.get_untracked();
+            let options = dialog_options("Export contacts", format);
+            let Some(mut path) = save_dialog(to_value(&options).unwrap()).await.as_string() else {
+                return;
+            };
+            if path
+                .rsplit(['/', '\\'])
+                .next()
+                .is_some_and(|name| !name.contains('.'))
+            {
+                path = format!("{}.{}", path, format.extension());
+            }
+            match ipc::export_contacts(&path, format).await {
+                Ok(count) => set_result.set(format!("Exported {} contacts to {}", count, path)),
+                Err(e) => set_result.set(format!("Export failed: {}", e)),
+            }
+        });
+    };
+
+    view! {
+        <div id="contacts-transfer" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Import / export contacts"</span>
+            <div class="mt-1">
+                "Format: "
+                <select id="contacts-transfer_format" on:change=update_format>
+                    <option value="VCard">"vCard 4.0 (.vcf)"</option>
+                    <option value="Csv">"CSV (.csv)"</option>
+                    <option value="Json">"EmiEssiEne (.json)"</option>
+                </select>
+            </div>
+            <div class="flex-row mt-1">
+                <button type="button" on:click=pick_import_file>
+                    "Import..."
+                </button>
+                <button type="button" class="ml-05" on:click=export>
+                    "Export..."
+                </button>
+                <button type="button" class="ml-auto" on:click=move |_| on_close()>
+                    "Close"
+                </button>
+            </div>
+            {move || {
+                preview
+                    .get()
+                    .map(|preview| {
+                        let importable = preview.new.len() + preview.updated.len();
+                        view! {
+                            <div class="contacts-transfer_preview flex-col mt-1 auto-y">
+                                <span class="bold">{format!("New ({})", preview.new.len())}</span>
+                                <ul>
+                                    {preview
+                                        .new
+                                        .iter()
+                                        .map(|f| view! { <li>"➕ " {contact_line(f)}</li> })
+                                        .collect::<Vec<_>>()}
+                                </ul>
+                                <span class="bold mt-1">
+                                    {format!("Updated ({})", preview.updated.len())}
+                                </span>
+                                <ul>
+                                    {preview
+                                        .updated
+                                        .iter()
+                                        .map(|u| {
+                                            view! {
+                                                <li>
+                                                    "✏️ " {contact_line(&u.before)} " → "
+                                                    {contact_line(&u.after)}
+                                                </li>
+                                            }
+                                        })
+                                        .collect::<Vec<_>>()}
+                                </ul>
+                                <span class="bold mt-1">
+                                    {format!("Duplicates, skipped ({})", preview.duplicates.len())}
+                                </span>
+                                <ul>
+                                    {preview
+                                        .duplicates
+                                        .iter()
+                                        .map(|f| view! { <li>"⏭️ " {contact_line(f)}</li> })
+                                        .collect::<Vec<_>>()}
+                                </ul>
+                                <div class="flex-row mt-1">
+                                    <button
+                                        type="button"
+                                        disabled=importable == 0
+                                        on:click=confirm_import
+                                    >
+                                        {format!("Import {} contacts", importable)}
+                                    </button>
+                                    <button
+                                        type="button"
+                                        class="ml-05"
+                                        on:click=move |_| {
+                                            set_preview.set(None);
+                                            set_import_path.set(None);
+                                        }
+                                    >
+                                        "Cancel"
+                                    </button>
+                                </div>
+                            </div>
+                        }
+                    })
+            }}
+            <span class="mt-1">{result}</span>
+        </div>
+    }
+}
@@ -0,0 +1,121 @@
+use crate::app::open_dialog;
+use crate::components::message_component::custom_emoticon_src;
+use crate::ipc;
+use leptos::*;
+use serde_wasm_bindgen::to_value;
+
+use super::models::emoticons::CustomEmoticon;
+use super::models::{DialogFilter, DialogOptions};
+
+#[component]
+pub fn EmoticonManager(
+    on_close: impl Fn() + Copy + 'static,
+    on_changed: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    let (emoticons, set_emoticons) = create_signal(Vec::<CustomEmoticon>::new());
+    let (shortcut, set_shortcut) = create_signal(String::new());
+    let (error, set_error) = create_signal(String::new());
+
+    let reload = move || {
+        spawn_local(async move {
+            match ipc::list_custom_emoticons().await {
+                Ok(loaded) => set_emoticons.set(loaded),
+                Err(e) => set_error.set(format!("Couldn't load your emoticons: {}", e)),
+            }
+        });
+    };
+    reload();
+
+    let add = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        spawn_local(async move {
+            let options = DialogOptions {
+                title: "Choose an emoticon image",
+                filters: vec![DialogFilter {
+                    name: "Images",
+                    extensions: &["png", "gif", "jpg", "jpeg"],
+                }],
+                directory: false,
+            };
+            let Some(path) = open_dialog(to_value(&options).unwrap()).await.as_string() else {
+                return;
+            };
+            match ipc::add_custom_emoticon(&path, &shortcut.get_untracked()).await {
+                Ok(_) => {
+                    set_shortcut.set(String::new());
+                    set_error.set(String::new());
+                    reload();
+                    on_changed();
+                }
+                Err(e) => set_error.set(format!("Couldn't add the emoticon: {}", e)),
+            }
+        });
+    };
+
+    let remove = move |shortcut: String| {
+        spawn_local(async move {
+            match ipc::remove_custom_emoticon(&shortcut).await {
+                Ok(()) => {
+                    reload();
+                    on_changed();
+                }
+                Err(e) => set_error.set(format!("Couldn't remove {}: {}", shortcut, e)),
+            }
+        });
+    };
+
+    view! {
+        <div id="emoticon-manager" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"My emoticons"</span>
+            <ul class="auto-y">
+                {move || {
+                    emoticons
+                        .get()
+                        .into_iter()
+                        .map(|emoticon| {
+                            let shortcut = emoticon.shortcut.clone();
+                            view! {
+                                <li class="flex-row">
+                                    <img
+                                        class="emoticon"
+                                        src=custom_emoticon_src(&emoticon.hash)
+                                        alt=emoticon.shortcut.clone()
+                                    />
+                                    <span class="ml-05">{emoticon.shortcut.clone()}</span>
+                                    <a
+                                        class="ml-auto"
+                                        title="Remove"
+                                        on:click=move |_| remove(shortcut.clone())
+                                    >
+                                        "✖"
+                                    </a>
+                                </li>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </ul>
+            <form class="flex-col mt-1" on:submit=add>
+                <label for="emoticon-manager_shortcut">
+                    "Shortcut (up to 7 characters)"
+                </label>
+                <input
+                    type="text"
+                    id="emoticon-manager_shortcut"
+                    class="border-1b"
+                    maxlength="7"
+                    prop:value=shortcut
+                    on:input=move |ev| set_shortcut.set(event_target_value(&ev))
+                />
+                <span class="mt-1">"PNG, GIF or JPEG, up to 64 KB. It's sent to friends the first time you use it."</span>
+                <div class="flex-row mt-1">
+                    <button type="submit">"Choose image…"</button>
+                    <button type="button" class="ml-05" on:click=move |_| on_close()>
+                        "Close"
+                    </button>
+                </div>
+                <span class="error-text">{error}</span>
+            </form>
+        </div>
+    }
+}
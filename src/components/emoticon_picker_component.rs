@@ -0,0 +1,78 @@
+use crate::components::emoticon_manager_component::EmoticonManager;
+use crate::components::message_component::{custom_emoticon_src, emoticon_src};
+use crate::ipc;
+use leptos::*;
+
+use super::models::emoticons::{CustomEmoticon, EMOTICONS};
+
+#[component]
+pub fn EmoticonPicker(
+    on_pick: impl Fn(&str) + Copy + 'static,
+    on_close: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    let (custom, set_custom) = create_signal(Vec::<CustomEmoticon>::new());
+    let (managing, set_managing) = create_signal(false);
+
+    let load_custom = move || {
+        spawn_local(async move {
+            match ipc::list_custom_emoticons().await {
+                Ok(emoticons) => set_custom.set(emoticons),
+                Err(e) => logging::warn!("Couldn't load custom emoticons: {}", e),
+            }
+        });
+    };
+    load_custom();
+
+    view! {
+        <div id="emoticon-picker" class="bg-white border-1b p-4">
+            {EMOTICONS
+                .iter()
+                .map(|emoticon| {
+                    let shortcut = emoticon.shortcuts[0];
+                    view! {
+                        <img
+                            class="emoticon"
+                            src=emoticon_src(emoticon.id)
+                            alt=shortcut
+                            title=format!("{} {}", emoticon.title, shortcut)
+                            on:click=move |_| {
+                                on_pick(shortcut);
+                                on_close();
+                            }
+                        />
+                    }
+                })
+                .collect_view()}
+            {move || {
+                custom
+                    .get()
+                    .into_iter()
+                    .map(|emoticon| {
+                        let shortcut = emoticon.shortcut.clone();
+                        view! {
+                            <img
+                                class="emoticon"
+                                src=custom_emoticon_src(&emoticon.hash)
+                                alt=emoticon.shortcut.clone()
+                                title=emoticon.shortcut.clone()
+                                on:click=move |_| {
+                                    on_pick(&shortcut);
+                                    on_close();
+                                }
+                            />
+                        }
+                    })
+                    .collect_view()
+            }}
+            <a class="emoticon-picker_manage" on:click=move |_| set_managing.set(true)>
+                "Manage…"
+            </a>
+        </div>
+        <Show when=move || managing.get()>
+            <EmoticonManager
+                on_close=move || set_managing.set(false)
+                on_changed=load_custom
+            />
+        </Show>
+    }
+}
@@ -0,0 +1,144 @@
+use crate::app::open_dialog;
+use crate::ipc;
+use leptos::*;
+use serde_wasm_bindgen::to_value;
+
+use super::models::{format_size, DialogOptions, FileOffer, FileTransfer, TransferStatus};
+
+#[derive(Clone, Copy)]
+enum Action {
+    Accept,
+    Decline,
+    Resume,
+    Cancel,
+}
+
+/// Lets the user pick the folder received files are saved in.
+fn change_download_folder(set_error: WriteSignal<String>) {
+    spawn_local(async move {
+        let options = DialogOptions {
+            title: "Save received files in",
+            filters: Vec::new(),
+            directory: true,
+        };
+        let Some(folder) = open_dialog(to_value(&options).unwrap()).await.as_string() else {
+            return;
+        };
+        let saved = match ipc::get_settings().await {
+            Ok(mut settings) => {
+                settings.download_dir = Some(folder);
+                ipc::update_settings(settings).await.map(|_| ())
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = saved {
+            set_error.set(format!("Couldn't change the download folder: {}", e));
+        }
+    });
+}
+
+/// The card shown in a conversation for a file offered by either side.
+#[component]
+pub fn FileTransferCard(
+    file: FileOffer,
+    /// The transfer's latest state, missing until the backend has reported it.
+    transfer: Signal<Option<FileTransfer>>,
+    /// Display name of the friend on the other end.
+    friend_name: Signal<String>,
+    incoming: bool,
+) -> impl IntoView {
+    let (error, set_error) = create_signal(String::new());
+
+    let act = move |action: Action| {
+        let Some(id) = transfer.get_untracked().map(|t| t.id) else {
+            return;
+        };
+        spawn_local(async move {
+            let result = match action {
+                Action::Accept => ipc::accept_file(&id).await,
+                Action::Decline => ipc::decline_file(&id).await,
+                Action::Resume => ipc::resume_file(&id).await,
+                Action::Cancel => ipc::cancel_file(&id).await,
+            };
+            match result {
+                Ok(_) => set_error.set(String::new()),
+                Err(e) => set_error.set(e.to_string()),
+            }
+        });
+    };
+
+    let status = move || transfer.get().map(|t| t.status);
+    // An incoming offer is turned down with Decline instead.
+    let cancellable = move || {
+        status().is_some_and(|s| !(s.is_finished() || incoming && s == TransferStatus::Offered))
+    };
+    let status_line = move || {
+        let Some(t) = transfer.get() else {
+            return String::new();
+        };
+        let reason = t.error.clone().unwrap_or_default();
+        match (t.status, incoming) {
+            (TransferStatus::Offered, true) => {
+                format!("{} wants to send you this file.", friend_name.get())
+            }
+            (TransferStatus::Offered, false) => {
+                format!("Waiting for {} to accept the file…", friend_name.get())
+            }
+            (TransferStatus::Declined, true) => "You declined this file.".to_string(),
+            (TransferStatus::Declined, false) => {
+                format!("{} declined the file.", friend_name.get())
+            }
+            (TransferStatus::Transferring, _) => format!(
+                "{} of {}",
+                format_size(t.transferred),
+                format_size(t.file.size)
+            ),
+            (TransferStatus::Interrupted, _) => format!("The transfer was interrupted. {}", reason),
+            (TransferStatus::Completed, true) => {
+                format!("Saved to {}", t.path.clone().unwrap_or_default())
+            }
+            (TransferStatus::Completed, false) => "The file was sent.".to_string(),
+            (TransferStatus::Failed, _) => format!("The transfer failed. {}", reason),
+            (TransferStatus::Cancelled, _) => "The transfer was cancelled.".to_string(),
+        }
+    };
+
+    view! {
+        <div class="file-transfer main_bordered">
+            <div class="file-transfer_name">
+                {"📄 "} {file.name.clone()}
+                <span class="file-transfer_size">{format!(" ({})", format_size(file.size))}</span>
+            </div>
+            <Show when=move || status() == Some(TransferStatus::Transferring)>
+                <progress
+                    class="file-transfer_progress"
+                    max="100"
+                    value=move || transfer.get().map(|t| t.percent()).unwrap_or_default()
+                ></progress>
+            </Show>
+            <div class="file-transfer_status">{status_line}</div>
+            <div class="file-transfer_actions flex-row">
+                <Show when=move || incoming && status() == Some(TransferStatus::Offered)>
+                    <button type="button" on:click=move |_| act(Action::Accept)>
+                        "Accept"
+                    </button>
+                    <button type="button" on:click=move |_| act(Action::Decline)>
+                        "Decline"
+                    </button>
+                    <a on:click=move |_| change_download_folder(set_error)>"Change folder…"</a>
+                </Show>
+                <Show when=move || status() == Some(TransferStatus::Interrupted)>
+                    <button type="button" on:click=move |_| act(Action::Resume)>
+                        "Resume"
+                    </button>
+                </Show>
+                <Show when=cancellable>
+                    <button type="button" on:click=move |_| act(Action::Cancel)>
+                        "Cancel"
+                    </button>
+                </Show>
+            </div>
+            <span class="error-text">{error}</span>
+        </div>
+    }
+}
@@ -1,13 +1,18 @@
 use super::models::Availability;
+use super::unread_badge_component::UnreadBadge;
 use leptos::*;
 
 #[component]
 pub fn Friend(
     availability: ReadSignal<Availability>,
     name: ReadSignal<String>,
+    alias: ReadSignal<Option<String>>,
+    email: ReadSignal<String>,
     status: ReadSignal<String>,
-    open_chat: impl Fn(usize) + 'static,
-    order: Option<usize>,
+    #[prop(into)] unread: Signal<u32>,
+    open_chat: impl Fn(String) + 'static,
+    edit_contact: impl Fn(String) + 'static,
+    online: bool,
 ) -> impl IntoView {
     let format_status = move || {
         if !status.get().is_empty() {
@@ -17,18 +22,34 @@ pub fn Friend(
         }
     };
 
+    let display_name = move || alias.get().unwrap_or_else(|| name.get());
+    let real_name = move || alias.get().map(|_| name.get());
+
     view! {
         <div id="friend_container" class="flex-row minline-20">
             <span>{move || availability.get().to_icon()}</span>
             <a on:click=move |ev| {
                 ev.prevent_default();
-                if order.is_some() {
-                    open_chat(order.unwrap())
+                if online {
+                    open_chat(email.get())
                 }
             }>
-                <span class="bold">{move || name.get()}</span>
+                <span class="bold" title=real_name>
+                    {display_name}
+                </span>
             </a>
+            <UnreadBadge count=unread />
             <span class="ml-04">" " {move || format_status}</span>
+            <a
+                class="ml-auto friend_edit-link"
+                title="Edit contact"
+                on:click=move |ev| {
+                    ev.prevent_default();
+                    edit_contact(email.get());
+                }
+            >
+                "✏️"
+            </a>
         </div>
     }
 }
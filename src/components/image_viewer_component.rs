@@ -0,0 +1,17 @@
+use crate::components::message_component::image_src;
+use leptos::*;
+
+use super::models::SharedImage;
+
+/// Shows a shared picture full size over the whole window, until it is clicked.
+#[component]
+pub fn ImageViewer(image: SharedImage, on_close: impl Fn() + Copy + 'static) -> impl IntoView {
+    view! {
+        <div class="image-viewer" title="Click to close" on:click=move |_| on_close()>
+            <img src=image_src(&image, false) alt="Picture" />
+            <span class="image-viewer_size">
+                {format!("{} × {} pixels", image.width, image.height)}
+            </span>
+        </div>
+    }
+}
@@ -0,0 +1,48 @@
+use leptos::*;
+
+use super::models::Friend;
+
+/// Lists the friends who can be invited into a conversation, for `on_pick` to invite one.
+#[component]
+pub fn InvitePicker(
+    #[prop(into)] candidates: Signal<Vec<Friend>>,
+    on_pick: impl Fn(String) + Copy + 'static,
+    on_close: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    view! {
+        <div id="invite-picker" class="bg-white border-1b p-4">
+            <div class="invite-picker_title">"Invite someone to this conversation"</div>
+            {move || {
+                let candidates = candidates.get();
+                if candidates.is_empty() {
+                    return view! {
+                        <div class="invite-picker_empty">
+                            "None of your friends who are online can be invited."
+                        </div>
+                    }
+                        .into_view();
+                }
+                candidates
+                    .into_iter()
+                    .map(|friend| {
+                        let email = friend.email.clone();
+                        view! {
+                            <div
+                                class="invite-picker_friend"
+                                on:click=move |_| {
+                                    on_pick(email.clone());
+                                    on_close();
+                                }
+                            >
+                                {friend.display_name()}
+                                <span class="invite-picker_email">
+                                    {format!("<{}>", friend.email)}
+                                </span>
+                            </div>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </div>
+    }
+}
@@ -1,13 +1,23 @@
-use crate::components::models::Availability;
+use crate::components::avatar_component::Avatar;
+use crate::components::models::{Availability, User};
+use crate::ipc;
 use leptos::*;
 use leptos_router::use_navigate;
 
 #[component]
 pub fn LoginPage() -> impl IntoView {
-    let (_, set_availability) = create_signal(Availability::Online);
+    let (availability, set_availability) = create_signal(Availability::Online);
     let (remember_me, set_remember_me) = create_signal(false);
     let (auto_sign_in, set_auto_sign_in) = create_signal(false);
     let navigate = use_navigate();
+    let (user, set_user) = create_signal(None);
+
+    spawn_local(async move {
+        match ipc::get_user().await {
+            Ok(loaded) => set_user.set(Some(loaded)),
+            Err(e) => logging::warn!("Couldn't load your profile: {}", e),
+        }
+    });
 
     let update_availability = move |ev| {
         let value = event_target_value(&ev);
@@ -21,7 +31,13 @@ pub fn LoginPage() -> impl IntoView {
 
     let sign_in = move |ev: leptos::ev::MouseEvent| {
         ev.prevent_default();
-        navigate("/main", Default::default());
+        let navigate = navigate.clone();
+        spawn_local(async move {
+            if let Err(e) = ipc::set_availability(availability.get_untracked()).await {
+                logging::warn!("Couldn't set your status: {}", e);
+            }
+            navigate("/main", Default::default());
+        });
     };
 
     view! {
@@ -29,7 +45,14 @@ pub fn LoginPage() -> impl IntoView {
             <div id="login_title">"EmiEssiEne"</div>
             <div id="login_avatar">
                 <div id="login_avatar_img" style="width: 150px; height: 150px; background: black;">
-                    a
+                    <Avatar
+                        picture=Signal::derive(move || {
+                            user.get().and_then(|user: User| user.display_picture)
+                        })
+                        name=Signal::derive(move || {
+                            user.get().map(|user| user.name).unwrap_or_default()
+                        })
+                    />
                 </div>
             </div>
             <form id="login_form" class="flex-col">
@@ -0,0 +1,287 @@
+use crate::app::{open_dialog, save_dialog};
+use crate::ipc;
+use leptos::*;
+use serde_wasm_bindgen::to_value;
+
+use super::models;
+use models::{is_group, DialogFilter, DialogOptions, LogFormat, LogImportPreview};
+
+fn format_filter(format: LogFormat) -> DialogFilter<'static> {
+    let name = match format {
+        LogFormat::Html => "Web page",
+        LogFormat::Text => "Text",
+        LogFormat::MsnXml => "MSN Messenger log",
+    };
+    let extensions: &[&str] = match format {
+        LogFormat::Html => &["html", "htm"],
+        LogFormat::Text => &["txt"],
+        LogFormat::MsnXml => &["xml"],
+    };
+    DialogFilter { name, extensions }
+}
+
+/// Saves conversations as web pages, text or MSN Messenger logs, and adds the history of an old
+/// MSN Messenger log to the conversation with a friend.
+#[component]
+pub fn LogsTransfer(
+    /// Conversations that can be saved, as pairs of id and name.
+    #[prop(into)]
+    conversations: Signal<Vec<(String, String)>>,
+    on_close: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    let (conversation, set_conversation) = create_signal(None::<String>);
+    let (format, set_format) = create_signal(LogFormat::Html);
+    let (import_path, set_import_path) = create_signal(None::<String>);
+    let (import_into, set_import_into) = create_signal(None::<String>);
+    let (my_names, set_my_names) = create_signal(Vec::<String>::new());
+    let (preview, set_preview) = create_signal(None::<LogImportPreview>);
+    let (result, set_result) = create_signal(String::new());
+
+    let friends = Signal::derive(move || {
+        conversations
+            .get()
+            .into_iter()
+            .filter(|(id, _)| !is_group(id))
+            .collect::<Vec<_>>()
+    });
+
+    let update_format = move |ev| {
+        set_format.set(match event_target_value(&ev).as_str() {
+            "Text" => LogFormat::Text,
+            "MsnXml" => LogFormat::MsnXml,
+            _ => LogFormat::Html,
+        });
+    };
+
+    let export = move |_| {
+        spawn_local(async move {
+            let format = format.get_untracked();
+            let conversation = conversation.get_untracked();
+            let options = match conversation {
+                Some(_) => DialogOptions {
+                    title: "Save conversation",
+                    filters: vec![format_filter(format)],
+                    directory: false,
+                },
+                None => DialogOptions {
+                    title: "Save every conversation in",
+                    filters: Vec::new(),
+                    directory: true,
+                },
+            };
+            let options = to_value(&options).unwrap();
+            let picked = match conversation {
+                Some(_) => save_dialog(options).await,
+                None => open_dialog(options).await,
+            };
+            let Some(mut path) = picked.as_string() else {
+                return;
+            };
+            if conversation.is_some()
+                && path
+                    .rsplit(['/', '\\'])
+                    .next()
+                    .is_some_and(|name| !name.contains('.'))
+            {
+                path = format!("{}.{}", path, format.extension());
+            }
+            match ipc::export_conversation(conversation.as_deref(), &path, format).await {
+                Ok(count) => set_result.set(format!("Saved {} messages to {}", count, path)),
+                Err(e) => set_result.set(format!("Export failed: {}", e)),
+            }
+        });
+    };
+
+    // Reads the log again with the names picked so far, to count what importing it would add.
+    let refresh_preview = move || {
+        spawn_local(async move {
+            let (Some(path), Some(friend)) =
+                (import_path.get_untracked(), import_into.get_untracked())
+            else {
+                return;
+            };
+            match ipc::import_msn_log(&path, &friend, my_names.get_untracked(), true).await {
+                Ok(import_preview) => set_preview.set(Some(import_preview)),
+                Err(e) => {
+                    set_preview.set(None);
+                    set_import_path.set(None);
+                    set_result.set(format!("Couldn't read {}: {}", path, e));
+                }
+            }
+        });
+    };
+
+    let pick_import_file = move |_| {
+        spawn_local(async move {
+            let options = DialogOptions {
+                title: "Import MSN Messenger log",
+                filters: vec![format_filter(LogFormat::MsnXml)],
+                directory: false,
+            };
+            let Some(path) = open_dialog(to_value(&options).unwrap()).await.as_string() else {
+                return;
+            };
+            let friend = import_into
+                .get_untracked()
+                .or_else(|| friends.get_untracked().first().map(|(id, _)| id.clone()));
+            let Some(friend) = friend else {
+                set_result.set("Add the friend the log is with to your contacts first".to_string());
+                return;
+            };
+            set_import_into.set(Some(friend));
+            set_my_names.set(Vec::new());
+            set_import_path.set(Some(path));
+            refresh_preview();
+        });
+    };
+
+    let toggle_name = move |name: String, mine: bool| {
+        set_my_names.update(|names| {
+            names.retain(|n| *n != name);
+            if mine {
+                names.push(name);
+            }
+        });
+        refresh_preview();
+    };
+
+    let cancel_import = move || {
+        set_preview.set(None);
+        set_import_path.set(None);
+    };
+
+    let confirm_import = move |_| {
+        spawn_local(async move {
+            let (Some(path), Some(friend)) =
+                (import_path.get_untracked(), import_into.get_untracked())
+            else {
+                return;
+            };
+            match ipc::import_msn_log(&path, &friend, my_names.get_untracked(), false).await {
+                Ok(import_preview) => {
+                    set_result.set(format!(
+                        "Imported {} messages, skipped {} already there",
+                        import_preview.new, import_preview.duplicates
+                    ));
+                    cancel_import();
+                }
+                Err(e) => set_result.set(format!("Import failed: {}", e)),
+            }
+        });
+    };
+
+    view! {
+        <div id="logs-transfer" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Save / import conversations"</span>
+            <div class="logs-transfer_options flex-row mt-1">
+                <select on:change=move |ev| {
+                    let value = event_target_value(&ev);
+                    set_conversation.set(Some(value).filter(|value| !value.is_empty()));
+                }>
+                    <option value="">"All conversations"</option>
+                    {move || {
+                        conversations
+                            .get()
+                            .into_iter()
+                            .map(|(id, name)| view! { <option value=id>{name}</option> })
+                            .collect_view()
+                    }}
+                </select>
+                <select on:change=update_format>
+                    <option value="Html">"Web page (.html)"</option>
+                    <option value="Text">"Text (.txt)"</option>
+                    <option value="MsnXml">"MSN Messenger log (.xml)"</option>
+                </select>
+            </div>
+            <div class="flex-row mt-1">
+                <button type="button" on:click=export>
+                    "Export..."
+                </button>
+                <button type="button" class="ml-05" on:click=pick_import_file>
+                    "Import MSN log..."
+                </button>
+                <button type="button" class="ml-auto" on:click=move |_| on_close()>
+                    "Close"
+                </button>
+            </div>
+            {move || {
+                preview
+                    .get()
+                    .map(|preview| {
+                        view! {
+                            <div class="logs-transfer_preview flex-col mt-1 auto-y">
+                                <label>
+                                    "Conversation with "
+                                    <select on:change=move |ev| {
+                                        set_import_into.set(Some(event_target_value(&ev)));
+                                        refresh_preview();
+                                    }>
+                                        {move || {
+                                            friends
+                                                .get()
+                                                .into_iter()
+                                                .map(|(id, name)| {
+                                                    let selected = import_into.get_untracked().as_ref()
+                                                        == Some(&id);
+                                                    view! {
+                                                        <option value=id selected=selected>
+                                                            {name}
+                                                        </option>
+                                                    }
+                                                })
+                                                .collect_view()
+                                        }}
+                                    </select>
+                                </label>
+                                <span class="bold mt-1">"These names are mine:"</span>
+                                {preview
+                                    .senders
+                                    .into_iter()
+                                    .map(|name| {
+                                        let mine = my_names.get_untracked().contains(&name);
+                                        view! {
+                                            <label>
+                                                <input
+                                                    type="checkbox"
+                                                    prop:checked=mine
+                                                    on:change={
+                                                        let name = name.clone();
+                                                        move |ev| toggle_name(name.clone(), event_target_checked(&ev))
+                                                    }
+                                                />
+                                                {name}
+                                            </label>
+                                        }
+                                    })
+                                    .collect_view()}
+                                <span class="mt-1">
+                                    {format!(
+                                        "{} new messages, {} already in the conversation",
+                                        preview.new,
+                                        preview.duplicates,
+                                    )}
+                                </span>
+                                <div class="flex-row mt-1">
+                                    <button
+                                        type="button"
+                                        disabled=preview.new == 0
+                                        on:click=confirm_import
+                                    >
+                                        {format!("Import {} messages", preview.new)}
+                                    </button>
+                                    <button
+                                        type="button"
+                                        class="ml-05"
+                                        on:click=move |_| cancel_import()
+                                    >
+                                        "Cancel"
+                                    </button>
+                                </div>
+                            </div>
+                        }
+                    })
+            }}
+            <span class="mt-1">{result}</span>
+        </div>
+    }
+}
@@ -1,75 +1,145 @@
-use crate::app::invoke;
-use crate::components::chat_component::Chat;
+use crate::app::open_dialog;
+use crate::components::avatar_component::Avatar;
+use crate::components::chat_component::{group_name, Chat};
+use crate::components::contact_editor_component::ContactEditor;
+use crate::components::contacts_transfer_component::ContactsTransfer;
 use crate::components::friend_component::Friend;
+use crate::components::logs_transfer_component::LogsTransfer;
+use crate::components::search_panel_component::SearchPanel;
+use crate::components::unread_badge_component::UnreadBadge;
+use crate::ipc;
 use leptos::ev::{FocusEvent, KeyboardEvent};
 use leptos::*;
-use leptos_router::A;
-use serde_wasm_bindgen::{from_value, to_value};
-use wasm_bindgen::{JsCast, JsValue};
+use leptos_router::use_navigate;
+use serde_wasm_bindgen::to_value;
+use std::collections::HashMap;
+use std::time::Duration;
+use wasm_bindgen::JsCast;
 use web_sys::HtmlInputElement;
 
 use super::models;
-use models::{Availability, Friend, UpdateUsernameArgs, User};
+use models::{
+    events, is_group, Availability, ChatLayout, DialogFilter, DialogOptions, Friend, Group,
+    ImageSource, ShowMessageEvent, User,
+};
+
+/// How long the window shakes for a nudge, matching the `shake` animation in styles.css.
+const NUDGE_SHAKE_DURATION: Duration = Duration::from_millis(600);
 
 #[component]
 pub fn MainPage() -> impl IntoView {
     let (show_chat, set_show_chat) = create_signal(false);
-    let (friend_id, set_friend_id) = create_signal(0);
-    let (open_chats, set_open_chats) = create_signal(Vec::new());
+    let (chat_email, set_chat_email) = create_signal(String::new());
+    let (open_chats, set_open_chats) = create_signal(Vec::<String>::new());
     let (editing_user, set_editing_user) = create_signal(false);
+    let (editing_contact, set_editing_contact) = create_signal(None::<Friend>);
+    let (show_contacts_transfer, set_show_contacts_transfer) = create_signal(false);
+    let (show_search, set_show_search) = create_signal(false);
+    let (show_logs, set_show_logs) = create_signal(false);
+    let (scroll_to, set_scroll_to) = create_signal(None::<String>);
     let (user, set_user) = create_signal(User {
         name: "Username".to_string(),
         email: "user@hotmail.com".to_string(),
         status: "Status message".to_string(),
         availability: Availability::Online,
+        display_picture: None,
     });
     let (friends, set_friends) = create_signal((Vec::new(), Vec::new()));
+    let (groups, set_groups) = create_signal(Vec::<Group>::new());
+    let (layout, set_layout) = create_signal(ChatLayout::default());
+    let (quit_on_close, set_quit_on_close) = create_signal(false);
+    let (unread, set_unread) = create_signal(HashMap::<String, u32>::new());
+    let (error, set_error) = create_signal(None::<String>);
+    let (shaking, set_shaking) = create_signal(false);
 
-    let load_user = create_action(|_: &()| async {
-        let info = invoke("get_user", JsValue::null()).await;
-        let updated_user: User = from_value(info).expect("Failed to parse user info");
-        updated_user
-    });
+    let load_user = create_action(|_: &()| ipc::get_user());
+    let load_friends = create_action(|_: &()| ipc::get_friends());
 
-    let load_friends = create_action(|_: &()| async {
-        let info = invoke("get_friends", JsValue::null()).await;
-        let updated_friends: (Vec<Friend>, Vec<Friend>) =
-            from_value(info).expect("Failed to parse friends info");
-        updated_friends
+    create_effect(move |_| match load_user.value().get() {
+        Some(Ok(updated_user)) => set_user.set(updated_user),
+        Some(Err(e)) => set_error.set(Some(format!("Couldn't load your profile: {}", e))),
+        None => {}
     });
 
-    create_effect(move |_| {
-        if let Some(updated_user) = load_user.value().get() {
-            set_user.set(updated_user);
-        }
-        if let Some(updated_friends) = load_friends.value().get() {
-            set_friends.set(updated_friends);
-        }
+    create_effect(move |_| match load_friends.value().get() {
+        Some(Ok(updated_friends)) => set_friends.set(updated_friends),
+        Some(Err(e)) => set_error.set(Some(format!("Couldn't load your friends: {}", e))),
+        None => {}
     });
 
     load_user.dispatch(());
     load_friends.dispatch(());
 
+    let load_groups = move || {
+        spawn_local(async move {
+            match ipc::list_groups().await {
+                Ok(list) => set_groups.set(list),
+                Err(e) => set_error.set(Some(format!("Couldn't load your group chats: {}", e))),
+            }
+        });
+    };
+    load_groups();
+
+    spawn_local(async move {
+        match ipc::get_settings().await {
+            Ok(settings) => {
+                set_layout.set(settings.chat_layout);
+                set_quit_on_close.set(settings.quit_on_close);
+            }
+            Err(e) => set_error.set(Some(format!("Couldn't load your settings: {}", e))),
+        }
+    });
+
+    spawn_local(async move {
+        match ipc::get_unread_counts().await {
+            Ok(counts) => set_unread.set(counts),
+            Err(e) => logging::warn!("Couldn't count unread messages: {}", e),
+        }
+    });
+
     let update_username = {
         move |ev: FocusEvent| {
             ev.prevent_default();
-            let set_user = set_user.clone();
             spawn_local(async move {
                 let username = event_target_value(&ev);
-                let result = invoke(
-                    "update_username",
-                    to_value(&UpdateUsernameArgs { name: &username }).unwrap(),
-                )
-                .await;
-                let updated_user: User = from_value(result).expect("Failed to parse user info");
-                set_user.update(|user| {
-                    user.name = updated_user.name;
-                });
+                match ipc::update_username(&username).await {
+                    Ok(updated_user) => set_user.update(|user| {
+                        user.name = updated_user.name;
+                    }),
+                    Err(e) => set_error.set(Some(format!("Couldn't change your name: {}", e))),
+                }
             });
             set_editing_user.set(false);
         }
     };
 
+    let change_display_picture = move |source: Option<ImageSource>| {
+        spawn_local(async move {
+            match ipc::set_display_picture(source).await {
+                Ok(updated_user) => set_user.set(updated_user),
+                Err(e) => {
+                    set_error.set(Some(format!("Couldn't change your display picture: {}", e)))
+                }
+            }
+        });
+    };
+
+    let choose_display_picture = move |_| {
+        spawn_local(async move {
+            let options = DialogOptions {
+                title: "Choose a display picture",
+                filters: vec![DialogFilter {
+                    name: "Pictures",
+                    extensions: &["png", "gif", "jpg", "jpeg"],
+                }],
+                directory: false,
+            };
+            if let Some(path) = open_dialog(to_value(&options).unwrap()).await.as_string() {
+                change_display_picture(Some(ImageSource::Path(path)));
+            }
+        });
+    };
+
     let blur_on_enter = {
         move |ev: KeyboardEvent| {
             if ev.key() == "Enter" {
@@ -80,43 +150,243 @@ pub fn MainPage() -> impl IntoView {
         }
     };
 
-    let open_new_chat = move |id: usize| {
+    let open_new_chat = move |email: String| {
+        if layout.get_untracked() == ChatLayout::Windows {
+            spawn_local(async move {
+                if let Err(e) = ipc::open_chat_window(&email).await {
+                    set_error.set(Some(format!("Couldn't open the chat: {}", e)));
+                }
+            });
+            return;
+        }
         set_open_chats.update(|chats| {
-            if !chats.contains(&id) {
-                chats.push(id);
+            if !chats.contains(&email) {
+                chats.push(email.clone());
             }
         });
-        set_friend_id.set(id);
+        set_chat_email.set(email);
         set_show_chat.set(true);
     };
 
-    let close_chat = move |id: usize| {
+    ipc::listen(events::FRIENDS_CHANGED, move |_: ()| {
+        load_friends.dispatch(())
+    });
+    ipc::listen(events::GROUPS_CHANGED, move |_: ()| load_groups());
+    ipc::listen(events::OPEN_CHAT, open_new_chat);
+    ipc::listen(
+        events::UNREAD_CHANGED,
+        move |counts: HashMap<String, u32>| set_unread.set(counts),
+    );
+    ipc::listen(events::USER_CHANGED, move |changed: User| {
+        set_user.set(changed)
+    });
+    let navigate = use_navigate();
+    ipc::listen(events::SIGNED_OUT, move |_: ()| {
+        navigate("/", Default::default())
+    });
+    ipc::listen(events::SHOW_MESSAGE, move |event: ShowMessageEvent| {
+        // A chat in its own window scrolls by itself.
+        if layout.get_untracked() == ChatLayout::Tabs {
+            open_new_chat(event.conversation);
+            set_scroll_to.set(Some(event.message_id));
+        }
+    });
+    ipc::listen(events::NUDGE, move |email: String| {
+        // A chat in its own window shakes by itself.
+        if layout.get_untracked() == ChatLayout::Windows {
+            return;
+        }
+        open_new_chat(email);
+        set_shaking.set(true);
+        set_timeout(move || set_shaking.set(false), NUDGE_SHAKE_DURATION);
+    });
+
+    // Tell the backend which chat is on screen so it only notifies about the others.
+    create_effect(move |_| {
+        let active_chat = show_chat.get().then(|| chat_email.get());
+        spawn_local(async move {
+            if let Err(e) = ipc::focus_chat(active_chat.as_deref()).await {
+                logging::warn!("Couldn't report the open chat: {}", e);
+            }
+        });
+    });
+
+    let friend_name = move |email: &str| {
+        friends.with(|(online, offline)| {
+            online
+                .iter()
+                .chain(offline.iter())
+                .find(|f| f.email == email)
+                .map(|f| f.display_name())
+                .unwrap_or_else(|| email.to_string())
+        })
+    };
+
+    let conversation_name = move |conversation: &str| {
+        if !is_group(conversation) {
+            return friend_name(conversation);
+        }
+        groups
+            .with(|groups| {
+                groups.iter().find(|g| g.id == conversation).map(|group| {
+                    friends.with(|friends| group_name(group, &user.get().email, friends))
+                })
+            })
+            .unwrap_or_else(|| "Group chat".to_string())
+    };
+
+    let unread_count = move |conversation: &str| {
+        unread.with(|unread| unread.get(conversation).copied().unwrap_or(0))
+    };
+
+    let searchable_conversations = Signal::derive(move || {
+        let mut conversations: Vec<(String, String)> = friends.with(|(online, offline)| {
+            online
+                .iter()
+                .chain(offline.iter())
+                .map(|f| (f.email.clone(), f.display_name()))
+                .collect()
+        });
+        conversations.extend(groups.with(|groups| {
+            groups
+                .iter()
+                .map(|g| (g.id.clone(), conversation_name(&g.id)))
+                .collect::<Vec<_>>()
+        }));
+        conversations
+    });
+
+    let author_name = move |email: &str| {
+        if email == user.with_untracked(|user| user.email.clone()) {
+            user.with_untracked(|user| user.name.clone())
+        } else {
+            friend_name(email)
+        }
+    };
+
+    let edit_contact = move |email: String| {
+        let friend = friends.with_untracked(|(online, offline)| {
+            online
+                .iter()
+                .chain(offline.iter())
+                .find(|f| f.email == email)
+                .cloned()
+        });
+        set_editing_contact.set(friend);
+    };
+
+    let contact_saved = move |updated_friend: Friend| {
+        set_friends.update(|(online, offline)| {
+            if let Some(f) = online
+                .iter_mut()
+                .chain(offline.iter_mut())
+                .find(|f| f.email == updated_friend.email)
+            {
+                *f = updated_friend;
+            }
+        });
+    };
+
+    let toggle_layout = move |_| {
+        spawn_local(async move {
+            let saved = match ipc::get_settings().await {
+                Ok(mut settings) => {
+                    settings.chat_layout = match settings.chat_layout {
+                        ChatLayout::Windows => ChatLayout::Tabs,
+                        ChatLayout::Tabs => ChatLayout::Windows,
+                    };
+                    ipc::update_settings(settings).await
+                }
+                Err(e) => Err(e),
+            };
+            match saved {
+                Ok(settings) => {
+                    set_layout.set(settings.chat_layout);
+                    // Chats open as tabs move into windows of their own.
+                    if settings.chat_layout == ChatLayout::Windows {
+                        set_show_chat.set(false);
+                        for email in open_chats.get_untracked() {
+                            open_new_chat(email);
+                        }
+                        set_open_chats.set(Vec::new());
+                    }
+                }
+                Err(e) => set_error.set(Some(format!("Couldn't change how chats open: {}", e))),
+            }
+        });
+    };
+
+    let toggle_quit_on_close = move |_| {
+        spawn_local(async move {
+            let saved = match ipc::get_settings().await {
+                Ok(mut settings) => {
+                    settings.quit_on_close = !settings.quit_on_close;
+                    ipc::update_settings(settings).await
+                }
+                Err(e) => Err(e),
+            };
+            match saved {
+                Ok(settings) => set_quit_on_close.set(settings.quit_on_close),
+                Err(e) => set_error.set(Some(format!("Couldn't change what closing does: {}", e))),
+            }
+        });
+    };
+
+    let sign_out = move |_| {
+        spawn_local(async move {
+            if let Err(e) = ipc::sign_out().await {
+                set_error.set(Some(format!("Couldn't sign out: {}", e)));
+            }
+        });
+    };
+
+    let close_chat = move |email: String| {
         set_open_chats.update(|chats| {
-            chats.retain(|&x| x != id);
+            chats.retain(|x| *x != email);
         });
 
         set_show_chat.set(false);
     };
 
     view! {
-        <div id="main-container" class="flex-col">
+        <div id="main-container" class="flex-col" class:shake=shaking>
+            {move || {
+                error
+                    .get()
+                    .map(|message| {
+                        view! {
+                            <div class="error-banner flex-row p-4">
+                                <span>"⚠️ " {message}</span>
+                                <a class="ml-auto" on:click=move |_| set_error.set(None)>
+                                    "✖"
+                                </a>
+                            </div>
+                        }
+                    })
+            }}
             <div class="chat-tabs">
                 {move || {
                     open_chats
                         .get()
                         .iter()
-                        .map(|&id| {
-                            let friend = &friends.get().0[id];
+                        .map(|email| {
+                            let name = conversation_name(email);
+                            let tab_email = email.clone();
+                            let selected_email = email.clone();
+                            let unread_email = email.clone();
                             view! {
                                 <button
                                     class="chat-tab"
-                                    class:active=move || friend_id.get() == id
+                                    class:active=move || chat_email.get() == tab_email
                                     on:click=move |_| {
-                                        set_friend_id.set(id);
+                                        set_chat_email.set(selected_email.clone());
                                         set_show_chat.set(true);
                                     }
                                 >
-                                    {&friend.name}
+                                    {name}
+                                    <UnreadBadge count=Signal::derive(move || {
+                                        unread_count(&unread_email)
+                                    }) />
                                 </button>
                             }
                         })
@@ -130,8 +400,10 @@ pub fn MainPage() -> impl IntoView {
                             show=set_show_chat
                             user=user
                             friends=friends
-                            friend=friend_id
+                            groups=groups
+                            friend=chat_email
                             close=close_chat
+                            scroll_to=scroll_to
                         />
                     }
                 } else {
@@ -143,9 +415,27 @@ pub fn MainPage() -> impl IntoView {
                                         <div
                                             id="avatar_img"
                                             style="width: 90px; height: 90px; background: black;"
+                                            title="Click to change your display picture"
+                                            on:click=choose_display_picture
                                         >
-                                            a
+                                            <Avatar
+                                                picture=Signal::derive(move || {
+                                                    user.get().display_picture
+                                                })
+                                                name=Signal::derive(move || user.get().name)
+                                            />
                                         </div>
+                                        <Show when=move || {
+                                            user.get().display_picture.is_some()
+                                        }>
+                                            <a
+                                                class="avatar_remove"
+                                                title="Remove your display picture"
+                                                on:click=move |_| change_display_picture(None)
+                                            >
+                                                "Remove"
+                                            </a>
+                                        </Show>
                                     </div>
                                 </div>
                                 <div id="header_right" class="ml-1">
@@ -180,9 +470,9 @@ pub fn MainPage() -> impl IntoView {
                                             {move || user.get().status}
                                             <span class="tabbed-down-arrow">"🔽"</span>
                                         </div>
-                                        <A href="/" class="logout-link">
+                                        <a class="logout-link" on:click=sign_out>
                                             "Sign Out"
-                                        </A>
+                                        </a>
                                     </div>
                                 </div>
                             </div>
@@ -199,6 +489,53 @@ pub fn MainPage() -> impl IntoView {
                                 class="border-1b"
                             />
                             <span>"➕"</span>
+                            <a
+                                class="ml-05"
+                                title="Search conversations"
+                                on:click=move |_| set_show_search.set(true)
+                            >
+                                "🔍"
+                            </a>
+                            <a
+                                class="ml-05"
+                                title="Import / export contacts"
+                                on:click=move |_| set_show_contacts_transfer.set(true)
+                            >
+                                "📇"
+                            </a>
+                            <a
+                                class="ml-05"
+                                title="Save / import conversations"
+                                on:click=move |_| set_show_logs.set(true)
+                            >
+                                "🗂"
+                            </a>
+                            <a
+                                class="ml-05"
+                                title=move || match layout.get() {
+                                    ChatLayout::Windows => "Show chats as tabs in this window",
+                                    ChatLayout::Tabs => "Open chats in windows of their own",
+                                }
+                                on:click=toggle_layout
+                            >
+                                {move || match layout.get() {
+                                    ChatLayout::Windows => "📑",
+                                    ChatLayout::Tabs => "🗔",
+                                }}
+                            </a>
+                            <a
+                                class="ml-05"
+                                title=move || {
+                                    if quit_on_close.get() {
+                                        "Closing this window quits. Click to keep running in the tray instead"
+                                    } else {
+                                        "Closing this window keeps running in the tray. Click to quit instead"
+                                    }
+                                }
+                                on:click=toggle_quit_on_close
+                            >
+                                {move || if quit_on_close.get() { "⏻" } else { "📥" }}
+                            </a>
                         </div>
                         <div id="friends-container" class="flex-col flex-grow p-10 bg-white auto-y">
                             <span class="bold">"🔽 Friends"</span>
@@ -208,16 +545,22 @@ pub fn MainPage() -> impl IntoView {
                                         .get()
                                         .0
                                         .iter()
-                                        .enumerate()
-                                        .map(|(i, f)| {
+                                        .map(|f| {
                                             view! {
                                                 <li>
                                                     <Friend
                                                         availability=create_signal(f.availability.clone()).0
                                                         name=create_signal(f.name.to_string()).0
+                                                        alias=create_signal(f.alias.clone()).0
+                                                        email=create_signal(f.email.to_string()).0
                                                         status=create_signal(f.status.to_string()).0
+                                                        unread=Signal::derive({
+                                                            let email = f.email.clone();
+                                                            move || unread_count(&email)
+                                                        })
                                                         open_chat=open_new_chat
-                                                        order=Some(i)
+                                                        edit_contact=edit_contact
+                                                        online=true
                                                     />
                                                 </li>
                                             }
@@ -238,9 +581,16 @@ pub fn MainPage() -> impl IntoView {
                                                     <Friend
                                                         availability=create_signal(f.availability.clone()).0
                                                         name=create_signal(f.name.to_string()).0
+                                                        alias=create_signal(f.alias.clone()).0
+                                                        email=create_signal(f.email.to_string()).0
                                                         status=create_signal(f.status.to_string()).0
+                                                        unread=Signal::derive({
+                                                            let email = f.email.clone();
+                                                            move || unread_count(&email)
+                                                        })
                                                         open_chat=open_new_chat
-                                                        order=None
+                                                        edit_contact=edit_contact
+                                                        online=false
                                                     />
                                                 </li>
                                             }
@@ -249,6 +599,38 @@ pub fn MainPage() -> impl IntoView {
                                 }}
                             </ul>
                         </div>
+                        {move || {
+                            editing_contact
+                                .get()
+                                .map(|friend| {
+                                    view! {
+                                        <ContactEditor
+                                            friend=friend
+                                            on_close=move || set_editing_contact.set(None)
+                                            on_saved=contact_saved
+                                        />
+                                    }
+                                })
+                        }}
+                        <Show when=move || show_search.get()>
+                            <SearchPanel
+                                conversations=searchable_conversations
+                                author_name=author_name
+                                on_close=move || set_show_search.set(false)
+                            />
+                        </Show>
+                        <Show when=move || show_logs.get()>
+                            <LogsTransfer
+                                conversations=searchable_conversations
+                                on_close=move || set_show_logs.set(false)
+                            />
+                        </Show>
+                        <Show when=move || show_contacts_transfer.get()>
+                            <ContactsTransfer
+                                on_close=move || set_show_contacts_transfer.set(false)
+                                on_imported=move || load_friends.dispatch(())
+                            />
+                        </Show>
                     }
                         .into_view()
                 }
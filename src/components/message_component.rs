@@ -1,15 +1,125 @@
+use crate::app::convert_file_src;
+use crate::components::image_viewer_component::ImageViewer;
+use crate::components::voice_clip_component::VoiceClipPlayer;
 use leptos::*;
-use super::models;
-use models::User;
+use super::models::emoticons::{tokenize, CustomEmoticon, Segment};
+use super::models::{MessageStatus, SharedImage, TextStyle, VoiceClip};
+
+/// Where the bundled emoticon images are served from.
+pub fn emoticon_src(id: &str) -> String {
+    format!("public/emoticons/{}.svg", id)
+}
+
+/// URL of a custom emoticon's image, served by the backend's `emoticon` protocol.
+pub fn custom_emoticon_src(hash: &str) -> String {
+    convert_file_src(hash, "emoticon")
+}
+
+/// URL of a shared picture, or of its thumbnail, served by the backend's `image` protocol.
+pub fn image_src(image: &SharedImage, thumbnail: bool) -> String {
+    if thumbnail {
+        convert_file_src(&format!("{}-thumbnail", image.hash), "image")
+    } else {
+        convert_file_src(&image.hash, "image")
+    }
+}
+
+fn status_view(status: MessageStatus) -> View {
+    let (ticks, title) = match status {
+        MessageStatus::Sent => ("✓", "Sent"),
+        MessageStatus::Delivered => ("✓✓", "Delivered"),
+        MessageStatus::Read => ("✓✓", "Read"),
+    };
+    view! {
+        <span class="message_status" class:message_status-read=status == MessageStatus::Read title=title>
+            {ticks}
+        </span>
+    }
+    .into_view()
+}
+
+fn body_view(body: &str, custom: &[CustomEmoticon]) -> View {
+    tokenize(body, custom)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.into_view(),
+            Segment::Code(code) => view! { <code>{code.to_string()}</code> }.into_view(),
+            Segment::Emoticon { emoticon, shortcut } => view! {
+                <img
+                    class="emoticon"
+                    src=emoticon_src(emoticon.id)
+                    alt=shortcut.to_string()
+                    title=format!("{} {}", emoticon.title, shortcut)
+                />
+            }
+            .into_view(),
+            Segment::Custom { emoticon, shortcut } => view! {
+                <img
+                    class="emoticon"
+                    src=custom_emoticon_src(&emoticon.hash)
+                    alt=shortcut.to_string()
+                    title=shortcut.to_string()
+                />
+            }
+            .into_view(),
+        })
+        .collect_view()
+}
 
 #[component]
-pub fn Message(user: ReadSignal<User>, content: ReadSignal<String>) -> impl IntoView {
+pub fn Message(
+    author: ReadSignal<String>,
+    content: ReadSignal<String>,
+    emoticons: ReadSignal<Vec<CustomEmoticon>>,
+    style: ReadSignal<Option<TextStyle>>,
+    image: ReadSignal<Option<SharedImage>>,
+    voice: ReadSignal<Option<VoiceClip>>,
+    /// Tells apart the authors in a group conversation.
+    author_color: Option<&'static str>,
+    /// The author changed the text after sending it.
+    #[prop(optional)]
+    edited: bool,
+    /// The author took the message back, so only a note that it was there is shown.
+    #[prop(optional)]
+    deleted: bool,
+    /// How far a message the user sent got, shown as ticks.
+    #[prop(optional_no_strip)]
+    status: Option<MessageStatus>,
+) -> impl IntoView {
+    let (viewing, set_viewing) = create_signal(false);
+    if deleted {
+        return view! {
+            <div class="message_container">
+                <div class="message_content">
+                    <div class="message_author" style=author_color.map(|color| format!("color: {}", color))>{move || author.get()}" says:"</div>
+                    <div class="message_text message_deleted">"This message was deleted."</div>
+                </div>
+            </div>
+        };
+    }
     view! {
         <div class="message_container">
             <div class="message_content">
-                <div class="message_author">{move || user.get().name}" says:"</div>
-                <div class="message_text">{content.get()}</div>
+                <div class="message_author" style=author_color.map(|color| format!("color: {}", color))>{move || author.get()}" says:"</div>
+                <div class="message_text" style=move || style.with(|s| s.as_ref().map(TextStyle::to_css))>
+                    {move || emoticons.with(|custom| body_view(&content.get(), custom))}
+                    {edited.then(|| view! { <span class="message_edited">" (edited)"</span> })}
+                </div>
+                {move || image.get().map(|picture| view! {
+                    <img
+                        class="message_image"
+                        src=image_src(&picture, true)
+                        alt="Picture"
+                        title="Click to see it full size"
+                        on:click=move |_| set_viewing.set(true)
+                    />
+                })}
+                {move || voice.get().map(|clip| view! { <VoiceClipPlayer clip=clip /> })}
+                {status.map(status_view)}
             </div>
+            {move || image.get().filter(|_| viewing.get()).map(|picture| view! {
+                <ImageViewer image=picture on_close=move || set_viewing.set(false) />
+            })}
         </div>
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,93 @@
+use crate::ipc;
+use leptos::*;
+
+use super::models::Settings;
+
+fn edit_window_label(minutes: Option<u32>) -> String {
+    match minutes {
+        None => "For as long as I like".to_string(),
+        Some(60) => "For an hour".to_string(),
+        Some(minutes) => format!("For {} minutes", minutes),
+    }
+}
+
+/// Options for the messages the user sends.
+#[component]
+pub fn MessageOptions(on_close: impl Fn() + Copy + 'static) -> impl IntoView {
+    let (settings, set_settings) = create_signal(None::<Settings>);
+    let (error, set_error) = create_signal(String::new());
+
+    spawn_local(async move {
+        match ipc::get_settings().await {
+            Ok(loaded) => set_settings.set(Some(loaded)),
+            Err(e) => set_error.set(format!("Couldn't load your message settings: {}", e)),
+        }
+    });
+
+    let save = move |change: Box<dyn FnOnce(&mut Settings)>| {
+        let Some(mut updated) = settings.get_untracked() else {
+            return;
+        };
+        change(&mut updated);
+        spawn_local(async move {
+            match ipc::update_settings(updated).await {
+                Ok(saved) => {
+                    set_settings.set(Some(saved));
+                    set_error.set(String::new());
+                }
+                Err(e) => set_error.set(format!("Couldn't save your message settings: {}", e)),
+            }
+        });
+    };
+
+    let edit_window = move || settings.with(|s| s.as_ref().and_then(|s| s.edit_window_minutes));
+
+    view! {
+        <div id="message-options" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Messages"</span>
+            <label class="mt-1" for="message-options_edit-window">
+                "Edit or delete my messages after sending them"
+            </label>
+            <select
+                id="message-options_edit-window"
+                on:change=move |ev| {
+                    let minutes = event_target_value(&ev).parse().ok();
+                    save(Box::new(move |s| s.edit_window_minutes = minutes));
+                }
+            >
+                {Settings::EDIT_WINDOWS
+                    .into_iter()
+                    .map(|minutes| {
+                        view! {
+                            <option
+                                value=minutes.map(|m| m.to_string()).unwrap_or_default()
+                                selected=move || edit_window() == minutes
+                            >
+                                {edit_window_label(minutes)}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+            <label class="mt-1">
+                <input
+                    type="checkbox"
+                    prop:checked=move || {
+                        settings.with(|s| s.as_ref().is_some_and(|s| s.send_read_receipts))
+                    }
+                    on:change=move |ev| {
+                        let send = event_target_checked(&ev);
+                        save(Box::new(move |s| s.send_read_receipts = send));
+                    }
+                />
+                " Let my contacts know when I've read their messages"
+            </label>
+            <div class="flex-row mt-1">
+                <button type="button" on:click=move |_| on_close()>
+                    "Close"
+                </button>
+            </div>
+            <span class="error-text">{error}</span>
+        </div>
+    }
+}
@@ -1,53 +1,17 @@
-use serde::{Deserialize, Serialize};
-use std::fmt::Display;
+use serde::Serialize;
 
-#[derive(Serialize, Deserialize)]
-pub struct UpdateUsernameArgs<'a> {
-    pub name: &'a str,
-}
-
-#[derive(Clone, Deserialize, Serialize)]
-pub struct User {
-    pub name: String,
-    pub email: String,
-    pub status: String,
-    pub availability: Availability,
-}
+pub use emiessiene_models::*;
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct Friend {
-    pub name: String,
-    pub email: String,
-    pub status: String,
-    pub availability: Availability,
-}
-
-#[derive(Clone, Deserialize, Serialize)]
-pub enum Availability {
-    Online,
-    Away,
-    Busy,
-    Offline,
+#[derive(Serialize)]
+pub struct DialogFilter<'a> {
+    pub name: &'a str,
+    pub extensions: &'a [&'a str],
 }
 
-impl Display for Availability {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Availability::Online => write!(f, "Online"),
-            Availability::Away => write!(f, "Away"),
-            Availability::Busy => write!(f, "Busy"),
-            Availability::Offline => write!(f, "Offline"),
-        }
-    }
+#[derive(Serialize)]
+pub struct DialogOptions<'a> {
+    pub title: &'a str,
+    pub filters: Vec<DialogFilter<'a>>,
+    /// Picks a folder instead of a file.
+    pub directory: bool,
 }
-
-impl Availability {
-    pub fn to_icon(&self) -> String {
-        match self {
-            Availability::Online => "👤".to_string(),
-            Availability::Away => "⏳".to_string(),
-            Availability::Busy => "⛔".to_string(),
-            Availability::Offline => "📴".to_string(),
-        }
-    }
-}
\ No newline at end of file
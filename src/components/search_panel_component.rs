@@ -0,0 +1,191 @@
+use crate::ipc;
+use leptos::ev::SubmitEvent;
+use leptos::*;
+use wasm_bindgen::JsValue;
+
+use super::models::{SearchQuery, SearchResult};
+
+/// Milliseconds since the Unix epoch at the local midnight starting the day picked in a date
+/// input, or the day `days_later` after it.
+fn day_start(value: &str, days_later: u32) -> Option<u64> {
+    let mut parts = value.split('-').map(|part| part.parse::<u32>().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let date =
+        js_sys::Date::new_with_year_month_day(year, month as i32 - 1, (day + days_later) as i32);
+    Some(date.get_time() as u64)
+}
+
+fn format_time(millis: u64) -> String {
+    js_sys::Date::new(&JsValue::from_f64(millis as f64))
+        .to_locale_string("default", &JsValue::UNDEFINED)
+        .into()
+}
+
+/// Searches every conversation, or one of them between two dates, and opens the conversation at
+/// a message picked from the results.
+#[component]
+pub fn SearchPanel(
+    /// Conversations that can be searched on their own, as pairs of id and name.
+    #[prop(into)]
+    conversations: Signal<Vec<(String, String)>>,
+    /// Names the sender of a message, given their email.
+    author_name: impl Fn(&str) -> String + Copy + 'static,
+    on_close: impl Fn() + Copy + 'static,
+) -> impl IntoView {
+    let (text, set_text) = create_signal(String::new());
+    let (conversation, set_conversation) = create_signal(None::<String>);
+    let (since, set_since) = create_signal(String::new());
+    let (until, set_until) = create_signal(String::new());
+    let (results, set_results) = create_signal(None::<Vec<SearchResult>>);
+    let (error, set_error) = create_signal(None::<String>);
+
+    let search = move |ev: SubmitEvent| {
+        ev.prevent_default();
+        let query = SearchQuery {
+            text: text.get_untracked(),
+            conversation: conversation.get_untracked(),
+            since: day_start(&since.get_untracked(), 0),
+            // Up to the end of the last day picked.
+            until: day_start(&until.get_untracked(), 1).map(|end| end - 1),
+        };
+        spawn_local(async move {
+            match ipc::search_messages(query).await {
+                Ok(found) => {
+                    set_results.set(Some(found));
+                    set_error.set(None);
+                }
+                Err(e) => set_error.set(Some(format!("The search failed: {}", e))),
+            }
+        });
+    };
+
+    let conversation_name = move |id: &str| {
+        conversations.with(|conversations| {
+            conversations
+                .iter()
+                .find(|(conversation, _)| conversation == id)
+                .map(|(_, name)| name.clone())
+                .unwrap_or_else(|| id.to_string())
+        })
+    };
+
+    let show = move |conversation: String, message_id: String| {
+        spawn_local(async move {
+            match ipc::show_message(&conversation, &message_id).await {
+                Ok(()) => on_close(),
+                Err(e) => set_error.set(Some(format!("Couldn't open the conversation: {}", e))),
+            }
+        });
+    };
+
+    view! {
+        <div id="search-panel" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Search conversations"</span>
+            <form class="flex-col mt-1" on:submit=search>
+                <input
+                    type="text"
+                    class="border-1b"
+                    placeholder="Words to look for"
+                    prop:value=text
+                    on:input=move |ev| set_text.set(event_target_value(&ev))
+                />
+                <div class="search-panel_filters flex-row mt-1">
+                    <select on:change=move |ev| {
+                        let value = event_target_value(&ev);
+                        set_conversation.set(Some(value).filter(|value| !value.is_empty()));
+                    }>
+                        <option value="">"All conversations"</option>
+                        {move || {
+                            conversations
+                                .get()
+                                .into_iter()
+                                .map(|(id, name)| view! { <option value=id>{name}</option> })
+                                .collect_view()
+                        }}
+                    </select>
+                    <label>
+                        "From "
+                        <input
+                            type="date"
+                            on:change=move |ev| set_since.set(event_target_value(&ev))
+                        />
+                    </label>
+                    <label>
+                        "to "
+                        <input
+                            type="date"
+                            on:change=move |ev| set_until.set(event_target_value(&ev))
+                        />
+                    </label>
+                </div>
+                <div class="flex-row mt-1">
+                    <button type="submit">"Search"</button>
+                    <button type="button" class="ml-auto" on:click=move |_| on_close()>
+                        "Close"
+                    </button>
+                </div>
+            </form>
+            {move || error.get().map(|message| view! { <div class="error-text">{message}</div> })}
+            {move || {
+                results
+                    .get()
+                    .map(|results| {
+                        if results.is_empty() {
+                            return view! {
+                                <div class="search-panel_empty mt-1">"No messages found."</div>
+                            }
+                                .into_view();
+                        }
+                        view! {
+                            <div class="search-panel_results flex-col mt-1 auto-y">
+                                {results
+                                    .into_iter()
+                                    .map(|result| {
+                                        let conversation = result.conversation.clone();
+                                        let message_id = result.message_id.clone();
+                                        view! {
+                                            <div
+                                                class="search-result"
+                                                on:click=move |_| {
+                                                    show(conversation.clone(), message_id.clone())
+                                                }
+                                            >
+                                                <div class="flex-row">
+                                                    <span class="bold">
+                                                        {conversation_name(&result.conversation)}
+                                                    </span>
+                                                    <span class="search-result_date ml-auto">
+                                                        {format_time(result.sent_at)}
+                                                    </span>
+                                                </div>
+                                                <div class="search-result_snippet">
+                                                    {author_name(&result.from)} ": "
+                                                    {result
+                                                        .snippet
+                                                        .into_iter()
+                                                        .map(|part| {
+                                                            if part.matched {
+                                                                view! { <mark>{part.text}</mark> }
+                                                                    .into_view()
+                                                            } else {
+                                                                part.text.into_view()
+                                                            }
+                                                        })
+                                                        .collect_view()}
+                                                </div>
+                                            </div>
+                                        }
+                                    })
+                                    .collect_view()}
+                            </div>
+                        }
+                            .into_view()
+                    })
+            }}
+        </div>
+    }
+}
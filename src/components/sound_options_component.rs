@@ -0,0 +1,147 @@
+use crate::ipc;
+use leptos::*;
+
+use super::models::{Settings, SoundEvent};
+
+#[component]
+pub fn SoundOptions(on_close: impl Fn() + Copy + 'static) -> impl IntoView {
+    let (settings, set_settings) = create_signal(None::<Settings>);
+    let (packs, set_packs) = create_signal(Vec::<String>::new());
+    let (error, set_error) = create_signal(String::new());
+
+    spawn_local(async move {
+        match ipc::get_settings().await {
+            Ok(loaded) => set_settings.set(Some(loaded)),
+            Err(e) => set_error.set(format!("Couldn't load your sound settings: {}", e)),
+        }
+        match ipc::list_sound_packs().await {
+            Ok(loaded) => set_packs.set(loaded),
+            Err(e) => set_error.set(format!("Couldn't list sound packs: {}", e)),
+        }
+    });
+
+    // Every change is saved straight away, like the rest of the messenger's options.
+    let save = move |change: Box<dyn FnOnce(&mut Settings)>| {
+        let Some(mut updated) = settings.get_untracked() else {
+            return;
+        };
+        change(&mut updated);
+        spawn_local(async move {
+            match ipc::update_settings(updated).await {
+                Ok(saved) => {
+                    set_settings.set(Some(saved));
+                    set_error.set(String::new());
+                }
+                Err(e) => set_error.set(format!("Couldn't save your sound settings: {}", e)),
+            }
+        });
+    };
+
+    let preview = move |event: SoundEvent| {
+        spawn_local(async move {
+            if let Err(e) = ipc::play_sound(event).await {
+                set_error.set(format!("Couldn't play the sound: {}", e));
+            }
+        });
+    };
+
+    let sounds = move || settings.get().map(|s| s.sounds).unwrap_or_default();
+
+    view! {
+        <div id="sound-options" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Sounds"</span>
+            <label class="mt-1">
+                <input
+                    type="checkbox"
+                    prop:checked=move || sounds().enabled
+                    on:change=move |ev| {
+                        let enabled = event_target_checked(&ev);
+                        save(Box::new(move |s| s.sounds.enabled = enabled));
+                    }
+                />
+                " Play sounds (never while I'm Busy)"
+            </label>
+            <label class="mt-1" for="sound-options_pack">
+                "Sound pack"
+            </label>
+            <select
+                id="sound-options_pack"
+                on:change=move |ev| {
+                    let pack = event_target_value(&ev);
+                    save(Box::new(move |s| s.sounds.pack = pack));
+                }
+            >
+                {move || {
+                    packs
+                        .get()
+                        .into_iter()
+                        .map(|pack| {
+                            let selected = sounds().pack == pack;
+                            view! {
+                                <option value=pack.clone() selected=selected>
+                                    {pack.clone()}
+                                </option>
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                }}
+            </select>
+            <label class="mt-1" for="sound-options_volume">
+                "Volume"
+            </label>
+            <input
+                type="range"
+                id="sound-options_volume"
+                min="0"
+                max="100"
+                prop:value=move || sounds().volume.to_string()
+                on:change=move |ev| {
+                    if let Ok(volume) = event_target_value(&ev).parse::<u8>() {
+                        save(Box::new(move |s| s.sounds.volume = volume));
+                    }
+                }
+            />
+            <span class="mt-1">"Play a sound when…"</span>
+            <ul>
+                {SoundEvent::ALL
+                    .into_iter()
+                    .map(|event| {
+                        view! {
+                            <li class="flex-row">
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=move || {
+                                            !sounds().disabled_events.contains(&event)
+                                        }
+                                        on:change=move |ev| {
+                                            let enabled = event_target_checked(&ev);
+                                            save(
+                                                Box::new(move |s| {
+                                                    s.sounds.disabled_events.retain(|e| *e != event);
+                                                    if !enabled {
+                                                        s.sounds.disabled_events.push(event);
+                                                    }
+                                                }),
+                                            );
+                                        }
+                                    />
+                                    " " {event.label()}
+                                </label>
+                                <a class="ml-auto" title="Preview" on:click=move |_| preview(event)>
+                                    "▶️"
+                                </a>
+                            </li>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </ul>
+            <div class="flex-row mt-1">
+                <button type="button" on:click=move |_| on_close()>
+                    "Close"
+                </button>
+            </div>
+            <span class="error-text">{error}</span>
+        </div>
+    }
+}
@@ -0,0 +1,122 @@
+use crate::ipc;
+use leptos::*;
+
+use super::models::{Settings, TextStyle};
+
+#[component]
+pub fn TextStyleOptions(on_close: impl Fn() + Copy + 'static) -> impl IntoView {
+    let (settings, set_settings) = create_signal(None::<Settings>);
+    let (error, set_error) = create_signal(String::new());
+
+    spawn_local(async move {
+        match ipc::get_settings().await {
+            Ok(loaded) => set_settings.set(Some(loaded)),
+            Err(e) => set_error.set(format!("Couldn't load your font settings: {}", e)),
+        }
+    });
+
+    let save = move |change: Box<dyn FnOnce(&mut TextStyle)>| {
+        let Some(mut updated) = settings.get_untracked() else {
+            return;
+        };
+        change(&mut updated.text_style);
+        spawn_local(async move {
+            match ipc::update_settings(updated).await {
+                Ok(saved) => {
+                    set_settings.set(Some(saved));
+                    set_error.set(String::new());
+                }
+                Err(e) => set_error.set(format!("Couldn't save your font: {}", e)),
+            }
+        });
+    };
+
+    let style = move || settings.get().map(|s| s.text_style).unwrap_or_default();
+
+    let emphasis = [
+        (
+            "Bold",
+            (|s| s.bold) as fn(&TextStyle) -> bool,
+            (|s, on| s.bold = on) as fn(&mut TextStyle, bool),
+        ),
+        ("Italic", |s| s.italic, |s, on| s.italic = on),
+        ("Underline", |s| s.underline, |s, on| s.underline = on),
+        (
+            "Strikethrough",
+            |s| s.strikethrough,
+            |s, on| s.strikethrough = on,
+        ),
+    ];
+
+    view! {
+        <div id="text-style-options" class="dialog-panel flex-col p-10 bg-white border-1b">
+            <span class="bold">"Font"</span>
+            <label class="mt-1" for="text-style-options_font">
+                "Font"
+            </label>
+            <select
+                id="text-style-options_font"
+                on:change=move |ev| {
+                    let font = event_target_value(&ev);
+                    save(Box::new(move |s| s.font = font));
+                }
+            >
+                {TextStyle::FONTS
+                    .iter()
+                    .map(|font| {
+                        view! {
+                            <option value=*font selected=move || style().font == *font>
+                                {*font}
+                            </option>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </select>
+            <label class="mt-1" for="text-style-options_color">
+                "Color"
+            </label>
+            <input
+                type="color"
+                id="text-style-options_color"
+                prop:value=move || style().color
+                on:change=move |ev| {
+                    let color = event_target_value(&ev);
+                    save(Box::new(move |s| s.color = color));
+                }
+            />
+            <span class="mt-1">"Effects"</span>
+            <ul>
+                {emphasis
+                    .into_iter()
+                    .map(|(label, get, set)| {
+                        view! {
+                            <li>
+                                <label>
+                                    <input
+                                        type="checkbox"
+                                        prop:checked=move || get(&style())
+                                        on:change=move |ev| {
+                                            let on = event_target_checked(&ev);
+                                            save(Box::new(move |s| set(s, on)));
+                                        }
+                                    />
+                                    " " {label}
+                                </label>
+                            </li>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </ul>
+            <span class="mt-1">"Preview"</span>
+            <div class="text-style-options_preview main_bordered" style=move || style().to_css()>
+                "The quick brown fox jumps over the lazy dog"
+            </div>
+            <div class="flex-row mt-1">
+                <button type="button" on:click=move |_| on_close()>
+                    "Close"
+                </button>
+            </div>
+            <span class="error-text">{error}</span>
+        </div>
+    }
+}
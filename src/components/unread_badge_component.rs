@@ -0,0 +1,16 @@
+use leptos::*;
+
+/// The number of unread messages in a conversation, shown only while there are some.
+#[component]
+pub fn UnreadBadge(#[prop(into)] count: Signal<u32>) -> impl IntoView {
+    view! {
+        <Show when=move || { count.get() > 0 }>
+            <span class="unread-badge" title="Unread messages">
+                {move || match count.get() {
+                    count @ 1..=99 => count.to_string(),
+                    _ => "99+".to_string(),
+                }}
+            </span>
+        </Show>
+    }
+}
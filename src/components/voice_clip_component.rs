@@ -0,0 +1,94 @@
+use crate::app::convert_file_src;
+use leptos::*;
+
+use super::models::VoiceClip;
+
+/// URL of a voice clip, served by the backend's `voice` protocol.
+pub fn voice_src(clip: &VoiceClip) -> String {
+    convert_file_src(&clip.hash, "voice")
+}
+
+/// Formats milliseconds as `m:ss`.
+fn clock(ms: u32) -> String {
+    let seconds = ms / 1000;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Plays a voice clip, showing its waveform filling up as it plays.
+#[component]
+pub fn VoiceClipPlayer(clip: VoiceClip) -> impl IntoView {
+    let audio = create_node_ref::<html::Audio>();
+    let (playing, set_playing) = create_signal(false);
+    // How far playback has got, from 0 to 1.
+    let (progress, set_progress) = create_signal(0.0);
+
+    let toggle = move |_| {
+        let Some(audio) = audio.get_untracked() else {
+            return;
+        };
+        if audio.paused() {
+            if let Err(e) = audio.play() {
+                logging::warn!("Couldn't play voice clip: {:?}", e);
+            }
+        } else {
+            let _ = audio.pause();
+        }
+    };
+
+    let update_progress = move |_| {
+        if let Some(audio) = audio.get_untracked() {
+            let duration = audio.duration();
+            if duration.is_finite() && duration > 0.0 {
+                set_progress.set(audio.current_time() / duration);
+            }
+        }
+    };
+
+    let bars = clip.waveform.len().max(1) as f64;
+    let duration_ms = clip.duration_ms;
+
+    view! {
+        <div class="voice-clip main_bordered">
+            <button type="button" class="voice-clip_play" on:click=toggle>
+                {move || if playing.get() { "⏸️" } else { "▶️" }}
+            </button>
+            <div class="voice-clip_waveform">
+                {clip
+                    .waveform
+                    .iter()
+                    .enumerate()
+                    .map(|(i, level)| {
+                        view! {
+                            <span
+                                class="voice-clip_bar"
+                                class:played=move || (i as f64 + 0.5) / bars <= progress.get()
+                                style=format!("height: {}%", (*level).max(4))
+                            ></span>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </div>
+            <span class="voice-clip_duration">
+                {move || {
+                    if playing.get() || progress.get() > 0.0 {
+                        clock((progress.get() * f64::from(duration_ms)) as u32)
+                    } else {
+                        clock(duration_ms)
+                    }
+                }}
+            </span>
+            <audio
+                node_ref=audio
+                src=voice_src(&clip)
+                preload="none"
+                on:play=move |_| set_playing.set(true)
+                on:pause=move |_| set_playing.set(false)
+                on:timeupdate=update_progress
+                on:ended=move |_| {
+                    set_playing.set(false);
+                    set_progress.set(0.0);
+                }
+            ></audio>
+        </div>
+    }
+}
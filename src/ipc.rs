@@ -0,0 +1,513 @@
+//! Typed wrappers around the backend's Tauri commands and events.
+//!
+//! Every call returns a `Result` instead of panicking, with the `AppError` returned by the
+//! backend command decoded into `IpcError::Backend`.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt::Display,
+    rc::Rc,
+};
+
+use emiessiene_models::{
+    commands, emoticons::CustomEmoticon, AddCustomEmoticonArgs, AppError, Availability,
+    ChatMessage, ChatWindowArgs, ContactDetails, ContactFormat, DeleteMessageArgs, EditMessageArgs,
+    ExportContactsArgs, ExportConversationArgs, FileTransfer, FileTransferArgs, FocusChatArgs,
+    Friend, GetConversationArgs, Group, ImageSource, ImportContactsArgs, ImportMsnLogArgs,
+    ImportPreview, InviteToConversationArgs, LeaveConversationArgs, ListFileTransfersArgs,
+    LogFormat, LogImportPreview, PlaySoundArgs, RemoveCustomEmoticonArgs, SearchMessagesArgs,
+    SearchQuery, SearchResult, SendFileArgs, SendImageArgs, SendMessageArgs, SendNudgeArgs,
+    SendTypingArgs, SendVoiceClipArgs, SetAvailabilityArgs, SetDisplayPictureArgs, Settings,
+    ShowMessageArgs, SoundEvent, UpdateContactArgs, UpdateSettingsArgs, UpdateUsernameArgs, User,
+};
+use js_sys::{Function, Reflect};
+use leptos::{logging, on_cleanup, spawn_local};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_wasm_bindgen::{from_value, to_value};
+use wasm_bindgen::prelude::*;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "tauri"], js_name = invoke)]
+    async fn tauri_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "event"], js_name = listen)]
+    async fn tauri_listen(
+        event: &str,
+        handler: &Closure<dyn FnMut(JsValue)>,
+    ) -> Result<JsValue, JsValue>;
+}
+
+/// Emitted by Tauri with the dropped paths when files are dragged onto a window.
+pub const FILE_DROP: &str = "tauri://file-drop";
+
+#[derive(Clone, Debug)]
+pub enum IpcError {
+    /// The command ran and returned an error.
+    Backend(AppError),
+    /// The arguments or the response didn't match the expected type.
+    Serialization(String),
+}
+
+impl Display for IpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IpcError::Backend(error) => write!(f, "{}", friendly_message(error)),
+            IpcError::Serialization(message) => {
+                write!(f, "Unexpected response from the app: {}", message)
+            }
+        }
+    }
+}
+
+/// Describes a backend error in terms the user can act on, keeping the details for context.
+fn friendly_message(error: &AppError) -> String {
+    match error {
+        AppError::NotFound(message) => format!("It looks like that's gone. ({})", message),
+        AppError::Validation(message) => message.clone(),
+        AppError::Storage(message) => {
+            format!("Your data couldn't be read or saved. ({})", message)
+        }
+        AppError::Transport(message) => format!(
+            "Your contact couldn't be reached, check your connection and try again. ({})",
+            message
+        ),
+        AppError::Auth(message) => format!("You're not allowed to do that. ({})", message),
+        AppError::Window(message) => {
+            format!("A chat window couldn't be opened or closed. ({})", message)
+        }
+    }
+}
+
+impl From<serde_wasm_bindgen::Error> for IpcError {
+    fn from(e: serde_wasm_bindgen::Error) -> Self {
+        IpcError::Serialization(e.to_string())
+    }
+}
+
+/// Every backend command takes its arguments as a single parameter named `args`.
+#[derive(Serialize)]
+struct Invoke<'a, A> {
+    args: &'a A,
+}
+
+async fn call<A: Serialize, T: DeserializeOwned>(cmd: &str, args: &A) -> Result<T, IpcError> {
+    let args = to_value(&Invoke { args })?;
+    match tauri_invoke(cmd, args).await {
+        Ok(value) => Ok(from_value(value)?),
+        Err(error) => match from_value::<AppError>(error.clone()) {
+            Ok(app_error) => Err(IpcError::Backend(app_error)),
+            Err(_) => Err(IpcError::Serialization(format!(
+                "{} failed with {}",
+                cmd,
+                js_sys::JSON::stringify(&error)
+                    .map(String::from)
+                    .unwrap_or_default()
+            ))),
+        },
+    }
+}
+
+pub async fn get_user() -> Result<User, IpcError> {
+    call(commands::get_user, &()).await
+}
+
+pub async fn update_username(name: &str) -> Result<User, IpcError> {
+    call(
+        commands::update_username,
+        &UpdateUsernameArgs {
+            name: name.to_string(),
+        },
+    )
+    .await
+}
+
+/// Changes the user's display picture, or removes it when `source` is `None`.
+pub async fn set_display_picture(source: Option<ImageSource>) -> Result<User, IpcError> {
+    call(
+        commands::set_display_picture,
+        &SetDisplayPictureArgs { source },
+    )
+    .await
+}
+
+pub async fn set_availability(availability: Availability) -> Result<User, IpcError> {
+    call(
+        commands::set_availability,
+        &SetAvailabilityArgs { availability },
+    )
+    .await
+}
+
+/// Appears offline and closes every chat window. The backend then emits `events::SIGNED_OUT`.
+pub async fn sign_out() -> Result<(), IpcError> {
+    call(commands::sign_out, &()).await
+}
+
+pub async fn get_friends() -> Result<(Vec<Friend>, Vec<Friend>), IpcError> {
+    call(commands::get_friends, &()).await
+}
+
+pub async fn update_contact(email: &str, details: ContactDetails) -> Result<Friend, IpcError> {
+    call(
+        commands::update_contact,
+        &UpdateContactArgs {
+            email: email.to_string(),
+            details,
+        },
+    )
+    .await
+}
+
+pub async fn import_contacts(
+    path: &str,
+    format: ContactFormat,
+    dry_run: bool,
+) -> Result<ImportPreview, IpcError> {
+    let args = ImportContactsArgs {
+        path: path.to_string(),
+        format,
+        dry_run,
+    };
+    call(commands::import_contacts, &args).await
+}
+
+pub async fn export_contacts(path: &str, format: ContactFormat) -> Result<usize, IpcError> {
+    call(
+        commands::export_contacts,
+        &ExportContactsArgs {
+            path: path.to_string(),
+            format,
+        },
+    )
+    .await
+}
+
+pub async fn get_conversation(email: &str) -> Result<Vec<ChatMessage>, IpcError> {
+    call(
+        commands::get_conversation,
+        &GetConversationArgs {
+            email: email.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn send_message(email: &str, body: &str) -> Result<ChatMessage, IpcError> {
+    call(
+        commands::send_message,
+        &SendMessageArgs {
+            email: email.to_string(),
+            body: body.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn send_nudge(email: &str) -> Result<ChatMessage, IpcError> {
+    call(
+        commands::send_nudge,
+        &SendNudgeArgs {
+            email: email.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn send_typing(email: &str) -> Result<(), IpcError> {
+    call(
+        commands::send_typing,
+        &SendTypingArgs {
+            email: email.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn focus_chat(email: Option<&str>) -> Result<(), IpcError> {
+    call(
+        commands::focus_chat,
+        &FocusChatArgs {
+            email: email.map(str::to_string),
+        },
+    )
+    .await
+}
+
+/// How many messages arrived in each conversation while it wasn't on screen, keyed by friend's
+/// email or group id.
+pub async fn get_unread_counts() -> Result<HashMap<String, u32>, IpcError> {
+    call(commands::get_unread_counts, &()).await
+}
+
+pub async fn get_settings() -> Result<Settings, IpcError> {
+    call(commands::get_settings, &()).await
+}
+
+pub async fn update_settings(settings: Settings) -> Result<Settings, IpcError> {
+    call(commands::update_settings, &UpdateSettingsArgs { settings }).await
+}
+
+pub async fn list_sound_packs() -> Result<Vec<String>, IpcError> {
+    call(commands::list_sound_packs, &()).await
+}
+
+pub async fn play_sound(event: SoundEvent) -> Result<(), IpcError> {
+    call(commands::play_sound, &PlaySoundArgs { event }).await
+}
+
+pub async fn list_custom_emoticons() -> Result<Vec<CustomEmoticon>, IpcError> {
+    call(commands::list_custom_emoticons, &()).await
+}
+
+pub async fn add_custom_emoticon(path: &str, shortcut: &str) -> Result<CustomEmoticon, IpcError> {
+    call(
+        commands::add_custom_emoticon,
+        &AddCustomEmoticonArgs {
+            path: path.to_string(),
+            shortcut: shortcut.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn remove_custom_emoticon(shortcut: &str) -> Result<(), IpcError> {
+    call(
+        commands::remove_custom_emoticon,
+        &RemoveCustomEmoticonArgs {
+            shortcut: shortcut.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn send_file(email: &str, path: &str) -> Result<ChatMessage, IpcError> {
+    call(
+        commands::send_file,
+        &SendFileArgs {
+            email: email.to_string(),
+            path: path.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn accept_file(id: &str) -> Result<FileTransfer, IpcError> {
+    call(
+        commands::accept_file,
+        &FileTransferArgs { id: id.to_string() },
+    )
+    .await
+}
+
+pub async fn decline_file(id: &str) -> Result<FileTransfer, IpcError> {
+    call(
+        commands::decline_file,
+        &FileTransferArgs { id: id.to_string() },
+    )
+    .await
+}
+
+pub async fn resume_file(id: &str) -> Result<FileTransfer, IpcError> {
+    call(
+        commands::resume_file,
+        &FileTransferArgs { id: id.to_string() },
+    )
+    .await
+}
+
+pub async fn cancel_file(id: &str) -> Result<FileTransfer, IpcError> {
+    call(
+        commands::cancel_file,
+        &FileTransferArgs { id: id.to_string() },
+    )
+    .await
+}
+
+pub async fn list_file_transfers(email: &str) -> Result<Vec<FileTransfer>, IpcError> {
+    call(
+        commands::list_file_transfers,
+        &ListFileTransfersArgs {
+            email: email.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn send_image(email: &str, source: ImageSource) -> Result<ChatMessage, IpcError> {
+    call(
+        commands::send_image,
+        &SendImageArgs {
+            email: email.to_string(),
+            source,
+        },
+    )
+    .await
+}
+
+pub async fn send_voice_clip(
+    email: &str,
+    samples: &str,
+    sample_rate: u32,
+) -> Result<ChatMessage, IpcError> {
+    let args = SendVoiceClipArgs {
+        email: email.to_string(),
+        samples: samples.to_string(),
+        sample_rate,
+    };
+    call(commands::send_voice_clip, &args).await
+}
+
+pub async fn list_groups() -> Result<Vec<Group>, IpcError> {
+    call(commands::list_groups, &()).await
+}
+
+/// Invites a friend into a conversation, which starts a new group if it was with one friend.
+pub async fn invite_to_conversation(conversation: &str, email: &str) -> Result<Group, IpcError> {
+    let args = InviteToConversationArgs {
+        conversation: conversation.to_string(),
+        email: email.to_string(),
+    };
+    call(commands::invite_to_conversation, &args).await
+}
+
+pub async fn leave_conversation(conversation: &str) -> Result<(), IpcError> {
+    call(
+        commands::leave_conversation,
+        &LeaveConversationArgs {
+            conversation: conversation.to_string(),
+        },
+    )
+    .await
+}
+
+/// Opens a conversation in a window of its own, or brings its window to the front.
+pub async fn open_chat_window(conversation: &str) -> Result<(), IpcError> {
+    call(
+        commands::open_chat_window,
+        &ChatWindowArgs {
+            conversation: conversation.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn close_chat_window(conversation: &str) -> Result<(), IpcError> {
+    call(
+        commands::close_chat_window,
+        &ChatWindowArgs {
+            conversation: conversation.to_string(),
+        },
+    )
+    .await
+}
+
+pub async fn search_messages(query: SearchQuery) -> Result<Vec<SearchResult>, IpcError> {
+    call(commands::search_messages, &SearchMessagesArgs { query }).await
+}
+
+/// Brings up a conversation scrolled to one of its messages, as the user has chosen to see
+/// conversations.
+pub async fn show_message(conversation: &str, message_id: &str) -> Result<(), IpcError> {
+    let args = ShowMessageArgs {
+        conversation: conversation.to_string(),
+        message_id: message_id.to_string(),
+    };
+    call(commands::show_message, &args).await
+}
+
+pub async fn edit_message(
+    conversation: &str,
+    message_id: &str,
+    body: &str,
+) -> Result<ChatMessage, IpcError> {
+    let args = EditMessageArgs {
+        conversation: conversation.to_string(),
+        message_id: message_id.to_string(),
+        body: body.to_string(),
+    };
+    call(commands::edit_message, &args).await
+}
+
+/// Deletes a message the user sent, returning what is left of it.
+pub async fn delete_message(conversation: &str, message_id: &str) -> Result<ChatMessage, IpcError> {
+    let args = DeleteMessageArgs {
+        conversation: conversation.to_string(),
+        message_id: message_id.to_string(),
+    };
+    call(commands::delete_message, &args).await
+}
+
+/// Saves a conversation to `path`, or every conversation into the folder at `path` when
+/// `conversation` is `None`, and returns how many messages were saved.
+pub async fn export_conversation(
+    conversation: Option<&str>,
+    path: &str,
+    format: LogFormat,
+) -> Result<usize, IpcError> {
+    let args = ExportConversationArgs {
+        conversation: conversation.map(str::to_string),
+        path: path.to_string(),
+        format,
+    };
+    call(commands::export_conversation, &args).await
+}
+
+pub async fn import_msn_log(
+    path: &str,
+    conversation: &str,
+    my_names: Vec<String>,
+    dry_run: bool,
+) -> Result<LogImportPreview, IpcError> {
+    let args = ImportMsnLogArgs {
+        path: path.to_string(),
+        conversation: conversation.to_string(),
+        my_names,
+        dry_run,
+    };
+    call(commands::import_msn_log, &args).await
+}
+
+/// Calls `handler` with the payload of every backend event named `event`, until the reactive
+/// scope that registered it is cleaned up.
+pub fn listen<T, F>(event: &'static str, handler: F)
+where
+    T: DeserializeOwned,
+    F: Fn(T) + 'static,
+{
+    let handler = Closure::<dyn FnMut(JsValue)>::new(move |js_event: JsValue| {
+        let payload = Reflect::get(&js_event, &JsValue::from_str("payload")).unwrap_or_default();
+        match from_value(payload) {
+            Ok(payload) => handler(payload),
+            Err(e) => logging::warn!("Ignoring malformed {} event: {}", event, e),
+        }
+    });
+    // Holds the unlisten function along with the handler, which must stay alive until then.
+    let registration = Rc::new(RefCell::new(
+        None::<(Function, Closure<dyn FnMut(JsValue)>)>,
+    ));
+    let cleaned_up = Rc::new(Cell::new(false));
+
+    on_cleanup({
+        let registration = registration.clone();
+        let cleaned_up = cleaned_up.clone();
+        move || {
+            cleaned_up.set(true);
+            if let Some((unlisten, _)) = registration.borrow_mut().take() {
+                let _ = unlisten.call0(&JsValue::NULL);
+            }
+        }
+    });
+
+    spawn_local(async move {
+        match tauri_listen(event, &handler).await {
+            // The scope went away while registering, so stop listening straight away.
+            Ok(unlisten) if cleaned_up.get() => {
+                let _ = unlisten.unchecked_into::<Function>().call0(&JsValue::NULL);
+            }
+            Ok(unlisten) => *registration.borrow_mut() = Some((unlisten.unchecked_into(), handler)),
+            Err(e) => logging::error!("Failed to listen for {}: {:?}", event, e),
+        }
+    });
+}
@@ -1,6 +1,9 @@
 mod app;
 mod components;
+mod ipc;
+mod recorder;
 
+use components::chat_window_component::ChatWindow;
 use components::loginpage_component::LoginPage;
 use components::mainpage_component::MainPage;
 use leptos::*;
@@ -14,6 +17,7 @@ fn main() {
                 <Routes>
                     <Route path="/" view=LoginPage />
                     <Route path="/main" view=MainPage />
+                    <Route path="/chat" view=ChatWindow />
                 </Routes>
             </Router>
         }
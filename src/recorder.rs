@@ -0,0 +1,111 @@
+//! Records voice clips from the microphone.
+//!
+//! Samples are collected as they come in and handed to the backend as 16-bit PCM once the
+//! recording stops, so encoding happens in one place whatever the webview supports.
+
+use std::{cell::RefCell, rc::Rc};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use js_sys::Array;
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{
+    AudioContext, AudioProcessingEvent, MediaStream, MediaStreamConstraints, MediaStreamTrack,
+    ScriptProcessorNode,
+};
+
+/// Samples handed over by the browser at a time.
+const BUFFER_SIZE: u32 = 4096;
+
+/// A recording in progress, which stops when `stop` is called.
+pub struct Recorder {
+    context: AudioContext,
+    stream: MediaStream,
+    processor: ScriptProcessorNode,
+    samples: Rc<RefCell<Vec<f32>>>,
+    _on_audio: Closure<dyn FnMut(AudioProcessingEvent)>,
+}
+
+/// A finished recording, ready for `ipc::send_voice_clip`.
+pub struct Recording {
+    /// Mono 16-bit little-endian PCM, base64-encoded.
+    pub samples: String,
+    pub sample_rate: u32,
+}
+
+impl Recorder {
+    /// Asks for the microphone and starts recording from it.
+    pub async fn start() -> Result<Self, String> {
+        let error = |e: JsValue| e.as_string().unwrap_or_else(|| format!("{:?}", e));
+        let devices = web_sys::window()
+            .ok_or("No window")?
+            .navigator()
+            .media_devices()
+            .map_err(error)?;
+        let constraints = MediaStreamConstraints::new();
+        constraints.set_audio_bool(true);
+        let stream: MediaStream = JsFuture::from(
+            devices
+                .get_user_media_with_constraints(&constraints)
+                .map_err(error)?,
+        )
+        .await
+        .map_err(|_| "The microphone isn't available".to_string())?
+        .unchecked_into();
+
+        let context = AudioContext::new().map_err(error)?;
+        let source = context.create_media_stream_source(&stream).map_err(error)?;
+        let processor = context
+            .create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(
+                BUFFER_SIZE,
+                1,
+                1,
+            )
+            .map_err(error)?;
+        let samples = Rc::new(RefCell::new(Vec::new()));
+        let on_audio = Closure::<dyn FnMut(AudioProcessingEvent)>::new({
+            let samples = samples.clone();
+            move |event: AudioProcessingEvent| {
+                if let Ok(data) = event
+                    .input_buffer()
+                    .and_then(|buffer| buffer.get_channel_data(0))
+                {
+                    samples.borrow_mut().extend(data);
+                }
+            }
+        });
+        processor.set_onaudioprocess(Some(on_audio.as_ref().unchecked_ref()));
+        source.connect_with_audio_node(&processor).map_err(error)?;
+        // Browsers only run the processor while it is connected to an output.
+        processor
+            .connect_with_audio_node(&context.destination())
+            .map_err(error)?;
+        Ok(Self {
+            context,
+            stream,
+            processor,
+            samples,
+            _on_audio: on_audio,
+        })
+    }
+
+    /// Stops recording and releases the microphone.
+    pub fn stop(self) -> Recording {
+        self.processor.set_onaudioprocess(None);
+        let _ = self.processor.disconnect();
+        let _ = self.context.close();
+        for track in Array::from(&self.stream.get_tracks()).iter() {
+            track.unchecked_into::<MediaStreamTrack>().stop();
+        }
+        let bytes: Vec<u8> = self
+            .samples
+            .borrow()
+            .iter()
+            .flat_map(|sample| ((sample.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+            .collect();
+        Recording {
+            samples: STANDARD.encode(bytes),
+            sample_rate: self.context.sample_rate() as u32,
+        }
+    }
+}